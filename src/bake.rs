@@ -0,0 +1,292 @@
+// Baking: applying the contour operations MFEKglif stores with a glyph's contours (variable width
+// stroke, pattern along path, dash) with their saved parameters, leaving a flat, font-ready glyph.
+
+use clap::{App, AppSettings, Arg};
+use glifparser::glif::contour_operations::ContourOperations;
+use glifparser::glif::mfek::{MFEKContour, MFEKGlif};
+use glifparser::{CapType, Glif, Outline};
+use rayon::prelude::*;
+use MFEKmath::pattern_along_path_mfek;
+use MFEKmath::variable_width_stroking::{variable_width_stroke, VWSSettings};
+use MFEKmath::Piecewise;
+
+use crate::boolean;
+use crate::dash_along_path::{stroke_dash, DashOptions};
+use crate::error::catch_geometry;
+use crate::glif_io;
+use crate::preview::{self, PreviewOptions};
+use crate::remove_overlap::{self, remove_overlap_if_requested};
+use crate::svg;
+use crate::StrokeError;
+
+pub fn clap_app() -> clap::App<'static> {
+    App::new("BAKE")
+        .setting(AppSettings::DeriveDisplayOrder)
+        .alias("bake")
+        .about("Applies the contour operations (variable width stroke, pattern along path, dash) stored in a .glifjson, writing a flat glyph.")
+        .version("0.1.0")
+        .author("MFEK Authors")
+        .arg(
+            Arg::new("input")
+                .short('i')
+                .long("input")
+                .takes_value(true)
+                .allow_invalid_utf8(true)
+                .help("The path to the input .glifjson file.")
+                .required(true),
+        )
+        .arg(
+            Arg::new("output")
+                .short('o')
+                .long("output")
+                .takes_value(true)
+                .allow_invalid_utf8(true)
+                .help("The path where the output .glif (or .svg drawing) will be saved.")
+                .required(true),
+        )
+        .arg(
+            Arg::new("layer")
+                .long("layer")
+                .takes_value(true)
+                .help("<name or index> Only bake this layer. By default, every visible layer is baked, and their contours combined."),
+        )
+        .args(remove_overlap::clap_args())
+        .args(boolean::clap_args())
+        .args(svg::output_clap_args())
+        .args(preview::clap_args())
+}
+
+/// Settings for baking.
+#[derive(Debug, Clone, Default)]
+pub struct BakeOptions {
+    /// Only bake this layer, by index. If `None`, every visible layer.
+    pub layer: Option<usize>,
+}
+
+fn baked_layers(glif: &MFEKGlif<()>, options: &BakeOptions) -> Result<Vec<usize>, StrokeError> {
+    match options.layer {
+        Some(layer) if layer >= glif.layers.len() => Err(StrokeError::Usage(format!("no layer {} (there are {})", layer, glif.layers.len()))),
+        Some(layer) => Ok(vec![layer]),
+        None => Ok((0..glif.layers.len()).filter(|&l| glif.layers[l].visible).collect()),
+    }
+}
+
+fn unsupported(message: String) -> StrokeError {
+    StrokeError::Unsupported { path: None, message }
+}
+
+// Runs the operation stored with `contour`, the `index`th baked, if any; contours without one are copied
+// as they are.
+fn bake_contour(contour: &MFEKContour<()>, index: usize) -> Result<Outline<()>, StrokeError> {
+    let outline: Outline<()> = vec![glif_io::mfek_contour_to_contour(contour)?];
+
+    match contour.operation() {
+        None => Ok(outline),
+        Some(ContourOperations::VariableWidthStroke { data }) => {
+            // Custom caps aren't stored in the file, so only the built in cap types can be baked.
+            if matches!(data.cap_start_type, CapType::Custom) || matches!(data.cap_end_type, CapType::Custom) {
+                return Err(unsupported(format!(
+                    "contour {} has a variable width stroke with a custom cap, which .glifjson files don't store",
+                    index
+                )));
+            }
+            let path = Piecewise::from(&outline);
+            let settings = VWSSettings::<()> {
+                cap_custom_start: None,
+                cap_custom_end: None,
+            };
+            let stroked = catch_geometry(|| variable_width_stroke(&path.segs[0], data, &settings))?;
+            Ok(stroked.segs.iter().map(|c| c.to_contour()).collect())
+        }
+        Some(ContourOperations::PatternAlongPath { data }) => {
+            let path = Piecewise::from(&outline);
+            let patterned = catch_geometry(|| pattern_along_path_mfek(&path.segs[0], data))?;
+            patterned.iter().map(glif_io::mfek_contour_to_contour).collect()
+        }
+        Some(ContourOperations::DashAlongPath { data }) => {
+            let mut path: Glif<()> = Glif::new();
+            path.outline = Some(outline);
+            // Skia's miter limit, as MFEKglif draws the dashes with.
+            let options = DashOptions {
+                settings: data.clone(),
+                miter_limit: None,
+            };
+            let dashed = stroke_dash(&path, &options)?;
+            Ok(dashed.outline.unwrap_or_default())
+        }
+    }
+}
+
+/// Applies the operation stored with every contour of the baked layers of `glif`, returning a plain glif
+/// with all their results (and the contours without operations) combined. Contours are baked
+/// concurrently, and kept in file order.
+///
+/// Layer operations (boolean combinations of layers) can't be applied, nor variable width strokes with
+/// custom caps; either fails with `StrokeError::Unsupported`.
+pub fn bake(glif: &MFEKGlif<()>, options: &BakeOptions) -> Result<Glif<()>, StrokeError> {
+    let layers = baked_layers(glif, options)?;
+    if let Some(&layer) = layers.iter().find(|&&l| glif.layers[l].operation.is_some()) {
+        return Err(StrokeError::Unsupported {
+            path: glif.filename.clone(),
+            message: format!("layer {:?} has a layer operation, which BAKE can't apply", glif.layers[layer].name),
+        });
+    }
+    let contours: Vec<&MFEKContour<()>> = layers.into_iter().flat_map(|l| glif.layers[l].outline.iter()).collect();
+    if contours.is_empty() {
+        return Err(StrokeError::EmptyOutline { path: glif.filename.clone() });
+    }
+
+    let baked: Vec<Outline<()>> = contours
+        .par_iter()
+        .enumerate()
+        .map(|(i, c)| bake_contour(c, i))
+        .collect::<Result<_, _>>()?;
+    Ok(glif_io::glifjson_to_glif(glif, baked.into_iter().flatten().collect()))
+}
+
+// The baked layers before baking, to show with the result.
+fn unbaked(glif: &MFEKGlif<()>, options: &BakeOptions) -> Result<Glif<()>, StrokeError> {
+    let mut outline = Outline::new();
+    for layer in baked_layers(glif, options)? {
+        outline.extend(glif_io::glifjson_layer_outline(glif, layer)?);
+    }
+    Ok(glif_io::glifjson_to_glif(glif, outline))
+}
+
+pub fn bake_cli(matches: &clap::ArgMatches) -> Result<(), StrokeError> {
+    let input_file = matches.value_of_os("input").unwrap();
+    let output_file = matches.value_of_os("output").unwrap();
+
+    if !glif_io::is_glifjson(input_file) {
+        return Err(StrokeError::Usage(String::from("BAKE reads .glifjson files, which store contour operations")));
+    }
+    let glif = glif_io::read_glifjson(input_file)?;
+    let options = BakeOptions {
+        layer: match matches.value_of("layer") {
+            Some(layer) => Some(glif_io::select_layer(&glif, Some(layer))?),
+            None => None,
+        },
+    };
+    let preview = PreviewOptions::from_matches(matches)?;
+
+    let out = bake(&glif, &options)
+        .and_then(|out| remove_overlap_if_requested(matches, out))
+        .map_err(|e| e.in_file(input_file))?;
+    let skeleton = unbaked(&glif, &options)?;
+    if let Some(preview) = &preview {
+        preview::write_preview(&skeleton, &out, preview)?;
+    }
+    glif_io::write_output(&out, matches.is_present("svg-skeleton").then_some(&skeleton), output_file)
+}
+
+#[cfg(test)]
+mod tests {
+    use glifparser::glif::contour_operations::dash::DashContour;
+    use glifparser::glif::contour_operations::pap::{PAPContour, PatternCopies, PatternCulling, PatternStretch, PatternSubdivide};
+    use glifparser::glif::contour_operations::vws::{InterpolationType, VWSHandle};
+    use glifparser::{Contour, JoinType, Point, PointType, VWSContour};
+    use MFEKmath::skia_safe::{PaintCap, PaintJoin};
+
+    use super::*;
+
+    // An open, straight contour from (0, y) to (100, y).
+    fn line(y: f32) -> Contour<()> {
+        vec![Point::from_x_y_type((0., y), PointType::Move), Point::from_x_y_type((100., y), PointType::Line)]
+    }
+
+    fn vws(start_cap: CapType) -> ContourOperations<()> {
+        let handle = VWSHandle {
+            left_offset: 10.,
+            right_offset: 10.,
+            tangent_offset: 0.,
+            interpolation: InterpolationType::Linear,
+        };
+        let data = VWSContour {
+            join_type: JoinType::Round,
+            cap_start_type: start_cap,
+            cap_end_type: CapType::Square,
+            handles: vec![handle; 3],
+            remove_internal: false,
+            remove_external: false,
+        };
+        ContourOperations::VariableWidthStroke { data }
+    }
+
+    fn pap() -> ContourOperations<()> {
+        let square = [(-5., -5.), (5., -5.), (5., 5.), (-5., 5.)];
+        let mut pattern: Glif<()> = Glif::new();
+        pattern.outline = Some(vec![square.iter().map(|&p| Point::from_x_y_type(p, PointType::Line)).collect()]);
+        let data = PAPContour {
+            pattern: pattern.into(),
+            copies: PatternCopies::Repeated,
+            subdivide: PatternSubdivide::Off,
+            is_vertical: false,
+            stretch: PatternStretch::Off,
+            spacing: 10.,
+            simplify: false,
+            normal_offset: 0.,
+            tangent_offset: 0.,
+            pattern_scale: (1., 1.),
+            center_pattern: true,
+            cull_overlap: PatternCulling::Off,
+            two_pass_culling: false,
+            reverse_path: false,
+            reverse_culling: false,
+            split_path: false,
+            warp_pattern: false,
+        };
+        ContourOperations::PatternAlongPath { data }
+    }
+
+    fn dash() -> ContourOperations<()> {
+        let data = DashContour {
+            stroke_width: 10.,
+            dash_desc: vec![20., 20.],
+            include_last_path: false,
+            paint_join: PaintJoin::Miter as u8,
+            paint_cap: PaintCap::Butt as u8,
+            cull: None,
+        };
+        ContourOperations::DashAlongPath { data }
+    }
+
+    // A .glifjson of one layer: a line with each of `operations`, 100 units above the last, and one
+    // without any above them.
+    fn glifjson(operations: Vec<ContourOperations<()>>) -> MFEKGlif<()> {
+        let mut path: Glif<()> = Glif::new();
+        path.outline = Some((0..=operations.len()).map(|i| line(i as f32 * 100.)).collect());
+        let mut glif: MFEKGlif<()> = path.into();
+        for (contour, operation) in glif.layers[0].outline.iter_mut().zip(operations) {
+            contour.set_operation(Some(operation));
+        }
+        glif
+    }
+
+    // The contours of `outline` lying within `margin` of the line at height `y`.
+    fn near(outline: &Outline<()>, y: f32, margin: f32) -> usize {
+        outline.iter().filter(|c| c.iter().all(|p| (p.y - y).abs() <= margin)).count()
+    }
+
+    #[test]
+    fn bakes_each_operation() {
+        let baked = bake(&glifjson(vec![vws(CapType::Round), pap(), dash()]), &BakeOptions::default()).unwrap();
+        let outline = baked.outline.unwrap();
+
+        // The stroke is a single contour 20 units wide around its line, with its round cap.
+        assert_eq!(near(&outline, 0., 10.5), 1);
+        let stroke = outline.iter().find(|c| c.iter().all(|p| p.y.abs() <= 10.5)).unwrap();
+        assert!(stroke.iter().any(|p| p.x < -5.));
+        assert!(stroke.iter().any(|p| (p.y.abs() - 10.).abs() < 0.5));
+        // Squares along the second line, and dashes along the third.
+        assert!(near(&outline, 100., 5.5) > 2);
+        assert!(near(&outline, 200., 5.5) > 1);
+        // The last line is copied as it is.
+        assert!(outline.iter().any(|c| c.len() == 2 && c.iter().all(|p| p.y == 300.)));
+    }
+
+    #[test]
+    fn refuses_custom_caps() {
+        let result = bake(&glifjson(vec![vws(CapType::Custom)]), &BakeOptions::default());
+        assert!(matches!(result, Err(StrokeError::Unsupported { .. })), "{:?}", result);
+    }
+}
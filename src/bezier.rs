@@ -0,0 +1,430 @@
+// Bézier geometry on glif contours, in f64: segments, arc lengths, subdivision and flattening.
+
+use glifparser::{Contour, Handle, Point, PointType};
+
+pub(crate) type Pt = (f64, f64);
+
+/// A cubic Bézier: its start, its two control points and its end.
+pub(crate) type Cubic = [Pt; 4];
+
+/// How far along the tangents at its ends the control points of a cubic quarter ellipse lie, as a
+/// fraction of the radii: 4/3 · (√2 − 1), the closest a cubic gets to a quarter circle.
+pub(crate) const KAPPA: f64 = 0.552_284_749_830_793_6;
+
+/// One segment of a path, as a cubic; lines have their control points on their ends.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub(crate) struct Segment {
+    pub(crate) curve: Cubic,
+    pub(crate) line: bool,
+}
+
+impl Segment {
+    pub(crate) fn line(from: Pt, to: Pt) -> Self {
+        Segment {
+            curve: [from, from, to, to],
+            line: true,
+        }
+    }
+
+    pub(crate) fn cubic(from: Pt, c1: Pt, c2: Pt, to: Pt) -> Self {
+        Segment {
+            curve: [from, c1, c2, to],
+            line: false,
+        }
+    }
+
+    /// The segment with each of its points moved by `f`.
+    pub(crate) fn map(&self, f: impl Fn(Pt) -> Pt) -> Self {
+        Segment {
+            curve: self.curve.map(f),
+            line: self.line,
+        }
+    }
+}
+
+/// The Bézier from point `prev` of `contour` to point `cur`, as a cubic (quadratics are elevated).
+pub(crate) fn segment(contour: &Contour<()>, prev: usize, cur: usize) -> Cubic {
+    let at = |i: usize| (contour[i].x as f64, contour[i].y as f64);
+    let handle = |h: Handle, or: Pt| match h {
+        Handle::At(x, y) => (x as f64, y as f64),
+        Handle::Colocated => or,
+    };
+    let (p0, p3) = (at(prev), at(cur));
+    if contour[cur].ptype == PointType::QCurve {
+        // A quadratic's single control point, elevated to a cubic's two.
+        let c = handle(contour[prev].a, handle(contour[cur].b, p3));
+        let c1 = (p0.0 + 2. / 3. * (c.0 - p0.0), p0.1 + 2. / 3. * (c.1 - p0.1));
+        let c2 = (p3.0 + 2. / 3. * (c.0 - p3.0), p3.1 + 2. / 3. * (c.1 - p3.1));
+        [p0, c1, c2, p3]
+    } else {
+        [p0, handle(contour[prev].a, p0), handle(contour[cur].b, p3), p3]
+    }
+}
+
+pub(crate) fn is_line(contour: &Contour<()>, prev: usize, cur: usize) -> bool {
+    matches!((contour[prev].a, contour[cur].b), (Handle::Colocated, Handle::Colocated))
+}
+
+pub(crate) fn is_open(contour: &Contour<()>) -> bool {
+    contour.first().map(|p| p.ptype == PointType::Move).unwrap_or(true)
+}
+
+/// The segments of `contour` as (from, to) point indices, including the closing one of a closed contour.
+pub(crate) fn segment_ends(contour: &Contour<()>) -> impl Iterator<Item = (usize, usize)> {
+    let len = contour.len();
+    let segments = if is_open(contour) { len.saturating_sub(1) } else { len };
+    (1..=segments).map(move |i| (i - 1, i % len))
+}
+
+pub(crate) fn cubic_at([p0, c1, c2, p3]: Cubic, t: f64) -> Pt {
+    let mt = 1. - t;
+    let (a, b, c, d) = (mt * mt * mt, 3. * mt * mt * t, 3. * mt * t * t, t * t * t);
+    (a * p0.0 + b * c1.0 + c * c2.0 + d * p3.0, a * p0.1 + b * c1.1 + c * c2.1 + d * p3.1)
+}
+
+/// The derivative of a cubic Bézier at `t`.
+pub(crate) fn derivative([p0, c1, c2, p3]: Cubic, t: f64) -> Pt {
+    let mt = 1. - t;
+    let d = |a: f64, b: f64, c: f64, d: f64| 3. * (mt * mt * (b - a) + 2. * mt * t * (c - b) + t * t * (d - c));
+    (d(p0.0, c1.0, c2.0, p3.0), d(p0.1, c1.1, c2.1, p3.1))
+}
+
+/// The real roots of a·t² + b·t + c, or of b·t + c if `a` is about 0.
+pub(crate) fn quadratic_roots(a: f64, b: f64, c: f64) -> Vec<f64> {
+    if a.abs() < 1e-12 {
+        return if b.abs() < 1e-12 { vec![] } else { vec![-c / b] };
+    }
+    let discriminant = b * b - 4. * a * c;
+    if discriminant < 0. {
+        return vec![];
+    }
+    // The stable form, which doesn't subtract nearly equal numbers.
+    let q = -0.5 * (b + b.signum() * discriminant.sqrt());
+    if q == 0. {
+        vec![0.]
+    } else {
+        vec![q / a, c / q]
+    }
+}
+
+/// Length of a cubic Bézier, flattened to a polyline.
+pub(crate) fn cubic_length(cubic: Cubic) -> f64 {
+    const STEPS: usize = 32;
+    (1..=STEPS)
+        .map(|i| {
+            let (a, b) = (cubic_at(cubic, (i - 1) as f64 / STEPS as f64), cubic_at(cubic, i as f64 / STEPS as f64));
+            (b.0 - a.0).hypot(b.1 - a.1)
+        })
+        .sum()
+}
+
+/// Splits a cubic Bézier in two at `t` (de Casteljau).
+pub(crate) fn split_cubic([p0, c1, c2, p3]: Cubic, t: f64) -> (Cubic, Cubic) {
+    let lerp = |a: Pt, b: Pt| (a.0 + (b.0 - a.0) * t, a.1 + (b.1 - a.1) * t);
+    let (ab, bc, cd) = (lerp(p0, c1), lerp(c1, c2), lerp(c2, p3));
+    let (abc, bcd) = (lerp(ab, bc), lerp(bc, cd));
+    let mid = lerp(abc, bcd);
+    ([p0, ab, abc, mid], [mid, bcd, cd, p3])
+}
+
+/// The arc length from the start of `contour` to each of its points, and then to its end (which for a
+/// closed contour is its first point again).
+pub(crate) fn point_arc_lengths(contour: &Contour<()>) -> Vec<f64> {
+    let mut lengths = vec![0.];
+    let mut total = 0.;
+    for (prev, cur) in segment_ends(contour) {
+        total += cubic_length(segment(contour, prev, cur));
+        lengths.push(total);
+    }
+    if is_open(contour) {
+        lengths.push(total);
+    }
+    lengths
+}
+
+// Appends the cubic `piece` to `out`, whose last point is where it starts: sets that point's handle and
+// adds its end, which is `end` for a point of the original contour, or else a new point. A closed
+// contour's closing piece ends on the first point, which is already in.
+fn push_piece(out: &mut Contour<()>, piece: Cubic, line: bool, end: Option<&Point<()>>, closing: bool) {
+    let to_handle = |(x, y): Pt| Handle::At(x as f32, y as f32);
+    if !line {
+        out.last_mut().unwrap().a = to_handle(piece[1]);
+    }
+    let b = if line { Handle::Colocated } else { to_handle(piece[2]) };
+    if closing {
+        out[0].b = b;
+        return;
+    }
+    let mut end = match end {
+        Some(end) => end.clone(),
+        None => Point::from_x_y_type((piece[3].0 as f32, piece[3].1 as f32), if line { PointType::Line } else { PointType::Curve }),
+    };
+    end.b = b;
+    if !line && end.ptype == PointType::QCurve {
+        end.ptype = PointType::Curve;
+    }
+    out.push(end);
+}
+
+/// Splits every segment of `contour` into `pieces` segments of the same shape, so that it has more
+/// points to hang VWS handles on.
+pub(crate) fn subdivide(contour: &Contour<()>, pieces: usize) -> Contour<()> {
+    if contour.len() < 2 || pieces < 2 {
+        return contour.clone();
+    }
+    let mut out: Contour<()> = vec![contour[0].clone()];
+    for (prev, cur) in segment_ends(contour) {
+        let line = is_line(contour, prev, cur);
+        let mut rest = segment(contour, prev, cur);
+        for piece in 1..pieces {
+            let (head, tail) = split_cubic(rest, 1. / (pieces - piece + 1) as f64);
+            push_piece(&mut out, head, line, None, false);
+            rest = tail;
+        }
+        push_piece(&mut out, rest, line, Some(&contour[cur]), cur == 0);
+    }
+    if contour[0].ptype == PointType::QCurve {
+        out[0].ptype = PointType::Curve;
+    }
+    out
+}
+
+// The `t` at which the arc length of `cubic` from its start is `length`, found by bisection.
+fn t_at_length(cubic: Cubic, length: f64) -> f64 {
+    let (mut lo, mut hi) = (0., 1.);
+    for _ in 0..32 {
+        let mid = (lo + hi) / 2.;
+        if cubic_length(split_cubic(cubic, mid).0) < length {
+            lo = mid;
+        } else {
+            hi = mid;
+        }
+    }
+    (lo + hi) / 2.
+}
+
+/// Adds an on-curve point to `contour` at each of `lengths` (arc lengths from its start) where it doesn't
+/// already have one, without changing its shape.
+pub(crate) fn split_at_lengths(contour: &Contour<()>, lengths: &[f64]) -> Contour<()> {
+    const NEAR: f64 = 1e-3;
+    if contour.len() < 2 {
+        return contour.clone();
+    }
+    let point_lengths = point_arc_lengths(contour);
+    let mut out: Contour<()> = vec![contour[0].clone()];
+    for (prev, cur) in segment_ends(contour) {
+        let (from, to) = (point_lengths[prev], point_lengths[prev + 1]);
+        let mut cuts: Vec<f64> = lengths.iter().filter(|&&l| l > from + NEAR && l < to - NEAR).map(|l| l - from).collect();
+        cuts.sort_by(f64::total_cmp);
+        cuts.dedup_by(|a, b| (*a - *b).abs() < NEAR);
+
+        let line = is_line(contour, prev, cur);
+        let mut rest = segment(contour, prev, cur);
+        let mut done = 0.;
+        for cut in cuts {
+            let (head, tail) = split_cubic(rest, t_at_length(rest, cut - done));
+            push_piece(&mut out, head, line, None, false);
+            rest = tail;
+            done = cut;
+        }
+        push_piece(&mut out, rest, line, Some(&contour[cur]), cur == 0);
+    }
+    if contour[0].ptype == PointType::QCurve {
+        out[0].ptype = PointType::Curve;
+    }
+    out
+}
+
+/// Flattens `contour` to a polyline, no further than about `tolerance` from the curves. Closed contours
+/// don't repeat their first point at the end.
+pub(crate) fn flatten(contour: &Contour<()>, tolerance: f64) -> Vec<Pt> {
+    let mut points: Vec<Pt> = contour.first().map(|p| vec![(p.x as f64, p.y as f64)]).unwrap_or_default();
+    for (prev, cur) in segment_ends(contour) {
+        let cubic = segment(contour, prev, cur);
+        let steps = if is_line(contour, prev, cur) {
+            1
+        } else {
+            // The deviation of a chord from the curve is bounded by the second derivative.
+            let [p0, c1, c2, p3] = cubic;
+            let dd = |a: f64, b: f64, c: f64| (a - 2. * b + c).abs();
+            let d = (dd(p0.0, c1.0, c2.0).max(dd(c1.0, c2.0, p3.0))).hypot(dd(p0.1, c1.1, c2.1).max(dd(c1.1, c2.1, p3.1))) * 6.;
+            ((d / (8. * tolerance.max(f64::EPSILON))).sqrt().ceil() as usize).clamp(1, 1000)
+        };
+        for i in 1..=steps {
+            points.push(cubic_at(cubic, i as f64 / steps as f64));
+        }
+    }
+    if !is_open(contour) && points.len() > 1 {
+        points.pop();
+    }
+    points
+}
+
+/// A closed contour of `segments`, each starting where the one before it ends.
+pub(crate) fn closed_contour(segments: &[Segment]) -> Contour<()> {
+    let handle = |(x, y): Pt| Handle::At(x as f32, y as f32);
+    (0..segments.len())
+        .map(|i| {
+            let (segment, prev) = (&segments[i], &segments[(i + segments.len() - 1) % segments.len()]);
+            let (x, y) = segment.curve[0];
+            let mut point = Point::from_x_y_type((x as f32, y as f32), if prev.line { PointType::Line } else { PointType::Curve });
+            if !segment.line {
+                point.a = handle(segment.curve[1]);
+            }
+            if !prev.line {
+                point.b = handle(prev.curve[2]);
+            }
+            point
+        })
+        .collect()
+}
+
+/// The direction a cubic Bézier leaves its start in (not normalized), skipping handles on their point.
+pub(crate) fn start_direction([p0, c1, c2, p3]: Cubic) -> Pt {
+    [c1, c2, p3]
+        .into_iter()
+        .map(|p| (p.0 - p0.0, p.1 - p0.1))
+        .find(|d| d.0 != 0. || d.1 != 0.)
+        .unwrap_or((0., 0.))
+}
+
+/// The direction a cubic Bézier arrives at its end in (not normalized).
+pub(crate) fn end_direction([p0, c1, c2, p3]: Cubic) -> Pt {
+    let (d0, d1) = start_direction([p3, c2, c1, p0]);
+    (-d0, -d1)
+}
+
+pub(crate) fn unit((x, y): Pt) -> Option<Pt> {
+    let length = x.hypot(y);
+    (length > f64::EPSILON).then(|| (x / length, y / length))
+}
+
+/// A point where a contour changes direction.
+pub(crate) struct Corner {
+    pub at: Pt,
+    /// The index of the point of the contour it is at.
+    pub point: usize,
+    /// The unit direction the contour arrives in.
+    pub arrive: Pt,
+    /// The unit direction the contour leaves in.
+    pub leave: Pt,
+    /// The angle it turns by, in radians, positive to the left.
+    pub turn: f64,
+}
+
+/// The corners of `contour`: its on-curve points, but for the ends of an open contour, where it turns
+/// by more than `min_turn` radians.
+pub(crate) fn corners(contour: &Contour<()>, min_turn: f64) -> Vec<Corner> {
+    let segments: Vec<Cubic> = segment_ends(contour).map(|(prev, cur)| segment(contour, prev, cur)).collect();
+    let open = is_open(contour);
+    // Each segment with the one before it, around the point it starts at.
+    segments
+        .iter()
+        .enumerate()
+        .filter_map(|(i, leaving)| {
+            let arriving = match (i, open) {
+                (0, true) => return None,
+                (0, false) => segments[segments.len() - 1],
+                _ => segments[i - 1],
+            };
+            let (arrive, leave) = (unit(end_direction(arriving))?, unit(start_direction(*leaving))?);
+            let cross = arrive.0 * leave.1 - arrive.1 * leave.0;
+            let dot = arrive.0 * leave.0 + arrive.1 * leave.1;
+            let turn = cross.atan2(dot);
+            (turn.abs() > min_turn).then_some(Corner {
+                at: leaving[0],
+                point: i,
+                arrive,
+                leave,
+                turn,
+            })
+        })
+        .collect()
+}
+
+/// The signed area of a closed polygon: positive if it runs counterclockwise (with y up, as in a glif).
+pub(crate) fn signed_area(polygon: &[Pt]) -> f64 {
+    let n = polygon.len();
+    (0..n)
+        .map(|i| {
+            let (a, b) = (polygon[i], polygon[(i + 1) % n]);
+            a.0 * b.1 - b.0 * a.1
+        })
+        .sum::<f64>()
+        / 2.
+}
+
+/// Whether `p` is inside the closed `polygon`, by the even-odd rule.
+pub(crate) fn polygon_contains(polygon: &[Pt], p: Pt) -> bool {
+    let n = polygon.len();
+    let mut inside = false;
+    for i in 0..n {
+        let (a, b) = (polygon[i], polygon[(i + 1) % n]);
+        if (a.1 > p.1) != (b.1 > p.1) && p.0 < a.0 + (p.1 - a.1) / (b.1 - a.1) * (b.0 - a.0) {
+            inside = !inside;
+        }
+    }
+    inside
+}
+
+/// Whether each contour of `outline` bounds filled area on its inside: an outer contour (or the outside
+/// of a counter's counter), rather than a counter, judged by how many other closed contours it's inside
+/// of, whichever way its points run. Open contours bound nothing, and are `false`.
+pub(crate) fn fills_inside(outline: &[Contour<()>], tolerance: f64) -> Vec<bool> {
+    let polygons: Vec<Option<Vec<Pt>>> = outline
+        .iter()
+        .map(|contour| (!is_open(contour) && contour.len() > 1).then(|| flatten(contour, tolerance)))
+        .collect();
+    polygons
+        .iter()
+        .enumerate()
+        .map(|(i, polygon)| {
+            let point = match polygon {
+                Some(polygon) if !polygon.is_empty() => polygon[0],
+                _ => return false,
+            };
+            let depth = polygons
+                .iter()
+                .enumerate()
+                .filter(|&(j, other)| j != i && other.as_ref().map(|other| polygon_contains(other, point)).unwrap_or(false))
+                .count();
+            depth % 2 == 0
+        })
+        .collect()
+}
+
+/// The closed `contour` running the other way, from the same first point.
+pub(crate) fn reversed(contour: &Contour<()>) -> Contour<()> {
+    let len = contour.len();
+    (0..len)
+        .map(|k| {
+            let point = &contour[(len - k) % len];
+            // A point's type is that of the segment arriving at it, which now comes from the other side.
+            Point {
+                a: point.b,
+                b: point.a,
+                ptype: contour[(len - k + 1) % len].ptype,
+                ..point.clone()
+            }
+        })
+        .collect()
+}
+
+/// The box around the points and handles of `outline`, which holds its curves, as (min, max); `None` if
+/// it has no points.
+pub(crate) fn bounds(outline: &[Contour<()>]) -> Option<(Pt, Pt)> {
+    let handles = |p: &Point<()>| {
+        [p.a, p.b].into_iter().filter_map(|h| match h {
+            Handle::At(x, y) => Some((x as f64, y as f64)),
+            Handle::Colocated => None,
+        })
+    };
+    outline
+        .iter()
+        .flatten()
+        .flat_map(|p| std::iter::once((p.x as f64, p.y as f64)).chain(handles(p)))
+        .fold(None, |bounds, (x, y)| match bounds {
+            None => Some(((x, y), (x, y))),
+            Some(((x0, y0), (x1, y1))) => Some(((x0.min(x), y0.min(y)), (x1.max(x), y1.max(y)))),
+        })
+}
@@ -0,0 +1,1004 @@
+// Boolean operations on the areas outlines fill (union, difference, intersection and exclusive or),
+// done on their Béziers themselves, in f64. Skia's path ops work in f32, and fail on curves which nearly
+// coincide, as the copies of a pattern and the rails of strokes often do, sometimes by returning nothing
+// at all.
+//
+// Every segment of both outlines is split where it turns in x or y, so that each piece of it is
+// monotonic, then wherever it meets another. Points closer than the tolerance are merged, so the pieces
+// make a graph, and a piece is on the result's boundary if the result's area is on one side of it but not
+// the other, as judged by the winding numbers of points just to its left and right. The pieces kept are
+// turned to have the area on their left, so outer contours run counterclockwise and counters clockwise,
+// and chained into contours, joining again the pieces of a segment which nothing cut.
+
+use std::collections::HashMap;
+
+use clap::{Arg, ArgMatches};
+use glifparser::{Contour, Handle, Outline, Point, PointType};
+use MFEKmath::{Bezier, Piecewise};
+
+use crate::bezier::{self, Cubic, Pt};
+use crate::error::catch_geometry;
+use crate::skia_outline;
+use crate::StrokeError;
+
+pub fn clap_args() -> Vec<Arg<'static>> {
+    vec![Arg::new("boolean-tolerance")
+        .long("boolean-tolerance")
+        .takes_value(true)
+        .validator(super::arg_validator_positive_f64)
+        .default_value("0.01")
+        .help("<f64> How close, in font units, points must be to be merged when uniting or cutting contours.")]
+}
+
+/// A boolean operation on the areas two outlines fill.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BooleanOp {
+    Union,
+    /// The area of the first outline outside the second.
+    Difference,
+    Intersection,
+    /// The area filled by one outline or the other, but not both.
+    Xor,
+}
+
+impl BooleanOp {
+    fn keeps(self, a: bool, b: bool) -> bool {
+        match self {
+            BooleanOp::Union => a || b,
+            BooleanOp::Difference => a && !b,
+            BooleanOp::Intersection => a && b,
+            BooleanOp::Xor => a != b,
+        }
+    }
+}
+
+/// How the area an outline fills is found from its contours.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FillRule {
+    /// Wherever the contours wind around a point, in total, other than zero times, as in fonts.
+    NonZero,
+    /// Wherever a point is inside an odd number of contours.
+    EvenOdd,
+}
+
+/// Settings for boolean operations.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct BooleanOptions {
+    /// How close, in font units, points must be to be merged, and curves to be taken as meeting.
+    pub tolerance: f64,
+    pub fill_rule: FillRule,
+    /// How many times a failed operation is tried again, with ten times the tolerance each time, before
+    /// falling back to Skia.
+    pub retries: usize,
+}
+
+impl Default for BooleanOptions {
+    fn default() -> Self {
+        BooleanOptions {
+            tolerance: 0.01,
+            fill_rule: FillRule::NonZero,
+            retries: 2,
+        }
+    }
+}
+
+impl BooleanOptions {
+    /// The default options, with the tolerance given with --boolean-tolerance.
+    pub fn from_matches(matches: &ArgMatches) -> Self {
+        BooleanOptions {
+            tolerance: matches.value_of("boolean-tolerance").unwrap().parse().unwrap(), // validated by arg_validator_positive_f64
+            ..Self::default()
+        }
+    }
+}
+
+// How many times the crossings of two pieces may be narrowed down to boxes within the tolerance, or to
+// flat pieces, before the search stops with the crossings found so far.
+const MAX_LEAVES: usize = 256;
+const MAX_DEPTH: usize = 48;
+
+fn lerp(a: Pt, b: Pt, t: f64) -> Pt {
+    (a.0 + (b.0 - a.0) * t, a.1 + (b.1 - a.1) * t)
+}
+
+fn distance(a: Pt, b: Pt) -> f64 {
+    (a.0 - b.0).hypot(a.1 - b.1)
+}
+
+// The part of `curve` from `t0` to `t1`, reversed if `t0` is after `t1`.
+fn sub_curve(curve: Cubic, t0: f64, t1: f64) -> Cubic {
+    if t0 > t1 {
+        let mut reversed = sub_curve(curve, t1, t0);
+        reversed.reverse();
+        return reversed;
+    }
+    if t1 <= 0. {
+        return [curve[0]; 4];
+    }
+    let (left, _) = bezier::split_cubic(curve, t1.min(1.));
+    bezier::split_cubic(left, t0 / t1.min(1.)).1
+}
+
+// `curve`, moved to start at `p0` and end at `p3`, its handles moved with its ends.
+fn placed(curve: Cubic, line: bool, p0: Pt, p3: Pt) -> Cubic {
+    if line {
+        return [p0, lerp(p0, p3, 1. / 3.), lerp(p0, p3, 2. / 3.), p3];
+    }
+    let shift = |p: Pt, from: Pt, to: Pt| (p.0 + to.0 - from.0, p.1 + to.1 - from.1);
+    [p0, shift(curve[1], curve[0], p0), shift(curve[2], curve[3], p3), p3]
+}
+
+// Where `curve` turns in x or in y.
+fn extrema(curve: Cubic) -> Vec<f64> {
+    let axes: [fn(Pt) -> f64; 2] = [|p| p.0, |p| p.1];
+    let mut ts: Vec<f64> = axes
+        .into_iter()
+        .flat_map(|axis| {
+            let [p0, p1, p2, p3] = curve.map(axis);
+            bezier::quadratic_roots(-p0 + 3. * p1 - 3. * p2 + p3, 2. * (p0 - 2. * p1 + p2), p1 - p0)
+        })
+        .filter(|&t| t > 1e-9 && t < 1. - 1e-9)
+        .collect();
+    ts.sort_by(f64::total_cmp);
+    ts.dedup_by(|a, b| (*a - *b).abs() < 1e-9);
+    ts
+}
+
+#[derive(Debug, Clone, Copy)]
+struct Rect {
+    min: Pt,
+    max: Pt,
+}
+
+impl Rect {
+    fn of(points: impl IntoIterator<Item = Pt>) -> Rect {
+        points.into_iter().fold(
+            Rect {
+                min: (f64::MAX, f64::MAX),
+                max: (f64::MIN, f64::MIN),
+            },
+            |r, p| Rect {
+                min: (r.min.0.min(p.0), r.min.1.min(p.1)),
+                max: (r.max.0.max(p.0), r.max.1.max(p.1)),
+            },
+        )
+    }
+
+    fn overlaps(&self, other: &Rect, margin: f64) -> bool {
+        self.min.0 <= other.max.0 + margin
+            && other.min.0 <= self.max.0 + margin
+            && self.min.1 <= other.max.1 + margin
+            && other.min.1 <= self.max.1 + margin
+    }
+
+    fn size(&self) -> f64 {
+        (self.max.0 - self.min.0).max(self.max.1 - self.min.1)
+    }
+}
+
+// A segment of one of the two outlines. Lines are cubics with their handles a third of the way along.
+struct Segment {
+    curve: Cubic,
+    line: bool,
+    operand: usize,
+}
+
+// The part of a segment from `t0` to `t1`, over which it's monotonic in x and in y.
+struct Piece {
+    curve: Cubic,
+    bounds: Rect,
+    segment: usize,
+    t0: f64,
+    t1: f64,
+}
+
+impl Piece {
+    // Where `t` along the piece is along its segment; its ends are exactly the segment's parameters.
+    fn segment_t(&self, t: f64) -> f64 {
+        if t <= 0. {
+            self.t0
+        } else if t >= 1. {
+            self.t1
+        } else {
+            self.t0 + (self.t1 - self.t0) * t
+        }
+    }
+}
+
+fn segments_of(outline: &Outline<()>, operand: usize, tolerance: f64) -> Vec<Segment> {
+    outline
+        .iter()
+        .filter(|contour| contour.len() > 1 && !bezier::is_open(contour))
+        .flat_map(|contour| {
+            bezier::segment_ends(contour).map(move |(prev, cur)| {
+                let curve = bezier::segment(contour, prev, cur);
+                let line = bezier::is_line(contour, prev, cur) && contour[cur].ptype != PointType::QCurve;
+                Segment {
+                    curve: placed(curve, line, curve[0], curve[3]),
+                    line,
+                    operand,
+                }
+            })
+        })
+        .filter(|segment| Rect::of(segment.curve).size() > tolerance / 4.)
+        .collect()
+}
+
+fn pieces(segments: &[Segment]) -> Vec<Piece> {
+    segments
+        .iter()
+        .enumerate()
+        .flat_map(|(s, segment)| {
+            let mut ts = vec![0.];
+            ts.extend(extrema(segment.curve));
+            ts.push(1.);
+            ts.windows(2)
+                .map(|t| {
+                    let curve = sub_curve(segment.curve, t[0], t[1]);
+                    Piece {
+                        curve,
+                        bounds: Rect::of(curve),
+                        segment: s,
+                        t0: t[0],
+                        t1: t[1],
+                    }
+                })
+                .collect::<Vec<_>>()
+        })
+        .collect()
+}
+
+// Whether `curve` is within `tolerance` of the line between its ends.
+fn flat(curve: Cubic, tolerance: f64) -> bool {
+    let [p0, p1, p2, p3] = curve;
+    let (dx, dy) = (p3.0 - p0.0, p3.1 - p0.1);
+    let length = dx.hypot(dy);
+    let off = |p: Pt| {
+        if length == 0. {
+            distance(p, p0)
+        } else {
+            ((p.0 - p0.0) * dy - (p.1 - p0.1) * dx).abs() / length
+        }
+    };
+    off(p1) <= tolerance && off(p2) <= tolerance
+}
+
+// Where the lines from `a0` to `a1` and from `b0` to `b1` cross, as parameters along each. Parallel lines
+// don't cross.
+fn line_crossing(a0: Pt, a1: Pt, b0: Pt, b1: Pt) -> Option<(f64, f64)> {
+    let (r, w) = ((a1.0 - a0.0, a1.1 - a0.1), (b1.0 - b0.0, b1.1 - b0.1));
+    let denominator = r.0 * w.1 - r.1 * w.0;
+    if denominator.abs() <= f64::EPSILON * r.0.hypot(r.1) * w.0.hypot(w.1) {
+        return None;
+    }
+    let q = (b0.0 - a0.0, b0.1 - a0.1);
+    let (s, u) = ((q.0 * w.1 - q.1 * w.0) / denominator, (q.0 * r.1 - q.1 * r.0) / denominator);
+    ((0. ..=1.).contains(&s) && (0. ..=1.).contains(&u)).then_some((s, u))
+}
+
+// The crossings found so far by `subdivide_crossings`, and how many more leaves it may reach.
+struct Search {
+    leaf: f64,
+    hits: Vec<(f64, f64)>,
+    budget: usize,
+}
+
+// Narrows down where `a` and `b` cross to boxes of at most the leaf size, or to pieces flat within it
+// whose lines cross, keeping their parameters.
+fn subdivide_crossings(a: Cubic, ar: (f64, f64), b: Cubic, br: (f64, f64), depth: usize, search: &mut Search) {
+    if search.budget == 0 {
+        return;
+    }
+    let (a_box, b_box) = (Rect::of(a), Rect::of(b));
+    if !a_box.overlaps(&b_box, 0.) {
+        return;
+    }
+    let leaf = search.leaf;
+    if (a_box.size() <= leaf && b_box.size() <= leaf) || depth >= MAX_DEPTH {
+        search.budget -= 1;
+        search.hits.push(((ar.0 + ar.1) / 2., (br.0 + br.1) / 2.));
+        return;
+    }
+    if flat(a, leaf) && flat(b, leaf) {
+        search.budget -= 1;
+        if let Some((s, u)) = line_crossing(a[0], a[3], b[0], b[3]) {
+            search.hits.push((ar.0 + (ar.1 - ar.0) * s, br.0 + (br.1 - br.0) * u));
+        }
+        return;
+    }
+    if a_box.size() >= b_box.size() {
+        let (left, right) = bezier::split_cubic(a, 0.5);
+        let mid = (ar.0 + ar.1) / 2.;
+        subdivide_crossings(left, (ar.0, mid), b, br, depth + 1, search);
+        subdivide_crossings(right, (mid, ar.1), b, br, depth + 1, search);
+    } else {
+        let (left, right) = bezier::split_cubic(b, 0.5);
+        let mid = (br.0 + br.1) / 2.;
+        subdivide_crossings(a, ar, left, (br.0, mid), depth + 1, search);
+        subdivide_crossings(a, ar, right, (mid, br.1), depth + 1, search);
+    }
+}
+
+// The stretch of `a`, as parameters, along which `b` lies on it. Pieces only coincide between their
+// ends, so the stretch runs between ends of either lying on the other.
+fn coincidence(a: Cubic, b: Cubic, tolerance: f64) -> Option<(f64, f64)> {
+    const SAMPLES: usize = 8;
+    let mut ends: Vec<f64> = [(0., a[0]), (1., a[3])]
+        .into_iter()
+        .filter(|&(_, p)| nearest(b, p).1 <= tolerance)
+        .map(|(t, _)| t)
+        .collect();
+    ends.extend(
+        [b[0], b[3]]
+            .into_iter()
+            .map(|p| nearest(a, p))
+            .filter(|&(_, d)| d <= tolerance)
+            .map(|(t, _)| t),
+    );
+    let t0 = ends.iter().copied().reduce(f64::min)?;
+    let t1 = ends.iter().copied().reduce(f64::max)?;
+    if distance(bezier::cubic_at(a, t0), bezier::cubic_at(a, t1)) <= tolerance {
+        return None;
+    }
+    (1..SAMPLES)
+        .map(|i| t0 + (t1 - t0) * i as f64 / SAMPLES as f64)
+        .all(|t| nearest(b, bezier::cubic_at(a, t)).1 <= tolerance)
+        .then_some((t0, t1))
+}
+
+// The parameters on `a` and `b` where they cross. Where they coincide for a stretch they don't cross; the
+// ends of the stretch are found as the ends of pieces lying on another, by `nearest`.
+fn crossings(a: Cubic, b: Cubic, tolerance: f64) -> Vec<(f64, f64)> {
+    let ranges = match coincidence(a, b, tolerance) {
+        Some((t0, t1)) => vec![(0., t0), (t1, 1.)],
+        None => vec![(0., 1.)],
+    };
+    let mut search = Search {
+        leaf: tolerance / 4.,
+        hits: vec![],
+        budget: MAX_LEAVES,
+    };
+    for (t0, t1) in ranges.into_iter().filter(|(t0, t1)| t1 > t0) {
+        subdivide_crossings(sub_curve(a, t0, t1), (t0, t1), b, (0., 1.), 0, &mut search);
+    }
+    let mut hits = search.hits;
+    if search.budget == 0 {
+        log::debug!(
+            "Stopped looking for crossings after {} pieces, keeping the {} found",
+            MAX_LEAVES,
+            hits.len()
+        );
+    }
+
+    // Neighbouring boxes around each crossing are averaged into one. Where the curves cross at a shallow
+    // angle, where along them is poorly defined, so the crossing on `b` is taken as its point nearest that
+    // on `a`, for both to be cut at the same vertex.
+    hits.sort_by(|x, y| x.0.total_cmp(&y.0));
+    let mut groups: Vec<Vec<(f64, f64)>> = vec![];
+    for hit in hits {
+        let at = bezier::cubic_at(a, hit.0);
+        let near_last = |group: &Vec<(f64, f64)>| distance(bezier::cubic_at(a, group[group.len() - 1].0), at) <= tolerance;
+        match groups.last_mut() {
+            Some(group) if near_last(group) => group.push(hit),
+            _ => groups.push(vec![hit]),
+        }
+    }
+    groups
+        .into_iter()
+        .map(|group| {
+            let ta = group.iter().map(|&(t, _)| t).sum::<f64>() / group.len() as f64;
+            (ta, nearest(b, bezier::cubic_at(a, ta)).0)
+        })
+        .collect()
+}
+
+// The parameter of the point of `curve` nearest `p`, and how far it is.
+fn nearest(curve: Cubic, p: Pt) -> (f64, f64) {
+    const SAMPLES: usize = 32;
+    let distance_at = |t: f64| distance(bezier::cubic_at(curve, t), p);
+    let best = (0..=SAMPLES).map(|i| i as f64 / SAMPLES as f64).min_by(|&a, &b| distance_at(a).total_cmp(&distance_at(b))).unwrap();
+
+    // Golden section search, around the nearest sample.
+    let ratio = (5f64.sqrt() - 1.) / 2.;
+    let (mut lo, mut hi) = ((best - 1. / SAMPLES as f64).max(0.), (best + 1. / SAMPLES as f64).min(1.));
+    for _ in 0..40 {
+        let (x1, x2) = (hi - ratio * (hi - lo), lo + ratio * (hi - lo));
+        if distance_at(x1) < distance_at(x2) {
+            hi = x2;
+        } else {
+            lo = x1;
+        }
+    }
+    let t = (lo + hi) / 2.;
+    (t, distance_at(t))
+}
+
+// The points where pieces are cut, kept once each within the tolerance.
+struct Vertices {
+    points: Vec<Pt>,
+    grid: HashMap<(i64, i64), Vec<usize>>,
+    tolerance: f64,
+}
+
+impl Vertices {
+    fn new(tolerance: f64) -> Self {
+        Vertices {
+            points: vec![],
+            grid: HashMap::new(),
+            tolerance,
+        }
+    }
+
+    fn cell(&self, p: Pt) -> (i64, i64) {
+        ((p.0 / self.tolerance).floor() as i64, (p.1 / self.tolerance).floor() as i64)
+    }
+
+    // The vertex at `p`, or within the tolerance of it.
+    fn at(&mut self, p: Pt) -> usize {
+        let (cx, cy) = self.cell(p);
+        for x in cx - 1..=cx + 1 {
+            for y in cy - 1..=cy + 1 {
+                let near = self.grid.get(&(x, y)).and_then(|ids| ids.iter().find(|&&id| distance(self.points[id], p) <= self.tolerance));
+                if let Some(&id) = near {
+                    return id;
+                }
+            }
+        }
+        self.points.push(p);
+        self.grid.entry((cx, cy)).or_default().push(self.points.len() - 1);
+        self.points.len() - 1
+    }
+}
+
+// A piece of a segment between two vertices, which nothing else crosses.
+#[derive(Debug, Clone)]
+struct Edge {
+    from: usize,
+    to: usize,
+    curve: Cubic,
+    line: bool,
+    segment: usize,
+    s0: f64,
+    s1: f64,
+}
+
+impl Edge {
+    fn reversed(&self) -> Edge {
+        let mut curve = self.curve;
+        curve.reverse();
+        Edge {
+            from: self.to,
+            to: self.from,
+            curve,
+            s0: self.s1,
+            s1: self.s0,
+            ..self.clone()
+        }
+    }
+}
+
+// Cuts every piece wherever another crosses it, or where an end of another lies on it.
+fn edges(pieces: &[Piece], segments: &[Segment], vertices: &mut Vertices, tolerance: f64) -> Vec<Edge> {
+    let mut cuts: Vec<Vec<f64>> = vec![vec![]; pieces.len()];
+
+    // Sweeping left to right, only pieces whose boxes overlap in x are compared.
+    let mut order: Vec<usize> = (0..pieces.len()).collect();
+    order.sort_by(|&i, &j| pieces[i].bounds.min.0.total_cmp(&pieces[j].bounds.min.0));
+    for (k, &i) in order.iter().enumerate() {
+        for &j in order[k + 1..].iter().take_while(|&&j| pieces[j].bounds.min.0 <= pieces[i].bounds.max.0 + tolerance) {
+            let (a, b) = (&pieces[i], &pieces[j]);
+            if !a.bounds.overlaps(&b.bounds, tolerance) {
+                continue;
+            }
+            for (ta, tb) in crossings(a.curve, b.curve, tolerance) {
+                cuts[i].push(ta);
+                cuts[j].push(tb);
+            }
+            for (end, other, on) in [(a.curve[0], j, b), (a.curve[3], j, b), (b.curve[0], i, a), (b.curve[3], i, a)] {
+                let (t, d) = nearest(on.curve, end);
+                if d <= tolerance {
+                    cuts[other].push(t);
+                }
+            }
+        }
+    }
+
+    let mut edges: Vec<Edge> = vec![];
+    let mut between: HashMap<(usize, usize), Vec<usize>> = HashMap::new();
+    for (piece, mut ts) in pieces.iter().zip(cuts) {
+        // Cuts at the piece's ends, or on top of each other, would make edges of no length.
+        ts.retain(|&t| {
+            let p = bezier::cubic_at(piece.curve, t);
+            distance(p, piece.curve[0]) > tolerance && distance(p, piece.curve[3]) > tolerance
+        });
+        ts.sort_by(f64::total_cmp);
+        ts.dedup_by(|a, b| distance(bezier::cubic_at(piece.curve, *a), bezier::cubic_at(piece.curve, *b)) <= tolerance);
+
+        let mut bounds = vec![0.];
+        bounds.extend(ts);
+        bounds.push(1.);
+        let line = segments[piece.segment].line;
+        for t in bounds.windows(2) {
+            let curve = sub_curve(piece.curve, t[0], t[1]);
+            let (from, to) = (vertices.at(curve[0]), vertices.at(curve[3]));
+            if from == to {
+                continue;
+            }
+            let edge = Edge {
+                from,
+                to,
+                curve: placed(curve, line, vertices.points[from], vertices.points[to]),
+                line,
+                segment: piece.segment,
+                s0: piece.segment_t(t[0]),
+                s1: piece.segment_t(t[1]),
+            };
+            // Where the outlines coincide, they'd give the same edge twice.
+            let key = (from.min(to), from.max(to));
+            let mid = bezier::cubic_at(edge.curve, 0.5);
+            let same = between
+                .get(&key)
+                .map(|ids| ids.iter().any(|&id| nearest(edges[id].curve, mid).1 <= 2. * tolerance))
+                .unwrap_or(false);
+            if !same {
+                between.entry(key).or_default().push(edges.len());
+                edges.push(edge);
+            }
+        }
+    }
+    edges
+}
+
+// How many times `pieces` wind around `p`, counting those crossing a ray from it to the right.
+fn winding<'a>(pieces: impl Iterator<Item = &'a Piece>, p: Pt) -> i32 {
+    pieces
+        .filter(|piece| piece.bounds.max.0 > p.0)
+        .map(|piece| {
+            let [start, .., end] = piece.curve;
+            // Each piece is half open, so that a ray through the point between two counts once.
+            if (start.1 <= p.1) == (end.1 <= p.1) {
+                return 0;
+            }
+            let rising = end.1 > start.1;
+            let x = if piece.bounds.min.0 > p.0 {
+                piece.bounds.min.0
+            } else {
+                // The piece is monotonic in y, so there's one t where it's at p's height.
+                let (mut lo, mut hi) = (0., 1.);
+                for _ in 0..60 {
+                    let mid = (lo + hi) / 2.;
+                    if (bezier::cubic_at(piece.curve, mid).1 < p.1) == rising {
+                        lo = mid;
+                    } else {
+                        hi = mid;
+                    }
+                }
+                bezier::cubic_at(piece.curve, (lo + hi) / 2.).0
+            };
+            match (x > p.0, rising) {
+                (false, _) => 0,
+                (true, true) => 1,
+                (true, false) => -1,
+            }
+        })
+        .sum()
+}
+
+// The pieces of an operand, sorted into horizontal bands by the heights they span, so that the winding
+// around a point need only look at the pieces level with it, rather than at all of them.
+struct Bands<'a> {
+    pieces: Vec<&'a Piece>,
+    min: f64,
+    height: f64,
+    bands: Vec<Vec<usize>>,
+}
+
+impl<'a> Bands<'a> {
+    fn new(pieces: Vec<&'a Piece>) -> Self {
+        let count = ((pieces.len() as f64).sqrt().ceil() as usize).max(1);
+        let (min, max) = pieces
+            .iter()
+            .fold((f64::MAX, f64::MIN), |(min, max), piece| (min.min(piece.bounds.min.1), max.max(piece.bounds.max.1)));
+        let height = if max > min { (max - min) / count as f64 } else { 1. };
+        let mut ret = Bands {
+            pieces,
+            min,
+            height,
+            bands: vec![vec![]; count],
+        };
+        for (i, piece) in ret.pieces.iter().enumerate() {
+            for band in ret.band(piece.bounds.min.1)..=ret.band(piece.bounds.max.1) {
+                ret.bands[band].push(i);
+            }
+        }
+        ret
+    }
+
+    fn band(&self, y: f64) -> usize {
+        (((y - self.min) / self.height).floor().max(0.) as usize).min(self.bands.len() - 1)
+    }
+
+    fn winding(&self, p: Pt) -> i32 {
+        winding(self.bands[self.band(p.1)].iter().map(|&i| self.pieces[i]), p)
+    }
+}
+
+fn filled(winding: i32, rule: FillRule) -> bool {
+    match rule {
+        FillRule::NonZero => winding != 0,
+        FillRule::EvenOdd => winding % 2 != 0,
+    }
+}
+
+// The edges of the result, among `edges`, turned to have its area on their left. Fails if the result
+// has area, but no edges were kept.
+fn classify(
+    edges: Vec<Edge>,
+    pieces: &[Piece],
+    segments: &[Segment],
+    op: BooleanOp,
+    options: &BooleanOptions,
+    tolerance: f64,
+) -> Result<Vec<Edge>, String> {
+    let operands: Vec<Bands> = (0..2).map(|k| Bands::new(pieces.iter().filter(|p| segments[p.segment].operand == k).collect())).collect();
+    let kept_at = |p: Pt| {
+        let (in_a, in_b) = (operands[0].winding(p), operands[1].winding(p));
+        op.keeps(filled(in_a, options.fill_rule), filled(in_b, options.fill_rule))
+    };
+
+    let mut any_area = false;
+    let mut kept = vec![];
+    for edge in edges {
+        let direction = bezier::derivative(edge.curve, 0.5);
+        let chord = (edge.curve[3].0 - edge.curve[0].0, edge.curve[3].1 - edge.curve[0].1);
+        let direction = bezier::unit(direction).or_else(|| bezier::unit(chord));
+        let (dx, dy) = match direction {
+            Some(direction) => direction,
+            None => continue,
+        };
+        let mid = bezier::cubic_at(edge.curve, 0.5);
+        let step = 2. * tolerance;
+        let (left, right) = (kept_at((mid.0 - dy * step, mid.1 + dx * step)), kept_at((mid.0 + dy * step, mid.1 - dx * step)));
+        any_area |= left || right;
+        match (left, right) {
+            (true, false) => kept.push(edge),
+            (false, true) => kept.push(edge.reversed()),
+            _ => {}
+        }
+    }
+    if kept.is_empty() && any_area {
+        return Err(String::from("the result has area, but no edges were found around it"));
+    }
+    Ok(kept)
+}
+
+// How far `to` turns from `from`, in radians, positive to the left.
+fn turn(from: Pt, to: Pt) -> f64 {
+    (from.0 * to.1 - from.1 * to.0).atan2(from.0 * to.0 + from.1 * to.1)
+}
+
+// Chains `edges` into closed contours. Where several leave a vertex, the one turning furthest left is
+// taken, which keeps contours which touch at a point apart.
+fn chain(edges: &[Edge]) -> Result<Vec<Vec<usize>>, String> {
+    let mut leaving: HashMap<usize, Vec<usize>> = HashMap::new();
+    for (i, edge) in edges.iter().enumerate() {
+        leaving.entry(edge.from).or_default().push(i);
+    }
+
+    let mut used = vec![false; edges.len()];
+    let mut chains = vec![];
+    for start in 0..edges.len() {
+        if used[start] {
+            continue;
+        }
+        used[start] = true;
+        let mut chain = vec![start];
+        let mut current = start;
+        while edges[current].to != edges[start].from {
+            let arriving = bezier::end_direction(edges[current].curve);
+            let next = leaving
+                .get(&edges[current].to)
+                .into_iter()
+                .flatten()
+                .copied()
+                .filter(|&e| !used[e])
+                .max_by(|&a, &b| {
+                    let turn_to = |e: usize| turn(arriving, bezier::start_direction(edges[e].curve));
+                    turn_to(a).total_cmp(&turn_to(b))
+                })
+                .ok_or_else(|| String::from("a contour of the result didn't close"))?;
+            used[next] = true;
+            chain.push(next);
+            current = next;
+        }
+        chains.push(chain);
+    }
+    Ok(chains)
+}
+
+// Joins again the consecutive edges of `chain` which are of the same segment, where nothing else meets
+// them (`simple` vertices have a single edge arriving and a single one leaving).
+fn rejoined(chain: &[usize], edges: &[Edge], segments: &[Segment], vertices: &Vertices, simple: &[bool]) -> Vec<Edge> {
+    let joinable = |a: &Edge, b: &Edge| {
+        a.segment == b.segment && a.s1 == b.s0 && simple[a.to] && (a.s1 - a.s0).signum() == (b.s1 - b.s0).signum()
+    };
+    let join = |a: &Edge, b: &Edge| {
+        let segment = &segments[a.segment];
+        Edge {
+            to: b.to,
+            curve: placed(sub_curve(segment.curve, a.s0, b.s1), segment.line, vertices.points[a.from], vertices.points[b.to]),
+            s1: b.s1,
+            ..a.clone()
+        }
+    };
+
+    let mut joined: Vec<Edge> = vec![];
+    for edge in chain.iter().map(|&e| &edges[e]) {
+        match joined.last_mut() {
+            Some(last) if joinable(last, edge) => *last = join(last, edge),
+            _ => joined.push(edge.clone()),
+        }
+    }
+    if joined.len() > 2 && joinable(&joined[joined.len() - 1], &joined[0]) {
+        let last = joined.pop().unwrap();
+        joined[0] = join(&last, &joined[0]);
+    }
+    joined
+}
+
+fn to_contour(edges: &[Edge]) -> Contour<()> {
+    let at = |(x, y): Pt| (x as f32, y as f32);
+    let handle = |p: Pt| Handle::At(p.0 as f32, p.1 as f32);
+    (0..edges.len())
+        .map(|i| {
+            let (edge, prev) = (&edges[i], &edges[(i + edges.len() - 1) % edges.len()]);
+            let mut point = Point::from_x_y_type(at(edge.curve[0]), if prev.line { PointType::Line } else { PointType::Curve });
+            if !edge.line {
+                point.a = handle(edge.curve[1]);
+            }
+            if !prev.line {
+                point.b = handle(prev.curve[2]);
+            }
+            point
+        })
+        .collect()
+}
+
+fn native_boolean(
+    a: &Outline<()>,
+    b: &Outline<()>,
+    op: BooleanOp,
+    options: &BooleanOptions,
+    tolerance: f64,
+) -> Result<Outline<()>, String> {
+    let mut segments = segments_of(a, 0, tolerance);
+    segments.extend(segments_of(b, 1, tolerance));
+    if segments.iter().flat_map(|s| s.curve).any(|(x, y)| !x.is_finite() || !y.is_finite()) {
+        return Err(String::from("the outlines have points which aren't finite"));
+    }
+    let pieces = pieces(&segments);
+
+    let mut vertices = Vertices::new(tolerance);
+    let edges = edges(&pieces, &segments, &mut vertices, tolerance);
+    let kept = classify(edges, &pieces, &segments, op, options, tolerance)?;
+
+    let mut arriving = vec![0; vertices.points.len()];
+    let mut leaving = vec![0; vertices.points.len()];
+    for edge in kept.iter() {
+        arriving[edge.to] += 1;
+        leaving[edge.from] += 1;
+    }
+    let simple: Vec<bool> = arriving.iter().zip(leaving).map(|(&a, l)| a == 1 && l == 1).collect();
+
+    Ok(chain(&kept)?
+        .iter()
+        .map(|chain| to_contour(&rejoined(chain, &kept, &segments, &vertices, &simple)))
+        .filter(|contour| bezier::signed_area(&bezier::flatten(contour, tolerance)).abs() > tolerance * tolerance)
+        .collect())
+}
+
+/// The result of `op` on the areas filled by `a` and `b`'s closed contours (open contours bound no area,
+/// and are left out). Outer contours of the result run counterclockwise, and counters clockwise.
+///
+/// Should the operation fail, it's tried again with a greater tolerance, then with Skia's path ops
+/// (for the nonzero fill rule). It's an error if all of those fail, never an empty outline, nor the
+/// outlines left overlapping.
+pub fn boolean(a: &Outline<()>, b: &Outline<()>, op: BooleanOp, options: &BooleanOptions) -> Result<Outline<()>, StrokeError> {
+    let mut tolerance = options.tolerance;
+    for _ in 0..=options.retries {
+        match catch_geometry(|| native_boolean(a, b, op, options, tolerance)) {
+            Ok(Ok(result)) => return Ok(result),
+            Ok(Err(message)) => log::debug!("Boolean operation failed at tolerance {}: {}", tolerance, message),
+            Err(e) => log::debug!("Boolean operation failed at tolerance {}: {}", tolerance, e),
+        }
+        tolerance *= 10.;
+    }
+
+    if options.fill_rule == FillRule::NonZero {
+        if let Some(result) = skia_outline::boolean(a, b, op).filter(|result| !result.is_empty()) {
+            log::warn!("Boolean operation failed, falling back to Skia's");
+            return Ok(result);
+        }
+    }
+    Err(StrokeError::Geometry {
+        path: None,
+        message: format!("boolean operation ({:?}) failed", op),
+    })
+}
+
+/// [`boolean`], on MFEKmath's piecewise Béziers.
+pub fn boolean_piecewise(
+    a: &Piecewise<Piecewise<Bezier>>,
+    b: &Piecewise<Piecewise<Bezier>>,
+    op: BooleanOp,
+    options: &BooleanOptions,
+) -> Result<Piecewise<Piecewise<Bezier>>, StrokeError> {
+    Ok(Piecewise::from(&boolean(&a.to_outline(), &b.to_outline(), op, options)?))
+}
+
+/// Unites the closed contours of `outline` with each other, removing their overlaps (see [`boolean`]).
+pub fn simplify(outline: &Outline<()>, options: &BooleanOptions) -> Result<Outline<()>, StrokeError> {
+    boolean(outline, &Outline::new(), BooleanOp::Union, options)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::bezier::{Segment, KAPPA};
+
+    fn square((x, y): Pt, size: f64) -> Contour<()> {
+        let corners = [(x, y), (x + size, y), (x + size, y + size), (x, y + size)];
+        let sides: Vec<Segment> = (0..4).map(|i| Segment::line(corners[i], corners[(i + 1) % 4])).collect();
+        bezier::closed_contour(&sides)
+    }
+
+    fn circle((x, y): Pt, r: f64) -> Contour<()> {
+        let k = r * KAPPA;
+        let quarters: Vec<Segment> = [(1., 0.), (0., 1.), (-1., 0.), (0., -1.)]
+            .iter()
+            .map(|&(dx, dy)| {
+                let (ex, ey) = (-dy, dx);
+                Segment::cubic(
+                    (x + r * dx, y + r * dy),
+                    (x + r * dx + k * ex, y + r * dy + k * ey),
+                    (x + r * ex + k * dx, y + r * ey + k * dy),
+                    (x + r * ex, y + r * ey),
+                )
+            })
+            .collect();
+        bezier::closed_contour(&quarters)
+    }
+
+    fn area(outline: &Outline<()>) -> f64 {
+        outline
+            .iter()
+            .map(|contour| bezier::signed_area(&bezier::flatten(contour, 0.001)))
+            .sum()
+    }
+
+    fn assert_area(outline: &Outline<()>, expected: f64) {
+        let area = area(outline);
+        assert!((area - expected).abs() < 0.5, "area {} rather than {}", area, expected);
+    }
+
+    #[test]
+    fn operations_on_overlapping_squares() {
+        let (a, b) = (vec![square((0., 0.), 100.)], vec![square((50., 50.), 100.)]);
+        let options = BooleanOptions::default();
+        for (op, expected, contours) in [
+            (BooleanOp::Union, 17_500., Some(1)),
+            (BooleanOp::Difference, 7_500., Some(1)),
+            (BooleanOp::Intersection, 2_500., Some(1)),
+            // The two parts of the exclusive or meet at corners, so may be chained as one contour or two.
+            (BooleanOp::Xor, 15_000., None),
+        ] {
+            let result = boolean(&a, &b, op, &options).unwrap();
+            assert_area(&result, expected);
+            if let Some(contours) = contours {
+                assert_eq!(result.len(), contours, "{:?}", op);
+            }
+        }
+    }
+
+    #[test]
+    fn coincident_edges_are_kept_once() {
+        let options = BooleanOptions::default();
+        let a = vec![square((0., 0.), 100.)];
+        // Sharing a whole side, and part of one.
+        for b in [square((100., 0.), 100.), square((100., 50.), 100.)] {
+            let union = boolean(&a, &vec![b.clone()], BooleanOp::Union, &options).unwrap();
+            assert_eq!(union.len(), 1);
+            assert_area(&union, 20_000.);
+            assert!(boolean(&a, &vec![b.clone()], BooleanOp::Intersection, &options).unwrap().is_empty());
+            assert_area(&boolean(&a, &vec![b], BooleanOp::Difference, &options).unwrap(), 10_000.);
+        }
+        // A square on top of itself.
+        let union = boolean(&a, &a, BooleanOp::Union, &options).unwrap();
+        assert_eq!(union.len(), 1);
+        assert_area(&union, 10_000.);
+    }
+
+    #[test]
+    fn coincident_curves_split_differently_are_kept_once() {
+        let options = BooleanOptions {
+            retries: 0,
+            ..BooleanOptions::default()
+        };
+        let a = vec![circle((0., 0.), 100.)];
+        let b = vec![bezier::subdivide(&a[0], 3)];
+        let union = boolean(&a, &b, BooleanOp::Union, &options).unwrap();
+        assert_eq!(union.len(), 1);
+        assert_area(&union, area(&a));
+        assert!(boolean(&a, &b, BooleanOp::Xor, &options).unwrap().is_empty());
+    }
+
+    #[test]
+    fn shallow_crossings_are_found() {
+        // Circles this close cross, away from where they turn, at about a third of a degree, and stay
+        // within the tolerance of each other for a long way around it.
+        let (a, b) = (vec![circle((0., 0.), 100.)], vec![circle((0.3, 0.4), 100.)]);
+        // Without retrying at a greater tolerance, which could hide crossings missed at the first.
+        let options = BooleanOptions {
+            retries: 0,
+            ..BooleanOptions::default()
+        };
+        let union = boolean(&a, &b, BooleanOp::Union, &options).unwrap();
+        let intersection = boolean(&a, &b, BooleanOp::Intersection, &options).unwrap();
+        assert_eq!(union.len(), 1);
+        assert_eq!(intersection.len(), 1);
+        assert_area(&union, area(&a) + area(&b) - area(&intersection));
+        assert!(area(&union) > area(&a) + 90.);
+        let xor = boolean(&a, &b, BooleanOp::Xor, &options).unwrap();
+        assert_eq!(xor.len(), 2);
+        assert_area(&xor, area(&union) - area(&intersection));
+    }
+
+    #[test]
+    fn counters_stay_counters() {
+        let options = BooleanOptions::default();
+        let ring = vec![square((0., 0.), 300.), bezier::reversed(&square((100., 100.), 100.))];
+        // Poking out of the ring's right side, without reaching its counter.
+        let union = boolean(&ring, &vec![square((250., 100.), 100.)], BooleanOp::Union, &options).unwrap();
+        assert_eq!(union.len(), 2);
+        assert_eq!(
+            union
+                .iter()
+                .filter(|contour| bezier::signed_area(&bezier::flatten(contour, 0.001)) < 0.)
+                .count(),
+            1
+        );
+        assert_area(&union, 90_000. - 10_000. + 5_000.);
+        // Filling the counter.
+        let filled = boolean(&ring, &vec![square((100., 100.), 100.)], BooleanOp::Union, &options).unwrap();
+        assert_eq!(filled.len(), 1);
+        assert_area(&filled, 90_000.);
+    }
+
+    #[test]
+    fn open_contours_are_left_out() {
+        let line = vec![
+            Point::from_x_y_type((0., 0.), PointType::Move),
+            Point::from_x_y_type((200., 200.), PointType::Line),
+        ];
+        let outline = vec![square((50., 50.), 100.), line];
+        let simplified = simplify(&outline, &BooleanOptions::default()).unwrap();
+        assert_eq!(simplified.len(), 1);
+        assert_area(&simplified, 10_000.);
+    }
+
+    #[test]
+    fn bands_wind_as_all_the_pieces_do() {
+        let circles = (0..10).map(|i| circle((i as f64 * 30., i as f64 * 20.), 50.));
+        let outline: Outline<()> = circles.chain([square((-20., 50.), 300.)]).collect();
+        let segments = segments_of(&outline, 0, 0.01);
+        let pieces = pieces(&segments);
+        let bands = Bands::new(pieces.iter().collect());
+        assert!(bands.bands.len() > 1);
+        for x in (-80..400).step_by(7) {
+            for y in (-80..400).step_by(7) {
+                let p = (x as f64 + 0.5, y as f64 + 0.25);
+                assert_eq!(bands.winding(p), winding(pieces.iter(), p), "{:?}", p);
+            }
+        }
+    }
+}
@@ -1,17 +1,26 @@
-use std::ffi;
-use std::fs;
-use std::path::PathBuf as FsPathBuf;
-
-use MFEKmath::Piecewise;
-use MFEKmath::variable_width_stroking::variable_width_stroke;
-use glifparser::glif::contour_operations::ContourOperations;
 use glifparser::{CapType, JoinType, VWSContour};
 use glifparser::glif::contour_operations::vws::{InterpolationType, VWSHandle};
 
 use glifparser::glif::mfek::{MFEKGlif};
 use MFEKmath::variable_width_stroking::VWSSettings;
 
-use glifparser::{Glif, Outline, PointData};
+use glifparser::{Contour, Glif, Outline};
+
+use crate::bezier;
+use crate::boolean::{self, BooleanOptions};
+use crate::custom_join;
+use crate::error::{catch_geometry, parse_arg};
+use crate::glif_io::{self, InputOptions};
+use crate::glyph_filter;
+use crate::miter_limit::{self, MiterLimit};
+use crate::preview::{self, PreviewOptions};
+use crate::proof;
+use crate::remove_overlap::{self, remove_overlap_if_requested};
+use crate::svg;
+use crate::ufo;
+use crate::variable_width_stroke::{attach_vws_operations, custom_cap_if_requested, stroke_vws_contours};
+use crate::width_profile::{self, Stop, WidthCurve};
+use crate::StrokeError;
 
 use clap::{App, AppSettings, Arg};
 
@@ -30,7 +39,7 @@ pub fn clap_app() -> clap::App<'static> {
                 .long("input")
                 .takes_value(true)
                 .allow_invalid_utf8(true)
-                .help("The path to the input file.")
+                .help("The path to the input file. May be a .glif, .glifjson or .svg, or a .ufo font to stroke every glyph.")
                 .required(true),
         )
         .arg(
@@ -40,7 +49,7 @@ pub fn clap_app() -> clap::App<'static> {
                 .long("output")
                 .takes_value(true)
                 .allow_invalid_utf8(true)
-                .help("The path where the output will be saved.")
+                .help("The path where the output will be saved: a .glif, a .glifjson, or an .svg drawing. Must be a .ufo if the input is a .ufo.")
                 .required(true),
         )
         .arg(
@@ -48,7 +57,7 @@ pub fn clap_app() -> clap::App<'static> {
                 .long("startcap")
                 .short('s')
                 .takes_value(true)
-                .help(r#"Either the constant strings "circle", "round" or "square", or a .glif file."#)
+                .help(r#"Either the constant strings "circle", "round" or "square", or a .glif or .svg file."#)
                 .default_value("circle"),
         )
         .arg(
@@ -56,7 +65,7 @@ pub fn clap_app() -> clap::App<'static> {
                 .long("endcap")
                 .short('e')
                 .takes_value(true)
-                .help(r#"Either the constant strings "circle", "round" or "square", or a .glif file."#)
+                .help(r#"Either the constant strings "circle", "round" or "square", or a .glif or .svg file."#)
                 .default_value("circle"),
         )
         .arg(
@@ -64,8 +73,7 @@ pub fn clap_app() -> clap::App<'static> {
                 .long("jointype")
                 .short('j')
                 .takes_value(true)
-                .possible_values(&["round", "circle", "miter", "bevel"])
-                .help("How to join discontinuous splines")
+                .help(r#"How to join discontinuous splines: "round", "circle", "miter" or "bevel", or a .glif or .svg file of a shape to draw at each corner."#)
                 .default_value("round"),
         )
         .arg(
@@ -116,24 +124,200 @@ pub fn clap_app() -> clap::App<'static> {
                 .long("segmentwise")
                 .short('S')
                 .takes_value(false)
+                .conflicts_with("jointype")
                 .help(r#"Join all segments with caps (stroke all Bézier segments one by one)"#),
         )
+        .arg(
+            Arg::new("align")
+                .long("align")
+                .takes_value(true)
+                .possible_values(&["center", "inside", "outside"])
+                .default_value("center")
+                .help("Where the strokes of closed contours lie: centered on the path, or all inside or outside the glyph's filled area."),
+        )
+        .args(miter_limit::clap_args())
+        .arg(
+            Arg::new("taper-start")
+                .long("taper-start")
+                .takes_value(true)
+                .validator(super::arg_validator_positive_or_zero_f64)
+                .conflicts_with("segmentwise")
+                .help("<f64> Thin open contours to a point over this length, along the path, from their start."),
+        )
+        .arg(
+            Arg::new("taper-end")
+                .long("taper-end")
+                .takes_value(true)
+                .validator(super::arg_validator_positive_or_zero_f64)
+                .conflicts_with("segmentwise")
+                .help("<f64> Thin open contours to a point over this length, along the path, to their end."),
+        )
+        .arg(
+            Arg::new("taper-curve")
+                .long("taper-curve")
+                .takes_value(true)
+                .possible_values(&WidthCurve::NAMES)
+                .default_value("linear")
+                .help("The curve the width follows along a taper, from its tip. ease-in makes sharp tips, ease-out blunt ones."),
+        )
+        .arg(
+            Arg::new("taper-min-width")
+                .long("taper-min-width")
+                .takes_value(true)
+                .validator(super::arg_validator_positive_or_zero_f64)
+                .default_value("0")
+                .help("<f64> The width at the tip of a taper, rather than a point."),
+        )
+        .arg(
+            Arg::new("taper-samples")
+                .long("taper-samples")
+                .takes_value(true)
+                .validator(super::arg_validator_usize)
+                .default_value("8")
+                .help("<usize> Handles along each taper, for the width to follow the --taper-curve."),
+        )
+        .args(remove_overlap::clap_args())
+        .args(boolean::clap_args())
+        .args(glyph_filter::clap_args())
+        .args(svg::clap_args())
+        .args(glif_io::clap_args())
+        .args(svg::output_clap_args())
+        .args(preview::clap_args())
+        .args(proof::clap_args())
+}
+
+/// Where the strokes of closed contours lie against the area they bound.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum StrokeAlign {
+    /// On the path, `left` and `right` of it.
+    Center,
+    /// Entirely inside the glyph's filled area, so that outlining it shrinks it.
+    Inside,
+    /// Entirely outside the glyph's filled area, so that outlining it grows it.
+    Outside,
 }
 
+/// Settings for constant width stroking.
 #[derive(Debug)]
-struct CWSSettings<PD: PointData> {
-    vws_settings: VWSSettings<PD>,
-    left: f64,
-    right: f64,
-    jointype: JoinType,
-    startcap: CapType,
-    endcap: CapType,
-    remove_internal: bool,
-    remove_external: bool,
-    segmentwise: bool,
+pub struct CwsOptions {
+    /// Custom cap glifs, used when `startcap`/`endcap` are `CapType::Custom`.
+    pub vws_settings: VWSSettings<()>,
+    /// Stroke width to the left of the path.
+    pub left: f64,
+    /// Stroke width to the right of the path.
+    pub right: f64,
+    pub jointype: JoinType,
+    /// A shape drawn at every corner of the path, pointing out of it, scaled so that its width is the
+    /// stroke's; `jointype` should then be `JoinType::Bevel`, for the shape to make the corner.
+    pub join_custom: Option<Glif<()>>,
+    pub startcap: CapType,
+    pub endcap: CapType,
+    pub remove_internal: bool,
+    pub remove_external: bool,
+    /// Stroke every Bézier segment on its own, capping both ends of each.
+    pub segmentwise: bool,
+    /// Where the strokes of closed contours lie; unless `StrokeAlign::Center`, the whole width,
+    /// `left + right`, is on one side.
+    pub align: StrokeAlign,
+    /// How far, along the path, open contours thin towards their start; 0 for no taper.
+    pub taper_start: f64,
+    /// How far, along the path, open contours thin towards their end; 0 for no taper.
+    pub taper_end: f64,
+    /// The curve the width follows along a taper, from its tip (mirrored at the end).
+    pub taper_curve: WidthCurve,
+    /// The width (left and right together) the tapers thin to, at most the stroke's.
+    pub taper_min_width: f64,
+    /// How many handles each taper is given to follow `taper_curve`.
+    pub taper_samples: usize,
+    /// The limit on the length of miter joins, if `jointype` is `JoinType::Miter`.
+    pub miter_limit: Option<MiterLimit>,
+    /// How `join_custom` is united with the stroke.
+    pub boolean: BooleanOptions,
 }
 
-fn make_vws_contours(path: &Glif<()>, settings: &CWSSettings<()>) -> Vec<VWSContour> {
+// The stops of a stroke of an open contour `total` units long, which thins over `start` units from its
+// start, and over `end` units to its end.
+fn taper_stops(total: f64, start: f64, end: f64, settings: &CwsOptions) -> Vec<Stop> {
+    let width = settings.left + settings.right;
+    let tip = if width > 0. { (settings.taper_min_width / width).clamp(0., 1.) } else { 0. };
+    let stop = |at: f64, scale: f64, curve: WidthCurve| Stop {
+        at: at / total,
+        left: settings.left * scale,
+        right: settings.right * scale,
+        tangent: 0.0,
+        curve,
+    };
+
+    let mut stops = vec![];
+    if start > 0. {
+        stops.push(stop(0., tip, settings.taper_curve));
+    }
+    stops.push(stop(start, 1., WidthCurve::Linear));
+    // The end taper is the start's mirror image.
+    stops.push(stop(total - end, 1., settings.taper_curve.mirrored()));
+    if end > 0. {
+        stops.push(stop(total, tip, WidthCurve::Linear));
+    }
+    stops
+}
+
+// Tapers an open contour: returns it with points where the tapers begin, and `taper_samples` along each
+// for the handles to follow the taper curve, along with the handles.
+fn tapered(contour: &Contour<()>, settings: &CwsOptions) -> Option<(Contour<()>, Vec<VWSHandle>)> {
+    let total = *bezier::point_arc_lengths(contour).last().unwrap();
+    if total <= 0. {
+        return None;
+    }
+    let (mut start, mut end) = (settings.taper_start.max(0.), settings.taper_end.max(0.));
+    if start + end > total {
+        // The tapers meet in the middle.
+        let scale = total / (start + end);
+        start *= scale;
+        end *= scale;
+    }
+
+    let samples = settings.taper_samples.max(1);
+    let cuts: Vec<f64> = (0..=samples)
+        .flat_map(|i| {
+            let k = i as f64 / samples as f64;
+            [start * k, total - end * k]
+        })
+        .collect();
+    let contour = bezier::split_at_lengths(contour, &cuts);
+    let handles = width_profile::handles_at(&taper_stops(total, start, end, settings), &contour, InterpolationType::Linear);
+    Some((contour, handles))
+}
+
+// The widths left and right of each contour of `outline`, as `settings.align` puts them. Which side of a
+// closed contour its inside is on comes from its winding, and whether its inside is filled (it's an
+// outer contour) or not (it's a counter) from how many others it's inside of, so the direction its
+// points run in doesn't matter. MFEKmath's left is to the left of the direction of the path.
+fn aligned_widths(outline: &Outline<()>, settings: &CwsOptions) -> Vec<(f64, f64)> {
+    const TOLERANCE: f64 = 1.;
+    let width = settings.left + settings.right;
+    let fills = bezier::fills_inside(outline, TOLERANCE);
+    outline
+        .iter()
+        .zip(fills)
+        .map(|(contour, fills_inside)| {
+            if settings.align == StrokeAlign::Center || bezier::is_open(contour) || contour.len() < 2 {
+                return (settings.left, settings.right);
+            }
+            // A counterclockwise contour has its inside on its left.
+            let inside_on_left = bezier::signed_area(&bezier::flatten(contour, TOLERANCE)) > 0.;
+            let fill_on_left = inside_on_left == fills_inside;
+            if fill_on_left == (settings.align == StrokeAlign::Inside) {
+                (width, 0.)
+            } else {
+                (0., width)
+            }
+        })
+        .collect()
+}
+
+// The VWS data of each contour of `path`, at the constant widths of `settings`, and `path` along with
+// it, with points added to follow any tapers.
+fn make_vws_contours(path: &Glif<()>, settings: &CwsOptions) -> (Glif<()>, Vec<VWSContour>) {
     let vws_contour = VWSContour {
         join_type: settings.jointype,
         cap_start_type: settings.startcap,
@@ -143,91 +327,83 @@ fn make_vws_contours(path: &Glif<()>, settings: &CWSSettings<()>) -> Vec<VWSCont
         remove_external: settings.remove_external,
     };
 
-    let mut vws_contours = vec![vws_contour; path.outline.as_ref().map(|o| o.len()).unwrap_or(0)];
+    let tapering = settings.taper_start > 0. || settings.taper_end > 0.;
+    let widths = path.outline.as_ref().map(|outline| aligned_widths(outline, settings)).unwrap_or_default();
+    let (outline, vws_contours): (Outline<()>, Vec<VWSContour>) = path
+        .outline
+        .iter()
+        .flatten()
+        .zip(widths)
+        .map(|(contour, (left, right))| {
+            let vws_handle = VWSHandle {
+                left_offset: left,
+                right_offset: right,
+                tangent_offset: 0.0,
+                interpolation: InterpolationType::Linear,
+            };
+            let taper = if tapering && contour.len() > 1 && bezier::is_open(contour) { tapered(contour, settings) } else { None };
+            // otherwise, one handle per point, and one for the end
+            let (contour, handles) = taper.unwrap_or_else(|| (contour.clone(), vec![vws_handle; contour.len() + 1]));
+            (contour, VWSContour { handles, ..vws_contour.clone() })
+        })
+        .unzip();
 
-    let vws_handle = VWSHandle {
-        left_offset: settings.left,
-        right_offset: settings.right,
-        tangent_offset: 0.0,
-        interpolation: InterpolationType::Linear,
+    let path = Glif {
+        outline: path.outline.as_ref().map(|_| outline),
+        ..path.clone()
     };
-
-    if let Some(outline) = path.outline.as_ref() {
-        for (cidx, contour) in outline.iter().enumerate() {
-            let pointiter = contour.iter().enumerate();
-
-            for (_, _) in pointiter {
-                vws_contours[cidx].handles.push(vws_handle);
-            }
-            vws_contours[cidx].handles.push(vws_handle);
-        }
-    }
-
-    vws_contours
+    (path, vws_contours)
 }
 
-fn constant_width_stroke_glifjson(path: Glif<()>, settings: &CWSSettings<()>) -> MFEKGlif<()> {
-    let vws_contours = make_vws_contours(&path, settings);
-    let mut ret: MFEKGlif<()> = path.into();
-    for (i, contour) in ret.layers[0].outline.iter_mut().enumerate() {
-        contour.set_operation(Some(ContourOperations::VariableWidthStroke {
-            data: vws_contours[i].clone(),
-        }));
-    }
-    ret
+/// Instead of stroking `path`, attaches the equivalent variable width stroke operation to each of its
+/// contours, for further editing in MFEKglif.
+/// The operation has no room for `settings`' miter limit or custom join, which are left out.
+pub fn stroke_constant_glifjson(path: &Glif<()>, settings: &CwsOptions) -> Result<MFEKGlif<()>, StrokeError> {
+    let (skeleton, vws_contours) = make_vws_contours(path, settings);
+    Ok(attach_vws_operations(&skeleton, &vws_contours))
 }
 
-fn constant_width_stroke(path: &glifparser::Glif<()>, settings: &CWSSettings<()>) -> Outline<()> {
-    if path.outline.as_ref().map(|o| o.len() == 0).unwrap_or(true) {
-        return Outline::new();
-    }
-    // convert our path and pattern to piecewise collections of beziers
-    let piece_path = Piecewise::from(path.outline.as_ref().unwrap());
-    let mut output_outline: Outline<()> = Vec::new();
-    let vws_contours = make_vws_contours(path, settings);
-
-    let iter = piece_path.segs.iter().enumerate();
-    for (i, pwpath_contour) in iter {
-        let vws_contour = &vws_contours[i];
-
-        let results = if settings.segmentwise {
-            pwpath_contour
-                .segs
-                .iter()
-                .map(|p| variable_width_stroke(&Piecewise::new(vec![p.clone()], None), &vws_contour, &settings.vws_settings))
-                .collect()
-        } else {
-            vec![variable_width_stroke(&pwpath_contour, &vws_contour, &settings.vws_settings)]
+fn constant_width_stroke(path: &glifparser::Glif<()>, settings: &CwsOptions) -> Result<Outline<()>, StrokeError> {
+    let (skeleton, vws_contours) = make_vws_contours(path, settings);
+    let mut outline = stroke_vws_contours(&skeleton, &vws_contours, &settings.vws_settings, settings.segmentwise);
+    if let (Some(limit), JoinType::Miter, Some(skeleton)) = (settings.miter_limit.as_ref(), settings.jointype, skeleton.outline.as_ref()) {
+        let width = settings.left + settings.right;
+        let widths: &[f64] = match settings.align {
+            StrokeAlign::Center => &[settings.left, settings.right],
+            StrokeAlign::Inside | StrokeAlign::Outside => &[width, settings.left, settings.right],
         };
-
-        for result_outline in results {
-            for result_contour in result_outline.segs.iter() {
-                output_outline.push(result_contour.to_contour());
-            }
+        miter_limit::limit_miters(&mut outline, skeleton, widths, limit);
+    }
+    match (settings.join_custom.as_ref(), skeleton.outline.as_ref()) {
+        (Some(join), Some(skeleton)) if !settings.segmentwise => {
+            custom_join::add_custom_joins(&outline, skeleton, join, |_, _| settings.left + settings.right, &settings.boolean)
         }
+        _ => Ok(outline),
     }
-    output_outline
+}
+
+/// Strokes every contour of `path` at a constant width.
+pub fn stroke_constant(path: &Glif<()>, settings: &CwsOptions) -> Result<Glif<()>, StrokeError> {
+    glif_io::require_contours(path)?;
+    let output_outline = catch_geometry(|| constant_width_stroke(path, settings))??;
+
+    Ok(Glif {
+        outline: Some(output_outline),
+        ..path.clone()
+    })
 }
 
 // Constant width stroking is really just a special case of variable width stroking. So, we take
 // the width, divide by two to make handles from it, and use those to stroke at a tangent of 0.
 //
 // Some of this was copied from MFEK/math.rlib file src/variable_width_stroking.rs fn variable_width_stroke_glif
-pub fn cws_cli(matches: &clap::ArgMatches) {
-    fn custom_cap_if_requested(ct: CapType, input_file: &str) -> Option<Glif<()>> {
-        if ct == CapType::Custom {
-            let path: glifparser::Glif<()> = glifparser::read(&fs::read_to_string(input_file).expect("Failed to read file!")).unwrap(); // TODO: Proper error handling!
-            Some(path)
-        } else {
-            None
-        }
-    }
-
+pub fn cws_cli(matches: &clap::ArgMatches) -> Result<(), StrokeError> {
     let input_file = matches.value_of_os("input").unwrap();
     let output_file = matches.value_of_os("output").unwrap();
-    let startcap: CapType = (matches.value_of("startcap").unwrap()).parse().expect("Invalid cap/join");
-    let endcap: CapType = (matches.value_of("endcap").unwrap()).parse().expect("Invalid cap/join");
-    let jointype: JoinType = (matches.value_of("jointype").unwrap()).parse().expect("Invalid cap/join");
+    let input_options = InputOptions::from_matches(matches);
+    let startcap: CapType = parse_arg("startcap", matches.value_of("startcap").unwrap())?;
+    let endcap: CapType = parse_arg("endcap", matches.value_of("endcap").unwrap())?;
+    let (jointype, join_custom) = custom_join::join_if_requested(matches.value_of("jointype").unwrap(), &input_options)?;
     let remove_internal = matches.is_present("remove-internal");
     let remove_external = matches.is_present("remove-external");
     let segmentwise = matches.is_present("segmentwise");
@@ -243,56 +419,85 @@ pub fn cws_cli(matches: &clap::ArgMatches) {
         right = width / 2.0;
     }
 
-    // TODO: Proper error handling!
-    let path: glifparser::Glif<()> = glifparser::read(&fs::read_to_string(input_file).expect("Failed to read file!")).unwrap();
-
     let vws_settings = VWSSettings {
-        cap_custom_end: custom_cap_if_requested(endcap, matches.value_of("endcap").unwrap()),
-        cap_custom_start: custom_cap_if_requested(startcap, matches.value_of("startcap").unwrap()),
+        cap_custom_end: custom_cap_if_requested(endcap, matches.value_of("endcap").unwrap(), &input_options)?,
+        cap_custom_start: custom_cap_if_requested(startcap, matches.value_of("startcap").unwrap(), &input_options)?,
     };
 
-    let cws_settings = CWSSettings {
+    let cws_settings = CwsOptions {
         vws_settings,
         left,
         right,
         startcap,
         endcap,
         jointype,
+        join_custom,
         remove_internal,
         remove_external,
         segmentwise,
+        align: match matches.value_of("align").unwrap() {
+            "center" => StrokeAlign::Center,
+            "inside" => StrokeAlign::Inside,
+            "outside" => StrokeAlign::Outside,
+            _ => unreachable!(),
+        },
+        taper_start: matches.value_of("taper-start").map(|t| t.parse().unwrap()).unwrap_or(0.),
+        taper_end: matches.value_of("taper-end").map(|t| t.parse().unwrap()).unwrap_or(0.),
+        taper_curve: matches.value_of("taper-curve").unwrap().parse().map_err(StrokeError::Usage)?,
+        taper_min_width: matches.value_of("taper-min-width").unwrap().parse().unwrap(),
+        taper_samples: matches.value_of("taper-samples").unwrap().parse().unwrap(),
+        miter_limit: MiterLimit::from_matches(matches)?,
+        boolean: BooleanOptions::from_matches(matches),
     };
 
-    let oss = match FsPathBuf::from(output_file).extension() {
-        Some(oss) => oss.to_ascii_lowercase(),
-        None => ffi::OsString::from("glif"),
-    };
+    if cws_settings.taper_min_width > left + right {
+        return Err(StrokeError::Usage(format!("--taper-min-width can't be more than the stroke's width, {}", left + right)));
+    }
 
-    if &oss == &ffi::OsString::from("glifjson") {
-        let out = constant_width_stroke_glifjson(path, &cws_settings);
-        fs::write(output_file, serde_json::to_vec_pretty(&out).unwrap()).expect("Write failed");
-    } else if &oss == &ffi::OsString::from("glif") {
-        let output_outline = path
-            .outline
-            .as_ref()
-            .map(|_| Some(constant_width_stroke(&path, &cws_settings)))
-            .unwrap_or_else(|| None);
+    let preview = PreviewOptions::from_matches(matches)?;
 
-        let out = Glif {
-            outline: output_outline,
-            anchors: path.anchors.clone(),
-            width: path.width,
-            unicode: path.unicode,
-            name: path.name,
-            lib: path.lib,
-            components: path.components,
-            guidelines: path.guidelines,
-            images: path.images,
-            note: path.note,
-            filename: path.filename,
-        };
+    if ufo::is_ufo(input_file) {
+        let settings = format!(
+            "width: {} left, {} right\ncaps: {} start, {} end\njoin: {}\nalign: {}\nsegmentwise: {}\ntaper: {} start, {} end\nmiter limit: {}",
+            cws_settings.left,
+            cws_settings.right,
+            matches.value_of("startcap").unwrap(),
+            matches.value_of("endcap").unwrap(),
+            matches.value_of("jointype").unwrap(),
+            matches.value_of("align").unwrap(),
+            cws_settings.segmentwise,
+            cws_settings.taper_start,
+            cws_settings.taper_end,
+            miter_limit::describe(cws_settings.miter_limit.as_ref())
+        );
+        return ufo::stroke_ufo_cli(matches, input_file.as_ref(), output_file.as_ref(), &settings, |glif| stroke_constant(glif, &cws_settings));
+    }
+
+    proof::reject_for_glif(matches)?;
+
+    let path = glif_io::read_input(input_file, &input_options)?;
 
-        let glifstring = glifparser::write(&out).unwrap(); // TODO: Proper error handling!
-        fs::write(output_file, glifstring).expect("Unable to write file");
+    if glif_io::is_glifjson(output_file) {
+        glif_io::reject_for_glifjson(&[
+            ("A custom --jointype", cws_settings.join_custom.is_some()),
+            ("--miter-limit", cws_settings.miter_limit.is_some()),
+            ("--remove-overlap", matches.is_present("remove-overlap")),
+        ])?;
+        let out = stroke_constant_glifjson(&path, &cws_settings).map_err(|e| e.in_file(input_file))?;
+        glif_io::write_glifjson(&out, output_file)?;
+        if let Some(preview) = &preview {
+            let out = stroke_constant(&path, &cws_settings).map_err(|e| e.in_file(input_file))?;
+            preview::write_preview(&path, &out, preview)?;
+        }
+    } else {
+        let out = stroke_constant(&path, &cws_settings)
+            .and_then(|out| remove_overlap_if_requested(matches, out))
+            .map_err(|e| e.in_file(input_file))?;
+        glif_io::write_output(&out, matches.is_present("svg-skeleton").then_some(&path), output_file)?;
+        if let Some(preview) = &preview {
+            preview::write_preview(&path, &out, preview)?;
+        }
     }
+
+    Ok(())
 }
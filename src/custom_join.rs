@@ -0,0 +1,175 @@
+// Custom joins: a shape from a .glif (or .svg) drawn at every corner of the skeleton, as custom caps
+// are at its ends, for ball corners, notches and the like. MFEKmath only has its own join types, so the
+// path is stroked with bevel joins and the shape, placed on each corner, is united with the stroke.
+//
+// The shape is drawn pointing up, with the corner at the origin. At each corner, up is turned to point
+// out of the corner, along the bisector of its angle, and the shape is scaled so that its width is the
+// width of the stroke.
+
+use glifparser::{Glif, Handle, JoinType, Outline, Point};
+
+use crate::bezier::{self, Pt};
+use crate::boolean::{self, BooleanOp, BooleanOptions};
+use crate::error::parse_arg;
+use crate::glif_io::{self, InputOptions};
+use crate::StrokeError;
+
+/// The names of the join types MFEKmath has; anything else given as a join is a file.
+pub(crate) const JOIN_NAMES: [&str; 4] = ["round", "circle", "miter", "bevel"];
+
+// Corners the path turns by less than this, in radians, are smooth, and get no join.
+const MIN_TURN: f64 = 0.01;
+
+/// Reads a join argument: one of [`JOIN_NAMES`], or else the .glif or .svg file of a custom join, which
+/// must have contours and some width. A custom join is stroked as a bevel.
+pub(crate) fn join_if_requested(arg: &str, input_options: &InputOptions) -> Result<(JoinType, Option<Glif<()>>), StrokeError> {
+    if JOIN_NAMES.contains(&arg.to_ascii_lowercase().as_str()) {
+        return Ok((parse_arg("jointype", &arg.to_ascii_lowercase())?, None));
+    }
+
+    let invalid_join = |message: String| StrokeError::InvalidJoin { path: arg.into(), message };
+    let join = glif_io::read_input(arg, input_options).map_err(|e| match e {
+        StrokeError::Read { source, .. } => invalid_join(format!("not one of {}, nor a readable .glif or .svg ({})", JOIN_NAMES.join(", "), source)),
+        e => invalid_join(e.to_string()),
+    })?;
+    if !glif_io::has_contours(&join) {
+        return Err(invalid_join(String::from("join glif has no contours")));
+    }
+    if join_width(&join) <= 0. {
+        return Err(invalid_join(String::from("join glif has no width to scale to the stroke's")));
+    }
+    Ok((JoinType::Bevel, Some(join)))
+}
+
+fn join_width(join: &Glif<()>) -> f64 {
+    let xs = join.outline.iter().flatten().flat_map(|contour| bezier::flatten(contour, 0.1)).map(|(x, _)| x);
+    let (min, max) = xs.fold((f64::MAX, f64::MIN), |(min, max), x| (min.min(x), max.max(x)));
+    max - min
+}
+
+fn transformed(point: &Point<()>, transform: &impl Fn(Pt) -> Pt) -> Point<()> {
+    let place = |x: f32, y: f32| {
+        let (x, y) = transform((x as f64, y as f64));
+        (x as f32, y as f32)
+    };
+    let handle = |h: Handle| match h {
+        Handle::At(x, y) => {
+            let (x, y) = place(x, y);
+            Handle::At(x, y)
+        }
+        Handle::Colocated => Handle::Colocated,
+    };
+    let (x, y) = place(point.x, point.y);
+    Point {
+        x,
+        y,
+        a: handle(point.a),
+        b: handle(point.b),
+        ..point.clone()
+    }
+}
+
+/// Unites `join`, turned to each corner of `skeleton`, with `outline`, its stroke, according to `boolean`.
+/// `width` gives the width of the stroke, which the shape is scaled to, at a contour and point index.
+pub(crate) fn add_custom_joins(
+    outline: &Outline<()>,
+    skeleton: &Outline<()>,
+    join: &Glif<()>,
+    width: impl Fn(usize, usize) -> f64,
+    boolean: &BooleanOptions,
+) -> Result<Outline<()>, StrokeError> {
+    let join_width = join_width(join);
+    let mut joins = Outline::new();
+    let corners = skeleton.iter().enumerate().flat_map(|(c, contour)| bezier::corners(contour, MIN_TURN).into_iter().map(move |k| (c, k)));
+    for (c, corner) in corners {
+        let scale = width(c, corner.point) / join_width;
+        // The shape's up is out of the corner, and its right a quarter turn clockwise from that.
+        let up = match bezier::unit((corner.arrive.0 - corner.leave.0, corner.arrive.1 - corner.leave.1)) {
+            Some(up) => up,
+            None => continue,
+        };
+        let right = (up.1, -up.0);
+        let transform = |(x, y): Pt| {
+            let (x, y) = (x * scale, y * scale);
+            (corner.at.0 + x * right.0 + y * up.0, corner.at.1 + x * right.1 + y * up.1)
+        };
+        for contour in join.outline.iter().flatten() {
+            joins.push(contour.iter().map(|p| transformed(p, &transform)).collect());
+        }
+    }
+    if joins.is_empty() {
+        return Ok(outline.clone());
+    }
+
+    boolean::boolean(outline, &joins, BooleanOp::Union, boolean)
+}
+
+#[cfg(test)]
+mod tests {
+    use glifparser::{Contour, PointType};
+
+    use super::*;
+
+    fn polygon(points: &[Pt], ptype: PointType) -> Contour<()> {
+        points.iter().map(|&(x, y)| Point::from_x_y_type((x as f32, y as f32), ptype)).collect()
+    }
+
+    // A spike 10 units wide and 20 tall, pointing up from the origin.
+    fn spike() -> Glif<()> {
+        let mut join: Glif<()> = Glif::new();
+        join.outline = Some(vec![polygon(&[(-5., 0.), (5., 0.), (0., 20.)], PointType::Line)]);
+        join
+    }
+
+    // A square far from everything else, standing for the stroke.
+    fn stroke() -> Outline<()> {
+        vec![polygon(&[(-500., -500.), (-400., -500.), (-400., -400.), (-500., -400.)], PointType::Line)]
+    }
+
+    // An open contour through `points`.
+    fn path(points: &[Pt]) -> Outline<()> {
+        let mut contour = polygon(points, PointType::Line);
+        contour[0].ptype = PointType::Move;
+        vec![contour]
+    }
+
+    fn near(p: &Point<()>, (x, y): Pt) -> bool {
+        (p.x as f64 - x).abs() < 0.01 && (p.y as f64 - y).abs() < 0.01
+    }
+
+    #[test]
+    fn joins_point_out_of_corners_at_the_strokes_width() {
+        // Right along the baseline, then up: the corner at (100, 0) turns left.
+        let skeleton = path(&[(0., 0.), (100., 0.), (100., 100.)]);
+        let width = |c, p| {
+            assert_eq!((c, p), (0, 1));
+            20.
+        };
+        let outline = add_custom_joins(&stroke(), &skeleton, &spike(), width, &BooleanOptions::default()).unwrap();
+        assert_eq!(outline.len(), 2);
+        let join = outline.iter().find(|c| c.iter().all(|p| p.x > 0.)).unwrap();
+
+        // Twice the size, the spike's base is 20 wide across the corner, and its tip 40 out, down and
+        // to the right, away from the inside of the turn.
+        let out = 40. * std::f64::consts::FRAC_1_SQRT_2;
+        assert!(join.iter().any(|p| near(p, (100. + out, -out))));
+        let base = 10. * std::f64::consts::FRAC_1_SQRT_2;
+        assert!(join.iter().any(|p| near(p, (100. - base, -base))));
+        assert!(join.iter().any(|p| near(p, (100. + base, base))));
+    }
+
+    #[test]
+    fn smooth_paths_get_no_joins() {
+        let skeleton = path(&[(0., 0.), (100., 0.), (200., 0.)]);
+        let outline = add_custom_joins(&stroke(), &skeleton, &spike(), |_, _| 20., &BooleanOptions::default()).unwrap();
+        assert_eq!(outline, stroke());
+    }
+
+    #[test]
+    fn named_joins_are_not_files() {
+        let (jointype, join) = join_if_requested("Miter", &InputOptions::default()).unwrap();
+        assert_eq!(jointype, JoinType::Miter);
+        assert!(join.is_none());
+        assert!(matches!(join_if_requested("no-such-join.glif", &InputOptions::default()), Err(StrokeError::InvalidJoin { .. })));
+    }
+}
@@ -3,7 +3,20 @@ use glifparser::glif::contour_operations::dash::{DashContour, DashCull};
 use glifparser::outline::RefigurePointTypes as _;
 use MFEKmath::skia_safe::{PaintCap, PaintJoin};
 
-use std::fs;
+use crate::boolean;
+use crate::error::catch_geometry;
+use crate::glif_io::{self, InputOptions};
+use crate::glyph_filter;
+use crate::miter_limit::{self, MiterFallback, MiterLimit};
+use crate::preview::{self, PreviewOptions};
+use crate::proof;
+use crate::remove_overlap::{self, remove_overlap_if_requested};
+use crate::svg;
+use crate::ufo;
+use crate::StrokeError;
+
+/// Skia strokes the dashes with its default miter limit, and bevels every miter longer than this.
+pub(crate) const SKIA_MITER_LIMIT: f64 = 4.;
 
 pub fn clap_app() -> clap::App<'static> {
     App::new("DASH")
@@ -19,7 +32,7 @@ pub fn clap_app() -> clap::App<'static> {
                 .takes_value(true)
                 .allow_invalid_utf8(true)
                 .required(true)
-                .help("The path to the input glif file."),
+                .help("The path to the input .glif, .glifjson or .svg file, or a .ufo font to dash every glyph."),
         )
         .arg(
             Arg::new("output")
@@ -28,7 +41,7 @@ pub fn clap_app() -> clap::App<'static> {
                 .required(true)
                 .takes_value(true)
                 .allow_invalid_utf8(true)
-                .help("The path to the output glif file."),
+                .help("The path to the output .glif file, or an .svg drawing. Must be a .ufo if the input is a .ufo."),
         )
         .arg(
             Arg::new("dash")
@@ -104,6 +117,7 @@ pub fn clap_app() -> clap::App<'static> {
                 .help("How to join discontinuous splines")
                 .default_value("round"),
         )
+        .args(miter_limit::clap_args())
         .arg(
             Arg::new("cap-type")
                 .long("cap")
@@ -117,9 +131,17 @@ pub fn clap_app() -> clap::App<'static> {
                 .help("How to cap splines")
                 .default_value("round"),
         )
+        .args(remove_overlap::clap_args())
+        .args(boolean::clap_args())
+        .args(glyph_filter::clap_args())
+        .args(svg::clap_args())
+        .args(glif_io::clap_args())
+        .args(svg::output_clap_args())
+        .args(preview::clap_args())
+        .args(proof::clap_args())
 }
 
-pub fn dash_cli(matches: &ArgMatches) {
+pub fn dash_cli(matches: &ArgMatches) -> Result<(), StrokeError> {
     let path_string = matches.value_of("input").unwrap(); // required options shouldn't panic
     let out_string = matches.value_of("output").unwrap(); // required options shouldn't panic
     let stroke_width = matches.value_of("width").unwrap().parse::<f32>().unwrap();
@@ -151,7 +173,7 @@ pub fn dash_cli(matches: &ArgMatches) {
         _ => unreachable!(),
     };
 
-    let dash_settings = DashContour {
+    let settings = DashContour {
         stroke_width,
         dash_desc,
         include_last_path,
@@ -167,14 +189,126 @@ pub fn dash_cli(matches: &ArgMatches) {
         },
     };
 
-    // TODO: Handle errors properly!
-    let mut path: glifparser::Glif<()> = glifparser::read(&fs::read_to_string(path_string).expect("Failed to read path file!"))
-        .expect("glifparser couldn't parse input path glif. Invalid glif?");
-    let mut out = MFEKmath::dash_along_glif(&path, &dash_settings);
+    let options = DashOptions {
+        settings,
+        miter_limit: MiterLimit::from_matches(matches)?,
+    };
+    let preview = PreviewOptions::from_matches(matches)?;
+
+    if ufo::is_ufo(path_string) {
+        let settings = format!(
+            "dash: {}\nwidth: {}\ncap: {}\njoin: {}\nmiter limit: {}",
+            matches.values_of("dash").unwrap().collect::<Vec<_>>().join(" "),
+            stroke_width,
+            matches.value_of("cap-type").unwrap(),
+            matches.value_of("join-type").unwrap(),
+            miter_limit::describe(options.miter_limit.as_ref())
+        );
+        return ufo::stroke_ufo_cli(matches, path_string.as_ref(), out_string.as_ref(), &settings, |glif| stroke_dash(glif, &options));
+    }
+
+    proof::reject_for_glif(matches)?;
+
+    let path = glif_io::read_input(path_string, &InputOptions::from_matches(matches))?;
+    let out = stroke_dash(&path, &options)
+        .and_then(|out| remove_overlap_if_requested(matches, out))
+        .map_err(|e| e.in_file(path_string))?;
+    if let Some(preview) = &preview {
+        preview::write_preview(&path, &out, preview)?;
+    }
+    glif_io::write_output(&out, matches.is_present("svg-skeleton").then_some(&path), out_string)
+}
+
+/// Settings for dashing.
+#[derive(Debug, Clone)]
+pub struct DashOptions {
+    /// The dash description, stroke width, caps, joins and culling, as stored in an MFEKglif dash operation.
+    pub settings: DashContour,
+    /// The limit on the length of miter joins, if `settings.paint_join` is a miter. `None` keeps Skia's
+    /// own limit of 4.
+    pub miter_limit: Option<MiterLimit>,
+}
+
+/// Dashes every contour of `path`, dropping degenerate contours from the result.
+pub fn stroke_dash(path: &glifparser::Glif<()>, options: &DashOptions) -> Result<glifparser::Glif<()>, StrokeError> {
+    glif_io::require_contours(path)?;
+    let mut out = catch_geometry(|| MFEKmath::dash_along_glif(path, &options.settings))?;
+    if let (Some(skeleton), Some(outline)) = (path.outline.as_ref(), out.outline.as_mut()) {
+        if options.settings.paint_join == PaintJoin::Miter as u8 {
+            let half_width = options.settings.stroke_width as f64 / 2.;
+            // Without a limit, Skia's own stands.
+            if let Some(limit) = options.miter_limit.as_ref() {
+                miter_limit::limit_miters(outline, skeleton, &[half_width], limit);
+                // The miters Skia beveled are put back where the limit allows them, or clipped.
+                if limit.ratio > SKIA_MITER_LIMIT || limit.fallback == MiterFallback::Clip {
+                    miter_limit::extend_miters(outline, skeleton, &[half_width], SKIA_MITER_LIMIT, Some(limit));
+                }
+            }
+        }
+    }
     out.outline.as_mut().map(|o| {
         o.retain(|c| c.len() > 1);
         o.refigure_point_types()
     });
-    path = out;
-    glifparser::write_to_filename(&path, out_string).unwrap();
+    Ok(out)
+}
+
+#[cfg(test)]
+mod tests {
+    use glifparser::{Point, PointType};
+
+    use super::*;
+
+    // An open skeleton with a 19° corner, whose miter is 6.1 times the width, and a 90° one.
+    fn skeleton() -> glifparser::Glif<()> {
+        let points = [(0., 0.), (50., 300.), (100., 0.), (200., 0.), (200., 100.)];
+        let mut path: glifparser::Glif<()> = glifparser::Glif::new();
+        let mut contour: Vec<Point<()>> = points.iter().map(|&p| Point::from_x_y_type(p, PointType::Line)).collect();
+        contour[0].ptype = PointType::Move;
+        path.outline = Some(vec![contour]);
+        path
+    }
+
+    fn options(miter_limit: Option<MiterLimit>) -> DashOptions {
+        DashOptions {
+            settings: DashContour {
+                stroke_width: 10.,
+                dash_desc: vec![1000., 10.],
+                include_last_path: false,
+                paint_join: PaintJoin::Miter as u8,
+                paint_cap: PaintCap::Butt as u8,
+                cull: None,
+            },
+            miter_limit,
+        }
+    }
+
+    fn points(glif: &glifparser::Glif<()>) -> Vec<(f32, f32)> {
+        glif.outline.iter().flatten().flatten().map(|p| (p.x, p.y)).collect()
+    }
+
+    #[test]
+    fn no_limit_is_skias() {
+        let path = skeleton();
+        let mut skia = MFEKmath::dash_along_glif(&path, &options(None).settings);
+        skia.outline.as_mut().map(|o| {
+            o.retain(|c| c.len() > 1);
+            o.refigure_point_types()
+        });
+        assert_eq!(points(&stroke_dash(&path, &options(None)).unwrap()), points(&skia));
+    }
+
+    #[test]
+    fn a_higher_limit_puts_back_skias_bevels() {
+        let path = skeleton();
+        let beveled = points(&stroke_dash(&path, &options(None)).unwrap());
+        let limit = MiterLimit {
+            ratio: 10.,
+            fallback: MiterFallback::Bevel,
+        };
+        let mitered = points(&stroke_dash(&path, &options(Some(limit))).unwrap());
+        // The apex's miter reaches 6.1 × 5 units past the corner, above the bevel.
+        let top = |points: &[(f32, f32)]| points.iter().map(|p| p.1).fold(f32::MIN, f32::max);
+        assert!(top(&mitered) > top(&beveled) + 20.);
+    }
 }
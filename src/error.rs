@@ -0,0 +1,176 @@
+use std::cell::Cell;
+use std::error::Error;
+use std::fmt;
+use std::io;
+use std::panic::{self, AssertUnwindSafe};
+use std::path::{Path as FsPath, PathBuf as FsPathBuf};
+use std::str::FromStr;
+use std::sync::Once;
+
+/// Everything that can go wrong while stroking a glyph.
+///
+/// Each variant names the file it concerns (when known) and has its own process exit code, see
+/// [`StrokeError::exit_code`].
+#[derive(Debug)]
+pub enum StrokeError {
+    /// An option's value is unusable in a way clap couldn't check, e.g. a bad --glyph-regex.
+    Usage(String),
+    /// An input file couldn't be read.
+    Read { path: FsPathBuf, source: io::Error },
+    /// glifparser couldn't parse a glif.
+    GlifParse { path: Option<FsPathBuf>, message: String },
+    /// A custom cap was requested, but its .glif couldn't be read or has no contours.
+    InvalidCap { path: FsPathBuf, message: String },
+    /// A custom join was requested, but its .glif couldn't be read, or has no contours or no width.
+    InvalidJoin { path: FsPathBuf, message: String },
+    /// The glif has no contours to stroke.
+    EmptyOutline { path: Option<FsPathBuf> },
+    /// The stroking math failed on this outline.
+    Geometry { path: Option<FsPathBuf>, message: String },
+    /// The output couldn't be serialized or written.
+    Write { path: FsPathBuf, source: io::Error },
+    /// FontForge failed to nib stroke the path.
+    FontForge { path: Option<FsPathBuf>, message: String },
+    /// Some glyphs of a .ufo failed to stroke. Each was reported as it failed, and copied unchanged.
+    Batch { failed: usize, total: usize },
+    /// An .svg input couldn't be parsed.
+    SvgParse { path: FsPathBuf, message: String },
+    /// A contour has no variable width stroke data, and no widths were given to stroke it with.
+    NoVwsData { path: Option<FsPathBuf>, contour: usize },
+    /// A width profile couldn't be parsed, or doesn't fit the glif it was applied to.
+    Profile { path: FsPathBuf, message: String },
+    /// The input asks for something MFEKstroke can't do, e.g. a stored operation BAKE can't apply.
+    Unsupported { path: Option<FsPathBuf>, message: String },
+}
+
+impl StrokeError {
+    /// The process exit code MFEKstroke uses for this error. These are part of the CLI API:
+    ///
+    /// | code | error |
+    /// |------|-------|
+    /// | 2 | [`Usage`](StrokeError::Usage), or an invalid command line reported by clap |
+    /// | 3 | [`Read`](StrokeError::Read) |
+    /// | 4 | [`GlifParse`](StrokeError::GlifParse) |
+    /// | 5 | [`InvalidCap`](StrokeError::InvalidCap), [`InvalidJoin`](StrokeError::InvalidJoin) |
+    /// | 6 | [`EmptyOutline`](StrokeError::EmptyOutline) |
+    /// | 7 | [`Geometry`](StrokeError::Geometry) |
+    /// | 8 | [`Write`](StrokeError::Write) |
+    /// | 9 | [`FontForge`](StrokeError::FontForge) |
+    /// | 10 | [`Batch`](StrokeError::Batch) |
+    /// | 11 | [`SvgParse`](StrokeError::SvgParse) |
+    /// | 12 | [`NoVwsData`](StrokeError::NoVwsData) |
+    /// | 13 | [`Profile`](StrokeError::Profile) |
+    /// | 14 | [`Unsupported`](StrokeError::Unsupported) |
+    pub fn exit_code(&self) -> i32 {
+        match self {
+            StrokeError::Usage(_) => 2,
+            StrokeError::Read { .. } => 3,
+            StrokeError::GlifParse { .. } => 4,
+            StrokeError::InvalidCap { .. } | StrokeError::InvalidJoin { .. } => 5,
+            StrokeError::EmptyOutline { .. } => 6,
+            StrokeError::Geometry { .. } => 7,
+            StrokeError::Write { .. } => 8,
+            StrokeError::FontForge { .. } => 9,
+            StrokeError::Batch { .. } => 10,
+            StrokeError::SvgParse { .. } => 11,
+            StrokeError::NoVwsData { .. } => 12,
+            StrokeError::Profile { .. } => 13,
+            StrokeError::Unsupported { .. } => 14,
+        }
+    }
+
+    /// Names `path` as the file the error concerns, unless it already names one.
+    pub fn in_file<P: AsRef<FsPath>>(mut self, file: P) -> Self {
+        match &mut self {
+            StrokeError::GlifParse { path, .. }
+            | StrokeError::EmptyOutline { path }
+            | StrokeError::Geometry { path, .. }
+            | StrokeError::FontForge { path, .. }
+            | StrokeError::NoVwsData { path, .. }
+            | StrokeError::Unsupported { path, .. } => {
+                path.get_or_insert_with(|| file.as_ref().to_path_buf());
+            }
+            _ => {}
+        }
+        self
+    }
+}
+
+impl fmt::Display for StrokeError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let display = |path: &Option<FsPathBuf>| path.as_ref().map(|p| p.display().to_string()).unwrap_or_else(|| String::from("<glif>"));
+
+        match self {
+            StrokeError::Usage(message) => write!(f, "{}", message),
+            StrokeError::Read { path, source } => write!(f, "{}: failed to read file: {}", path.display(), source),
+            StrokeError::GlifParse { path, message } => write!(f, "{}: invalid glif: {}", display(path), message),
+            StrokeError::InvalidCap { path, message } => write!(f, "{}: invalid cap: {}", path.display(), message),
+            StrokeError::InvalidJoin { path, message } => write!(f, "{}: invalid join: {}", path.display(), message),
+            StrokeError::EmptyOutline { path } => write!(f, "{}: glif has no contours to stroke", display(path)),
+            StrokeError::Geometry { path, message } => write!(f, "{}: failed to stroke outline: {}", display(path), message),
+            StrokeError::Write { path, source } => write!(f, "{}: failed to write output: {}", path.display(), source),
+            StrokeError::FontForge { path, message } => write!(f, "{}: FontForge failed to nib stroke: {}", display(path), message),
+            StrokeError::Batch { failed, total } => write!(f, "{} of {} glyphs failed to stroke", failed, total),
+            StrokeError::SvgParse { path, message } => write!(f, "{}: invalid SVG: {}", path.display(), message),
+            StrokeError::NoVwsData { path, contour } => write!(
+                f,
+                "{}: contour {} has no variable width stroke data (give widths with --width, --left and --right, or --profile)",
+                display(path),
+                contour
+            ),
+            StrokeError::Profile { path, message } => write!(f, "{}: invalid width profile: {}", path.display(), message),
+            StrokeError::Unsupported { path, message } => write!(f, "{}: unsupported: {}", display(path), message),
+        }
+    }
+}
+
+impl Error for StrokeError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            StrokeError::Read { source, .. } | StrokeError::Write { source, .. } => Some(source),
+            _ => None,
+        }
+    }
+}
+
+/// Parses the value of the option `--{name}`, reporting a value it can't be parsed from as
+/// [`StrokeError::Usage`].
+pub(crate) fn parse_arg<T: FromStr>(name: &str, value: &str) -> Result<T, StrokeError> {
+    value.parse().map_err(|_| StrokeError::Usage(format!("Invalid value {:?} for --{}", value, name)))
+}
+
+thread_local! {
+    // Number of `catch_geometry` calls in progress on this thread. While there are any, its panics are
+    // expected to be caught and reported as StrokeError::Geometry, so the panic hook stays quiet for
+    // them, but not for panics on other threads.
+    static GEOMETRY_CALLS: Cell<usize> = Cell::new(0);
+}
+static QUIET_PANIC_HOOK: Once = Once::new();
+
+/// Runs stroking math which reports failure by panicking (as MFEKmath does), turning a panic into
+/// [`StrokeError::Geometry`] instead of a backtrace.
+pub(crate) fn catch_geometry<T, F: FnOnce() -> T>(f: F) -> Result<T, StrokeError> {
+    QUIET_PANIC_HOOK.call_once(|| {
+        let default_hook = panic::take_hook();
+        panic::set_hook(Box::new(move |info| {
+            if GEOMETRY_CALLS.with(|calls| calls.get() > 0) {
+                log::debug!("Stroking panicked: {}", info);
+            } else {
+                default_hook(info);
+            }
+        }));
+    });
+
+    GEOMETRY_CALLS.with(|calls| calls.set(calls.get() + 1));
+    let result = panic::catch_unwind(AssertUnwindSafe(f));
+    GEOMETRY_CALLS.with(|calls| calls.set(calls.get() - 1));
+
+    result.map_err(|payload| {
+        let message = payload
+            .downcast_ref::<&str>()
+            .map(|s| s.to_string())
+            .or_else(|| payload.downcast_ref::<String>().cloned())
+            .unwrap_or_else(|| String::from("unknown error"));
+        StrokeError::Geometry { path: None, message }
+    })
+}
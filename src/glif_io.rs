@@ -0,0 +1,217 @@
+// Reading and writing the files MFEKstroke works on, with errors that name the file.
+
+use std::fs;
+use std::io;
+use std::path::Path as FsPath;
+
+use clap::{Arg, ArgMatches};
+use glifparser::glif::mfek::{MFEKContour, MFEKGlif};
+use glifparser::{Contour, Glif, Outline};
+
+use crate::svg::{self, SvgOptions};
+use crate::ufo;
+use crate::StrokeError;
+
+/// Reads and parses the .glif at `path`, setting its `filename`.
+pub fn read_glif<P: AsRef<FsPath>>(path: P) -> Result<Glif<()>, StrokeError> {
+    let path = path.as_ref();
+    let glifstring = fs::read_to_string(path).map_err(|source| StrokeError::Read { path: path.to_path_buf(), source })?;
+    let mut glif: Glif<()> = glifparser::read(&glifstring).map_err(|e| StrokeError::GlifParse {
+        path: Some(path.to_path_buf()),
+        message: e.to_string(),
+    })?;
+    glif.filename = Some(path.to_path_buf());
+    Ok(glif)
+}
+
+pub fn clap_args() -> Vec<Arg<'static>> {
+    vec![Arg::new("layer")
+        .long("layer")
+        .takes_value(true)
+        .help_heading("GLIFJSON INPUT")
+        .help("<name or index> Which layer of .glifjson inputs to use. Defaults to the first.")]
+}
+
+/// How to read inputs which aren't plain .glif files.
+#[derive(Debug, Clone, Default)]
+pub struct InputOptions {
+    pub svg: SvgOptions,
+    /// The layer of .glifjson inputs to read, by name or index. If `None`, the first.
+    pub layer: Option<String>,
+}
+
+impl InputOptions {
+    pub fn from_matches(matches: &ArgMatches) -> Self {
+        InputOptions {
+            svg: SvgOptions::from_matches(matches),
+            layer: matches.value_of("layer").map(String::from),
+        }
+    }
+}
+
+/// Reads the path at `path`. By extension, it may be a .glif, an .svg (see [`svg::read_svg`]) or a
+/// .glifjson (see [`read_glifjson`]), of which a single layer is read.
+pub fn read_input<P: AsRef<FsPath>>(path: P, options: &InputOptions) -> Result<Glif<()>, StrokeError> {
+    if svg::is_svg(&path) {
+        svg::read_svg(path, &options.svg)
+    } else if is_glifjson(&path) {
+        let glif = read_glifjson(&path)?;
+        let layer = select_layer(&glif, options.layer.as_deref())?;
+        glifjson_layer_to_glif(&glif, layer)
+    } else {
+        read_glif(path)
+    }
+}
+
+/// Whether `path` should be read as a .glifjson rather than a .glif.
+pub fn is_glifjson<P: AsRef<FsPath>>(path: P) -> bool {
+    path.as_ref().extension().map(|e| e.eq_ignore_ascii_case("glifjson")).unwrap_or(false)
+}
+
+/// Fails with a usage error if any of `options`, each a flag and whether it was given, was given for a
+/// .glifjson output, which stores the stroke's data for MFEKglif rather than its outline, and so has no
+/// room for them.
+pub fn reject_for_glifjson(options: &[(&str, bool)]) -> Result<(), StrokeError> {
+    match options.iter().find(|(_, given)| *given) {
+        Some((flag, _)) => Err(StrokeError::Usage(format!("{} can't be used when writing a .glifjson, which stores the stroke data rather than the outline", flag))),
+        None => Ok(()),
+    }
+}
+
+/// Reads and parses the .glifjson (an `MFEKGlif`, as saved by MFEKglif and `CWS -o *.glifjson`) at
+/// `path`, setting its `filename`.
+pub fn read_glifjson<P: AsRef<FsPath>>(path: P) -> Result<MFEKGlif<()>, StrokeError> {
+    let path = path.as_ref();
+    let json = fs::read_to_string(path).map_err(|source| StrokeError::Read { path: path.to_path_buf(), source })?;
+    let mut glif: MFEKGlif<()> = serde_json::from_str(&json).map_err(|e| StrokeError::GlifParse {
+        path: Some(path.to_path_buf()),
+        message: e.to_string(),
+    })?;
+    glif.filename = Some(path.to_path_buf());
+    Ok(glif)
+}
+
+/// The index of the layer of `glif` named `layer`, or if it's a number, at that index. `None` means the
+/// first layer.
+pub fn find_layer(glif: &MFEKGlif<()>, layer: Option<&str>) -> Option<usize> {
+    match layer {
+        None => (!glif.layers.is_empty()).then_some(0),
+        Some(layer) => glif
+            .layers
+            .iter()
+            .position(|l| l.name == layer)
+            .or_else(|| layer.parse().ok().filter(|&i: &usize| i < glif.layers.len())),
+    }
+}
+
+/// Like [`find_layer`], but fails with a usage error naming the file and its layers.
+pub fn select_layer(glif: &MFEKGlif<()>, layer: Option<&str>) -> Result<usize, StrokeError> {
+    find_layer(glif, layer).ok_or_else(|| {
+        let names: Vec<&str> = glif.layers.iter().map(|l| l.name.as_str()).collect();
+        StrokeError::Usage(format!(
+            "{}: no layer {:?} (its layers are: {})",
+            glif.filename.as_ref().map(|f| f.display().to_string()).unwrap_or_default(),
+            layer.unwrap_or("0"),
+            names.join(", ")
+        ))
+    })
+}
+
+/// The contours of layer `layer` of `glif` as a plain outline, as cubic Béziers. Any contour operations
+/// stored with them are ignored.
+pub fn glifjson_layer_outline(glif: &MFEKGlif<()>, layer: usize) -> Result<Outline<()>, StrokeError> {
+    glif.layers[layer]
+        .outline
+        .iter()
+        .enumerate()
+        .map(|(i, contour)| {
+            mfek_contour_to_contour(contour).map_err(|_| StrokeError::GlifParse {
+                path: glif.filename.clone(),
+                message: format!("contour {} of layer {} couldn't be converted to cubic Béziers", i, layer),
+            })
+        })
+        .collect()
+}
+
+/// An MFEKglif contour (which may be quadratic, or a hyperbezier) as a plain cubic contour. Fails with
+/// `StrokeError::GlifParse` if it can't be converted.
+pub fn mfek_contour_to_contour(contour: &MFEKContour<()>) -> Result<Contour<()>, StrokeError> {
+    let mut contour = contour.clone();
+    contour.to_cubic();
+    contour.inner().cubic().cloned().ok_or_else(|| StrokeError::GlifParse {
+        path: None,
+        message: String::from("a contour couldn't be converted to cubic Béziers"),
+    })
+}
+
+/// Layer `layer` of `glif` as a plain glif, with the glyph's name, advance, code points and anchors.
+pub fn glifjson_layer_to_glif(glif: &MFEKGlif<()>, layer: usize) -> Result<Glif<()>, StrokeError> {
+    Ok(glifjson_to_glif(glif, glifjson_layer_outline(glif, layer)?))
+}
+
+/// A plain glif with the name, advance, code points and anchors of `glif`, and the given outline.
+pub fn glifjson_to_glif(glif: &MFEKGlif<()>, outline: Outline<()>) -> Glif<()> {
+    let mut ret: Glif<()> = Glif::new();
+    ret.name = glif.name.clone();
+    ret.width = glif.width;
+    ret.unicode = glif.unicode.clone();
+    ret.anchors = glif.anchors.clone();
+    ret.filename = glif.filename.clone();
+    ret.outline = Some(outline);
+    ret
+}
+
+/// Serializes `glif` to a string, as written by `write_glif`.
+pub fn glif_to_string<P: AsRef<FsPath>>(glif: &Glif<()>, path: P) -> Result<String, StrokeError> {
+    glifparser::write(glif).map_err(|e| StrokeError::Write {
+        path: path.as_ref().to_path_buf(),
+        source: io::Error::new(io::ErrorKind::InvalidData, e.to_string()),
+    })
+}
+
+/// Writes `contents` to `path`.
+pub fn write_file<P: AsRef<FsPath>, C: AsRef<[u8]>>(path: P, contents: C) -> Result<(), StrokeError> {
+    let path = path.as_ref();
+    fs::write(path, contents).map_err(|source| StrokeError::Write { path: path.to_path_buf(), source })
+}
+
+/// Serializes `glif` and writes it to `path`.
+pub fn write_glif<P: AsRef<FsPath>>(glif: &Glif<()>, path: P) -> Result<(), StrokeError> {
+    write_file(&path, glif_to_string(glif, &path)?)
+}
+
+/// Serializes `glif` as JSON, as MFEKglif saves .glifjson files, and writes it to `path`.
+pub fn write_glifjson<P: AsRef<FsPath>>(glif: &MFEKGlif<()>, path: P) -> Result<(), StrokeError> {
+    let json = serde_json::to_vec_pretty(glif).map_err(|e| StrokeError::Write {
+        path: path.as_ref().to_path_buf(),
+        source: e.into(),
+    })?;
+    write_file(path, json)
+}
+
+/// Writes the stroked `glif` to `path`: as an SVG drawing if `path` ends in .svg, otherwise as a .glif.
+/// `skeleton`, the unstroked input, is drawn over SVG output if given.
+///
+/// The SVG's viewBox uses the ascender and descender of the UFO the glif came from, if it came from one.
+pub fn write_output<P: AsRef<FsPath>>(glif: &Glif<()>, skeleton: Option<&Glif<()>>, path: P) -> Result<(), StrokeError> {
+    if svg::is_svg(&path) {
+        let metrics = glif.filename.as_deref().and_then(ufo::font_metrics_for_glif);
+        write_file(path, svg::glif_to_svg(glif, skeleton, metrics))
+    } else {
+        write_glif(glif, path)
+    }
+}
+
+/// Whether `glif` has at least one contour.
+pub fn has_contours(glif: &Glif<()>) -> bool {
+    glif.outline.as_ref().map(|o| !o.is_empty()).unwrap_or(false)
+}
+
+/// Fails with `StrokeError::EmptyOutline` if `glif` has no contours.
+pub fn require_contours(glif: &Glif<()>) -> Result<(), StrokeError> {
+    if has_contours(glif) {
+        Ok(())
+    } else {
+        Err(StrokeError::EmptyOutline { path: glif.filename.clone() })
+    }
+}
@@ -0,0 +1,193 @@
+// Selection of which glyphs a batch (.ufo) run strokes.
+//
+// A glyph is selected if it matches any of the --glyphs/--glyph-regex/--unicode-range/--glyph-list
+// filters (or if none of them were given), and matches none of the --exclude-* filters. Glyphs which
+// aren't selected are copied to the output font unchanged.
+
+use std::collections::HashSet;
+use std::fs;
+use std::ops::RangeInclusive;
+
+use clap::{Arg, ArgMatches};
+use regex::Regex;
+
+use crate::StrokeError;
+
+pub fn clap_args() -> Vec<Arg<'static>> {
+    let filter_arg = |name: &'static str, help: &'static str| {
+        Arg::new(name)
+            .long(name)
+            .takes_value(true)
+            .multiple_occurrences(true)
+            .help_heading("GLYPH SELECTION (.ufo INPUT ONLY)")
+            .help(help)
+    };
+
+    vec![
+        filter_arg("glyphs", "Comma-separated glyph names to stroke.").use_delimiter(true),
+        filter_arg("glyph-regex", "Stroke glyphs whose names match this regular expression."),
+        filter_arg("unicode-range", "Stroke glyphs with a code point in these ranges, e.g. U+0041-005A,U+00C0.").use_delimiter(true),
+        filter_arg("glyph-list", "Stroke the glyphs named in this file, one per line (# begins a comment)."),
+        filter_arg("exclude-glyphs", "Comma-separated glyph names not to stroke.").use_delimiter(true),
+        filter_arg("exclude-glyph-regex", "Don't stroke glyphs whose names match this regular expression."),
+        filter_arg("exclude-unicode-range", "Don't stroke glyphs with a code point in these ranges.").use_delimiter(true),
+        filter_arg("exclude-glyph-list", "Don't stroke the glyphs named in this file, one per line."),
+    ]
+}
+
+#[derive(Debug, Default)]
+struct Selection {
+    names: HashSet<String>,
+    regexes: Vec<Regex>,
+    ranges: Vec<RangeInclusive<u32>>,
+}
+
+impl Selection {
+    fn from_matches(matches: &ArgMatches, prefix: &str) -> Result<Self, StrokeError> {
+        let values = |name: &str| matches.values_of(format!("{}{}", prefix, name).as_str()).into_iter().flatten();
+        let mut ret = Selection::default();
+
+        ret.names.extend(values("glyphs").map(|s| s.trim().to_string()).filter(|s| !s.is_empty()));
+        for list in values("glyph-list") {
+            let contents = fs::read_to_string(list).map_err(|source| StrokeError::Read { path: list.into(), source })?;
+            ret.names.extend(
+                contents
+                    .lines()
+                    .map(|l| l.split('#').next().unwrap().trim())
+                    .filter(|l| !l.is_empty())
+                    .map(str::to_string),
+            );
+        }
+        for re in values("glyph-regex") {
+            ret.regexes.push(Regex::new(re).map_err(|e| StrokeError::Usage(format!("Invalid glyph regex: {}", e)))?);
+        }
+        for range in values("unicode-range") {
+            ret.ranges.push(parse_unicode_range(range).map_err(StrokeError::Usage)?);
+        }
+
+        Ok(ret)
+    }
+
+    fn is_empty(&self) -> bool {
+        self.names.is_empty() && self.regexes.is_empty() && self.ranges.is_empty()
+    }
+
+    fn matches(&self, name: &str, unicodes: &[char]) -> bool {
+        self.names.contains(name)
+            || self.regexes.iter().any(|re| re.is_match(name))
+            || unicodes.iter().any(|&c| self.ranges.iter().any(|r| r.contains(&(c as u32))))
+    }
+}
+
+// Accepts U+0041-005A, U+0041-U+005A, 0041-005A, U+0041 and 0041 (all hexadecimal).
+fn parse_unicode_range(s: &str) -> Result<RangeInclusive<u32>, String> {
+    let parse_cp = |cp: &str| {
+        let cp = cp.trim();
+        let hex = cp.strip_prefix("U+").or_else(|| cp.strip_prefix("u+")).unwrap_or(cp);
+        u32::from_str_radix(hex, 16).map_err(|_| format!("Invalid code point {:?} in Unicode range {:?}", cp, s))
+    };
+
+    let (start, end) = match s.split_once('-') {
+        Some((start, end)) => (parse_cp(start)?, parse_cp(end)?),
+        None => (parse_cp(s)?, parse_cp(s)?),
+    };
+
+    if start > end {
+        return Err(format!("Unicode range {:?} is backwards", s));
+    }
+
+    Ok(start..=end)
+}
+
+#[derive(Debug, Default)]
+pub struct GlyphFilter {
+    include: Selection,
+    exclude: Selection,
+}
+
+impl GlyphFilter {
+    pub fn from_matches(matches: &ArgMatches) -> Result<Self, StrokeError> {
+        Ok(GlyphFilter {
+            include: Selection::from_matches(matches, "")?,
+            exclude: Selection::from_matches(matches, "exclude-")?,
+        })
+    }
+
+    /// Whether the filter needs to know a glyph's code points, i.e. whether a Unicode range was given.
+    pub fn needs_unicodes(&self) -> bool {
+        !self.include.ranges.is_empty() || !self.exclude.ranges.is_empty()
+    }
+
+    pub fn selects(&self, name: &str, unicodes: &[char]) -> bool {
+        (self.include.is_empty() || self.include.matches(name, unicodes)) && !self.exclude.matches(name, unicodes)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use clap::App;
+
+    use super::*;
+
+    fn from_args(args: &[&str]) -> GlyphFilter {
+        let matches = App::new("test").args(clap_args()).get_matches_from([&["test"], args].concat());
+        GlyphFilter::from_matches(&matches).unwrap()
+    }
+
+    #[test]
+    fn unicode_ranges() {
+        assert_eq!(parse_unicode_range("U+0041"), Ok(0x41..=0x41));
+        assert_eq!(parse_unicode_range("0041"), Ok(0x41..=0x41));
+        assert_eq!(parse_unicode_range("U+0041-005A"), Ok(0x41..=0x5A));
+        assert_eq!(parse_unicode_range("u+0041-U+005A"), Ok(0x41..=0x5A));
+        assert_eq!(parse_unicode_range(" 0041 - 005a "), Ok(0x41..=0x5A));
+    }
+
+    #[test]
+    fn backwards_and_malformed_ranges_are_errors() {
+        assert!(parse_unicode_range("U+005A-0041").unwrap_err().contains("backwards"));
+        assert!(parse_unicode_range("U+00G1").is_err());
+        assert!(parse_unicode_range("U+0041-").is_err());
+        assert!(parse_unicode_range("").is_err());
+    }
+
+    #[test]
+    fn everything_is_selected_without_filters() {
+        let filter = from_args(&[]);
+        assert!(filter.selects("A", &['A']));
+        assert!(filter.selects(".notdef", &[]));
+        assert!(!filter.needs_unicodes());
+    }
+
+    #[test]
+    fn glyphs_are_selected_by_name_regex_or_code_point() {
+        let filter = from_args(&["--glyphs", "A,B", "--glyph-regex", "^a\\.", "--unicode-range", "U+0030-0039"]);
+        assert!(filter.needs_unicodes());
+        assert!(filter.selects("A", &[]));
+        assert!(filter.selects("B", &['B']));
+        assert!(filter.selects("a.alt", &[]));
+        assert!(!filter.selects("alpha.a", &[]));
+        assert!(filter.selects("seven", &['7']));
+        assert!(!filter.selects("C", &['C']));
+    }
+
+    #[test]
+    fn exclusions_win() {
+        let filter = from_args(&["--glyph-regex", "^a", "--exclude-glyphs", "ae", "--exclude-unicode-range", "U+00E1"]);
+        assert!(filter.selects("a", &['a']));
+        assert!(!filter.selects("ae", &['æ']));
+        assert!(!filter.selects("aacute", &['á']));
+        // Exclusions apply without any inclusions too.
+        let filter = from_args(&["--exclude-glyph-regex", "\\.sc$"]);
+        assert!(filter.selects("a", &[]));
+        assert!(!filter.selects("a.sc", &[]));
+    }
+
+    #[test]
+    fn bad_regexes_and_ranges_are_usage_errors() {
+        let matches = App::new("test").args(clap_args()).get_matches_from(["test", "--glyph-regex", "("]);
+        assert!(matches!(GlyphFilter::from_matches(&matches), Err(StrokeError::Usage(_))));
+        let matches = App::new("test").args(clap_args()).get_matches_from(["test", "--unicode-range", "0042-0041"]);
+        assert!(matches!(GlyphFilter::from_matches(&matches), Err(StrokeError::Usage(_))));
+    }
+}
@@ -0,0 +1,65 @@
+//! MFEKstroke as a library.
+//!
+//! Every stroking mode is exposed as a function taking the glyph to stroke and a settings struct, and
+//! returning the stroked glyph:
+//!
+//! * [`stroke_constant`] / [`CwsOptions`] — constant width stroking (CWS)
+//! * [`stroke_variable`] / [`VwsOptions`] — variable width stroking (VWS)
+//! * [`stroke_pattern`] / [`PapOptions`] — pattern along path (PAP)
+//! * [`stroke_dash`] / [`DashOptions`] — dashing (DASH)
+//! * [`stroke_nib`] / [`NibOptions`] — nib stroking (NIB)
+//!
+//! [`offset`] / [`OffsetOptions`] grows or shrinks the filled areas of a glyph instead of stroking its
+//! paths, and [`bake`] / [`BakeOptions`] instead applies the contour operations stored in an MFEKglif .glifjson.
+//!
+//! [`ufo::stroke_ufo`] applies any of them to every glyph of a UFO font, and [`glif_io::read_input`] reads
+//! paths drawn as .svg as well as .glif. The `MFEKstroke` binary is a
+//! thin command line wrapper around this crate; each mode's module also holds its `clap` definition.
+
+#![allow(non_snake_case)] // for our name MFEKstroke
+
+#[doc(hidden)]
+pub mod validators;
+use self::validators::*;
+
+pub mod bake;
+mod bezier;
+pub mod boolean;
+pub mod constant_width_stroke;
+mod custom_join;
+pub mod dash_along_path;
+mod error;
+pub mod glif_io;
+pub mod glyph_filter;
+pub mod miter_limit;
+#[cfg(feature = "fontforge")]
+pub mod nib_fontforge;
+pub mod nib_shape;
+pub mod nib_stroke;
+mod nib_sweep;
+pub mod offset;
+pub mod pattern_along_path;
+pub mod pen_model;
+pub mod preview;
+pub mod proof;
+pub mod remove_overlap;
+mod skia_outline;
+pub mod svg;
+pub mod ufo;
+pub mod variable_width_stroke;
+pub mod width_profile;
+
+pub use bake::{bake, BakeOptions};
+pub use boolean::{boolean, BooleanOp, BooleanOptions, FillRule};
+pub use constant_width_stroke::{stroke_constant, stroke_constant_glifjson, CwsOptions, StrokeAlign};
+pub use dash_along_path::{stroke_dash, DashOptions};
+pub use error::StrokeError;
+pub use miter_limit::{MiterFallback, MiterLimit};
+pub use nib_shape::NibShape;
+pub use nib_stroke::{stroke_nib, NibBackend, NibOptions};
+pub use offset::{offset, OffsetOptions};
+pub use pattern_along_path::{stroke_pattern, PapOptions};
+pub use pen_model::{Pen, PenModel, Ramp};
+pub use remove_overlap::remove_overlap;
+pub use variable_width_stroke::{stroke_variable, stroke_variable_glifjson, VwsOptions};
+pub use width_profile::{WidthCurve, WidthProfile};
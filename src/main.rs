@@ -1,16 +1,30 @@
 #![allow(non_snake_case)] // for our name MFEKstroke
 
-use clap::{App, AppSettings};
+use std::process;
+
+use clap::{App, AppSettings, Arg};
 use env_logger;
-mod validators;
-use self::validators::*;
 
-mod constant_width_stroke;
-mod dash_along_path;
+use MFEKstroke::validators::*;
+use MFEKstroke::{bake, constant_width_stroke, dash_along_path, nib_stroke, offset, pattern_along_path, variable_width_stroke};
 #[cfg(feature = "fontforge")]
-mod nib_stroke;
-mod pattern_along_path;
-mod variable_width_stroke;
+use MFEKstroke::nib_fontforge;
+
+const EXIT_STATUS: &str = "EXIT STATUS:
+    0     Success
+    2     Invalid command line
+    3     An input file couldn't be read
+    4     An input .glif couldn't be parsed
+    5     A custom cap or join isn't a usable .glif
+    6     The input has no contours to stroke
+    7     Stroking failed on the input's geometry
+    8     The output couldn't be written
+    9     FontForge failed (NIB --backend fontforge only)
+    10    Some glyphs of a .ufo failed (each is reported, and copied unchanged)
+    11    An input .svg couldn't be parsed
+    12    A contour has no VWS data, and no widths were given (VWS only)
+    13    A width profile is invalid, or doesn't fit the glyph (VWS only)
+    14    The input stores an operation that can't be applied (BAKE only)";
 
 fn main() {
     env_logger::init();
@@ -19,16 +33,28 @@ fn main() {
         .setting(AppSettings::SubcommandRequired)
         .setting(AppSettings::DisableHelpSubcommand)
         .author("Matthew Blanchard <matthewrblanchard@gmail.com>; Fredrick R. Brennan <copypasteⒶkittens⊙ph>; MFEK Authors")
-        .about("A utility for applying stroking techniques to contours (in UFO .glif format).")
+        .about("A utility for applying stroking techniques to contours (in UFO .glif format), or to every glyph of a UFO font.")
         .version(env!("CARGO_PKG_VERSION"))
+        .after_help(EXIT_STATUS)
+        .arg(
+            Arg::new("jobs")
+                .long("jobs")
+                .global(true)
+                .takes_value(true)
+                .validator(arg_validator_usize)
+                .help("<usize> How many threads to stroke glyphs and contours on. 0 (the default) means one per CPU."),
+        )
         .subcommand(pattern_along_path::clap_app())
         .subcommand(variable_width_stroke::clap_app())
         .subcommand(constant_width_stroke::clap_app())
-        .subcommand(dash_along_path::clap_app());
+        .subcommand(dash_along_path::clap_app())
+        .subcommand(nib_stroke::clap_app())
+        .subcommand(offset::clap_app())
+        .subcommand(bake::clap_app());
 
     #[cfg(feature = "fontforge")]
     {
-        argparser = argparser.subcommand(nib_stroke::clap_app());
+        argparser = argparser.subcommand(nib_fontforge::worker_clap_app());
     }
 
     let matches = argparser.try_get_matches();
@@ -40,15 +66,30 @@ fn main() {
         }
     };
 
-    match matches.subcommand_name() {
+    if let Some(jobs) = matches.value_of("jobs") {
+        rayon::ThreadPoolBuilder::new()
+            .num_threads(jobs.parse().unwrap()) // validated by arg_validator_usize
+            .build_global()
+            .expect("Failed to start thread pool");
+    }
+
+    let result = match matches.subcommand_name() {
         Some("PAP") => pattern_along_path::pap_cli(&matches.subcommand_matches("PAP").unwrap()),
         Some("VWS") => variable_width_stroke::vws_cli(&matches.subcommand_matches("VWS").unwrap()),
         Some("CWS") => constant_width_stroke::cws_cli(&matches.subcommand_matches("CWS").unwrap()),
         Some("DASH") => dash_along_path::dash_cli(&matches.subcommand_matches("DASH").unwrap()),
-        #[cfg(feature = "fontforge")]
+        Some("BAKE") => bake::bake_cli(&matches.subcommand_matches("BAKE").unwrap()),
         Some("NIB") => nib_stroke::nib_cli(&matches.subcommand_matches("NIB").unwrap()),
+        Some("OFFSET") => offset::offset_cli(&matches.subcommand_matches("OFFSET").unwrap()),
+        #[cfg(feature = "fontforge")]
+        Some(nib_fontforge::WORKER) => nib_fontforge::worker_cli(&matches.subcommand_matches(nib_fontforge::WORKER).unwrap()),
         _ => {
             unreachable!()
         }
+    };
+
+    if let Err(e) = result {
+        eprintln!("MFEKstroke: {}", e);
+        process::exit(e.exit_code());
     }
 }
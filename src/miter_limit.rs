@@ -0,0 +1,378 @@
+// Miter limits. MFEKmath's VWS engine takes no miter limit, and the dashes it strokes with Skia always
+// get Skia's, so the limit is applied to their results instead, the same way for both: at each corner of
+// the skeleton, a miter join's tip is where the outer edges of the stroke, carried straight on along the
+// skeleton's tangents, meet. Its length against the stroke's width is 1 / sin(θ / 2), θ being the angle
+// between the skeleton's segments, and past the limit the tip found there in the stroked outline is cut
+// off. Where Skia has beveled a miter a limit above its own allows, the tip is put back.
+
+use std::fmt;
+
+use clap::{Arg, ArgMatches};
+use glifparser::{Outline, Point, PointType};
+
+use crate::bezier::{self, Pt};
+use crate::StrokeError;
+
+pub fn clap_args() -> Vec<Arg<'static>> {
+    vec![
+        Arg::new("miter-limit")
+            .long("miter-limit")
+            .takes_value(true)
+            .validator(super::arg_validator_positive_f64)
+            .help("<f64> Longest a miter join may be, as a ratio of the stroke width (as in SVG). Sharper corners fall back to --miter-fallback. If not given, there's no limit, but for DASH, which keeps Skia's of 4."),
+        Arg::new("miter-fallback")
+            .long("miter-fallback")
+            .takes_value(true)
+            .possible_values(&["bevel", "clip"])
+            .default_value("bevel")
+            .help("What a miter join longer than --miter-limit becomes: a bevel, or the miter clipped at the limit."),
+    ]
+}
+
+/// What a miter join past the limit becomes.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MiterFallback {
+    /// A straight line across the ends of the stroke's outer edges, as Skia and SVG do.
+    Bevel,
+    /// The miter, cut off square to the corner's bisector at the limit (SVG 2's `miter-clip`).
+    Clip,
+}
+
+/// A miter limit, see [`limit_miters`].
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct MiterLimit {
+    /// The longest a miter may be, as a ratio of the stroke's width (SVG's `stroke-miterlimit`).
+    pub ratio: f64,
+    pub fallback: MiterFallback,
+}
+
+impl MiterLimit {
+    /// The limit requested with --miter-limit, if any.
+    pub fn from_matches(matches: &ArgMatches) -> Result<Option<Self>, StrokeError> {
+        let ratio: f64 = match matches.value_of("miter-limit") {
+            Some(ratio) => ratio.parse().unwrap(), // validated by arg_validator_positive_f64
+            None => return Ok(None),
+        };
+        if ratio < 1. {
+            return Err(StrokeError::Usage(String::from("--miter-limit must be at least 1, as no miter is shorter than the stroke is wide")));
+        }
+        let fallback = match matches.value_of("miter-fallback").unwrap() {
+            "bevel" => MiterFallback::Bevel,
+            "clip" => MiterFallback::Clip,
+            _ => unreachable!(),
+        };
+        Ok(Some(MiterLimit { ratio, fallback }))
+    }
+}
+
+impl fmt::Display for MiterLimit {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let fallback = match self.fallback {
+            MiterFallback::Bevel => "bevel",
+            MiterFallback::Clip => "clip",
+        };
+        write!(f, "{} ({} past it)", self.ratio, fallback)
+    }
+}
+
+/// `limit` as a proof sheet's settings describe it.
+pub(crate) fn describe(limit: Option<&MiterLimit>) -> String {
+    limit.map(MiterLimit::to_string).unwrap_or_else(|| String::from("none"))
+}
+
+// A corner of the skeleton too sharp for the limit: where it is, and the unit normals of the stroke's
+// outer side before and after it.
+struct SharpCorner {
+    at: Pt,
+    normal_in: Pt,
+    normal_out: Pt,
+    /// cos of half the angle the skeleton turns by, which is sin(θ / 2).
+    half_turn_cos: f64,
+}
+
+// Corners whose miters are longer than `limit` times the width.
+fn sharp_corners(skeleton: &Outline<()>, limit: f64) -> Vec<SharpCorner> {
+    skeleton
+        .iter()
+        .flat_map(|contour| bezier::corners(contour, 0.))
+        .filter_map(|corner| {
+            let half_turn_cos = (corner.turn / 2.).cos();
+            if half_turn_cos <= 0. || 1. / half_turn_cos <= limit {
+                return None;
+            }
+            // The outer side is on the right of a left turn, and the reverse.
+            let outer = |(x, y): Pt| if corner.turn > 0. { (y, -x) } else { (-y, x) };
+            Some(SharpCorner {
+                at: corner.at,
+                normal_in: outer(corner.arrive),
+                normal_out: outer(corner.leave),
+                half_turn_cos,
+            })
+        })
+        .collect()
+}
+
+fn distance(a: Pt, b: Pt) -> f64 {
+    (a.0 - b.0).hypot(a.1 - b.1)
+}
+
+// How far `p` is from the line through `a` and `b`.
+fn line_distance(p: Pt, a: Pt, b: Pt) -> f64 {
+    let (dx, dy) = (b.0 - a.0, b.1 - a.1);
+    let length = dx.hypot(dy);
+    if length <= f64::EPSILON {
+        return distance(p, a);
+    }
+    ((p.0 - a.0) * dy - (p.1 - a.1) * dx).abs() / length
+}
+
+fn at(point: &Point<()>) -> Pt {
+    (point.x as f64, point.y as f64)
+}
+
+fn line_point((x, y): Pt) -> Point<()> {
+    Point::from_x_y_type((x as f32, y as f32), PointType::Line)
+}
+
+// The tip of the miter at `corner`, `width` from the skeleton on its outer side, and the ends of the
+// stroke's outer edges there, before and after it.
+fn miter(corner: &SharpCorner, width: f64) -> Option<(Pt, Pt, Pt)> {
+    if width <= 0. {
+        return None;
+    }
+    let bisector = bezier::unit((corner.normal_in.0 + corner.normal_out.0, corner.normal_in.1 + corner.normal_out.1))?;
+    let offset = |(nx, ny): Pt, by: f64| (corner.at.0 + nx * by, corner.at.1 + ny * by);
+    Some((
+        offset(bisector, width / corner.half_turn_cos),
+        offset(corner.normal_in, width),
+        offset(corner.normal_out, width),
+    ))
+}
+
+// How far from the tip of the miter at `corner` towards the ends of the edges `limit` cuts it back: all
+// the way for a bevel, or as far along the bisector as the limit lets the miter reach.
+fn reach(corner: &SharpCorner, width: f64, limit: &MiterLimit) -> f64 {
+    match limit.fallback {
+        MiterFallback::Bevel => 1.,
+        MiterFallback::Clip => {
+            let (tip_depth, edge_depth) = (width / corner.half_turn_cos, width * corner.half_turn_cos);
+            ((tip_depth - limit.ratio * width) / (tip_depth - edge_depth)).clamp(0., 1.)
+        }
+    }
+}
+
+// Cuts the tip of the miter at `corner`, `width` from the skeleton on its outer side, out of `outline`.
+// Returns whether the tip was found.
+fn cut_tip(outline: &mut Outline<()>, corner: &SharpCorner, width: f64, limit: &MiterLimit) -> bool {
+    let (tip, edge_in, edge_out) = match miter(corner, width) {
+        Some(miter) => miter,
+        None => return false,
+    };
+    let reach = reach(corner, width, limit);
+    let toward = |end: Pt| (tip.0 + (end.0 - tip.0) * reach, tip.1 + (end.1 - tip.1) * reach);
+
+    let near = (width * 1e-2).max(0.5);
+    for contour in outline.iter_mut() {
+        let len = contour.len();
+        let found = contour.iter().position(|p| p.ptype != PointType::Move && distance(at(p), tip) < near);
+        let k = match found {
+            Some(k) if len > 2 => k,
+            _ => continue,
+        };
+        let (before, after) = (at(&contour[(k + len - 1) % len]), at(&contour[(k + 1) % len]));
+        // Which edge comes first depends on the direction of the outline.
+        let (first, second) = if line_distance(before, tip, edge_in) <= line_distance(before, tip, edge_out) {
+            (toward(edge_in), toward(edge_out))
+        } else {
+            (toward(edge_out), toward(edge_in))
+        };
+
+        // The tip was reached and left by straight lines, so the cut is all lines too.
+        contour.remove(k);
+        let cut = [(first, before), (second, after)]
+            .into_iter()
+            .filter(|&(point, neighbour)| distance(point, neighbour) >= near)
+            .map(|(point, _)| line_point(point));
+        contour.splice(k..k, cut);
+        return true;
+    }
+    false
+}
+
+// Puts back the tip of the miter at `corner`, `width` from the skeleton on its outer side, where `outline`
+// has a bevel instead: the whole tip, or, past `limit`, the tip clipped. Returns whether the bevel was
+// found.
+fn extend_tip(outline: &mut Outline<()>, corner: &SharpCorner, width: f64, limit: Option<&MiterLimit>) -> bool {
+    let (tip, edge_in, edge_out) = match miter(corner, width) {
+        Some(miter) => miter,
+        None => return false,
+    };
+    let reach = match limit {
+        Some(limit) if 1. / corner.half_turn_cos > limit.ratio => reach(corner, width, limit),
+        _ => 0.,
+    };
+    if reach >= 1. {
+        return false;
+    }
+    let toward = |end: Pt| (tip.0 + (end.0 - tip.0) * reach, tip.1 + (end.1 - tip.1) * reach);
+
+    let near = (width * 1e-2).max(0.5);
+    for contour in outline.iter_mut() {
+        let len = contour.len();
+        if len < 3 || bezier::is_open(contour) {
+            continue;
+        }
+        // The bevel is a line from the end of one edge to the end of the other, in either direction.
+        let found = (0..len).find_map(|k| {
+            let (a, b) = (at(&contour[k]), at(&contour[(k + 1) % len]));
+            if !bezier::is_line(contour, k, (k + 1) % len) {
+                None
+            } else if distance(a, edge_in) < near && distance(b, edge_out) < near {
+                Some((k, edge_in, edge_out))
+            } else if distance(a, edge_out) < near && distance(b, edge_in) < near {
+                Some((k, edge_out, edge_in))
+            } else {
+                None
+            }
+        });
+        let (k, first, second) = match found {
+            Some(found) => found,
+            None => continue,
+        };
+
+        let tip = if reach <= 0. { vec![tip] } else { vec![toward(first), toward(second)] };
+        // The bevel's ends are dropped where the edges reach them in straight lines, which now carry on
+        // to the tip.
+        let next = (k + 1) % len;
+        let drop_first = bezier::is_line(contour, (k + len - 1) % len, k);
+        let drop_second = bezier::is_line(contour, next, (next + 1) % len);
+        let mut points = vec![];
+        for (i, point) in contour.drain(..).enumerate() {
+            if i == next {
+                points.extend(tip.iter().map(|&p| line_point(p)));
+            }
+            if !(i == k && drop_first || i == next && drop_second) {
+                points.push(point);
+            }
+        }
+        *contour = points;
+        return true;
+    }
+    false
+}
+
+/// Cuts off the tips of the miter joins in `outline`, the stroke of `skeleton`, which are longer than
+/// `limit` allows. The stroke's outer edge is one of `widths` from the skeleton at each corner.
+pub(crate) fn limit_miters(outline: &mut Outline<()>, skeleton: &Outline<()>, widths: &[f64], limit: &MiterLimit) {
+    for corner in sharp_corners(skeleton, limit.ratio) {
+        // Which side of the skeleton is left depends on the engine's idea of direction, so the tip is
+        // looked for at each width.
+        for (i, &width) in widths.iter().enumerate() {
+            if !widths[..i].contains(&width) && cut_tip(outline, &corner, width, limit) {
+                break;
+            }
+        }
+    }
+}
+
+/// Lengthens the bevels in `outline`, the stroke of `skeleton` by an engine which bevels every miter
+/// longer than `engine_limit` times the width, back into the miters `limit` allows: all of them if
+/// there's no limit, as for CWS, and those past it clipped if its fallback is `MiterFallback::Clip`.
+/// The stroke's outer edge is one of `widths` from the skeleton at each corner.
+pub(crate) fn extend_miters(outline: &mut Outline<()>, skeleton: &Outline<()>, widths: &[f64], engine_limit: f64, limit: Option<&MiterLimit>) {
+    for corner in sharp_corners(skeleton, engine_limit) {
+        for (i, &width) in widths.iter().enumerate() {
+            if !widths[..i].contains(&width) && extend_tip(outline, &corner, width, limit) {
+                break;
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::bezier::Segment;
+
+    const WIDTH: f64 = 10.;
+
+    // A counterclockwise pentagon: a 124° corner at (100, 0), and a 19° one at its apex, whose miter is
+    // 6.1 times the width.
+    fn skeleton() -> Outline<()> {
+        let points = [(0., 0.), (100., 0.), (140., 60.), (50., 600.), (-40., 60.)];
+        let sides: Vec<Segment> = (0..points.len()).map(|i| Segment::line(points[i], points[(i + 1) % points.len()])).collect();
+        vec![bezier::closed_contour(&sides)]
+    }
+
+    // The outer edge of the skeleton's stroke, with every corner mitered.
+    fn mitered() -> Outline<()> {
+        let tips = sharp_corners(&skeleton(), 1.).iter().map(|corner| line_point(miter(corner, WIDTH).unwrap().0)).collect();
+        vec![tips]
+    }
+
+    fn tip(ratio: f64) -> Pt {
+        let corner = sharp_corners(&skeleton(), 1.).into_iter().find(|c| (1. / c.half_turn_cos - ratio).abs() < 0.1).unwrap();
+        miter(&corner, WIDTH).unwrap().0
+    }
+
+    fn has_point(outline: &Outline<()>, p: Pt) -> bool {
+        outline.iter().flatten().any(|q| distance(at(q), p) < 1e-3)
+    }
+
+    #[test]
+    fn acute_corners_are_beveled() {
+        let limit = MiterLimit {
+            ratio: 4.,
+            fallback: MiterFallback::Bevel,
+        };
+        let mut outline = mitered();
+        limit_miters(&mut outline, &skeleton(), &[WIDTH], &limit);
+        assert_eq!(outline[0].len(), 6);
+        assert!(!has_point(&outline, tip(6.1)));
+        assert!(has_point(&outline, tip(1.13)));
+    }
+
+    #[test]
+    fn acute_corners_are_clipped_at_the_limit() {
+        let limit = MiterLimit {
+            ratio: 4.,
+            fallback: MiterFallback::Clip,
+        };
+        let mut outline = mitered();
+        limit_miters(&mut outline, &skeleton(), &[WIDTH], &limit);
+        assert_eq!(outline[0].len(), 6);
+        // The cut is square to the corner's bisector, 4 widths from the corner.
+        let (a, b) = (at(&outline[0][3]), at(&outline[0][4]));
+        assert!((a.1 - b.1).abs() < 1e-3);
+        assert!((distance((50., 600.), ((a.0 + b.0) / 2., (a.1 + b.1) / 2.)) - 4. * WIDTH).abs() < 1e-3);
+    }
+
+    #[test]
+    fn bevels_are_extended_back_to_miters() {
+        let bevel = MiterLimit {
+            ratio: 4.,
+            fallback: MiterFallback::Bevel,
+        };
+        let mut outline = mitered();
+        limit_miters(&mut outline, &skeleton(), &[WIDTH], &bevel);
+
+        // Within the limit, the tip is put back as it was.
+        let mut extended = outline.clone();
+        extend_miters(&mut extended, &skeleton(), &[WIDTH], 4., None);
+        assert_eq!(extended[0].len(), 5);
+        assert!(has_point(&extended, tip(6.1)));
+
+        // Past it, it's put back clipped, or not at all.
+        let clip = MiterLimit {
+            ratio: 5.,
+            fallback: MiterFallback::Clip,
+        };
+        let mut clipped = outline.clone();
+        extend_miters(&mut clipped, &skeleton(), &[WIDTH], 4., Some(&clip));
+        assert_eq!(clipped[0].len(), 6);
+        assert!(!has_point(&clipped, tip(6.1)));
+        let mut beveled = outline.clone();
+        extend_miters(&mut beveled, &skeleton(), &[WIDTH], 4., Some(&MiterLimit { ratio: 5., ..bevel }));
+        assert_eq!(beveled[0].len(), 6);
+    }
+}
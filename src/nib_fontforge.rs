@@ -0,0 +1,154 @@
+// The FontForge nib stroking backend. FontForge is C, and not memory safe, so by default each glyph is
+// stroked in a worker process: a crash or a hang then fails that glyph rather than taking down a whole
+// batch run.
+
+use std::env;
+use std::fs;
+use std::io::{self, Read, Write};
+use std::path::{Path as FsPath, PathBuf as FsPathBuf};
+use std::process::{self, Command, Stdio};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Mutex;
+use std::thread;
+use std::time::{Duration, Instant};
+
+use clap::{App, AppSettings, Arg};
+use glifparser::Glif;
+use MFEKmath::nib_stroking::{convert_glif, NibSettings};
+
+use crate::glif_io;
+use crate::StrokeError;
+
+/// The name of the hidden subcommand which runs FontForge for a worker process.
+pub const WORKER: &str = "FONTFORGE-WORKER";
+
+pub fn worker_clap_app() -> clap::App<'static> {
+    App::new(WORKER)
+        .setting(AppSettings::Hidden)
+        .about("Nib strokes one .glif with FontForge, printing the result. NIB runs this in a subprocess.")
+        .arg(Arg::new("nib").required(true).allow_invalid_utf8(true))
+        .arg(Arg::new("path").required(true).allow_invalid_utf8(true))
+        .arg(Arg::new("accuracy").required(true).validator(super::arg_validator_positive_f64))
+}
+
+pub fn worker_cli(matches: &clap::ArgMatches) -> Result<(), StrokeError> {
+    let path = matches.value_of_os("path").unwrap();
+    let settings = NibSettings {
+        nib: matches.value_of_os("nib").unwrap().into(),
+        path: path.into(),
+        accuracy: matches.value_of("accuracy").unwrap().parse().unwrap(), // validated by super::arg_validator_positive_f64
+        quiet: true,
+    };
+    let glifstring = convert_glif(&settings).ok_or_else(|| StrokeError::FontForge {
+        path: Some(path.into()),
+        message: String::from("no output"),
+    })?;
+    io::stdout().write_all(glifstring.as_bytes()).map_err(|source| StrokeError::Write {
+        path: FsPathBuf::from("<stdout>"),
+        source,
+    })
+}
+
+// FontForge isn't thread safe, so only one glyph may be in it at a time (in this process).
+static FONTFORGE: Mutex<()> = Mutex::new(());
+static TEMP_FILE_COUNTER: AtomicUsize = AtomicUsize::new(0);
+
+// FontForge reads the nib and the path from disk itself, so we hand it temporary copies of both.
+fn write_temp_glif(glif: &Glif<()>, what: &str) -> Result<FsPathBuf, StrokeError> {
+    let n = TEMP_FILE_COUNTER.fetch_add(1, Ordering::Relaxed);
+    let filename = env::temp_dir().join(format!("MFEKstroke-{}-{}-{}.glif", process::id(), n, what));
+    glif_io::write_glif(glif, &filename)?;
+    Ok(filename)
+}
+
+// Runs FontForge on the temporary files in a worker process, returning the stroked glif's XML.
+fn run_worker(nib_file: &FsPath, path_file: &FsPath, accuracy: f64, timeout: Duration) -> Result<String, String> {
+    let exe = env::current_exe().map_err(|e| format!("can't find the MFEKstroke executable to run FontForge in: {}", e))?;
+    let mut child = Command::new(exe)
+        .arg(WORKER)
+        .arg(nib_file)
+        .arg(path_file)
+        .arg(accuracy.to_string())
+        .stdin(Stdio::null())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .map_err(|e| format!("failed to start a FontForge worker: {}", e))?;
+
+    // The pipes are read as the worker writes to them, lest it block on a full one and seem to hang.
+    let (mut stdout, mut stderr) = (child.stdout.take().unwrap(), child.stderr.take().unwrap());
+    let stdout = thread::spawn(move || {
+        let mut out = String::new();
+        stdout.read_to_string(&mut out).map(|_| out)
+    });
+    let stderr = thread::spawn(move || {
+        let mut err = String::new();
+        let _ = stderr.read_to_string(&mut err);
+        err
+    });
+
+    let started = Instant::now();
+    let status = loop {
+        match child.try_wait() {
+            Ok(Some(status)) => break status,
+            Ok(None) if started.elapsed() > timeout => {
+                let _ = child.kill();
+                let _ = child.wait();
+                return Err(format!("FontForge took longer than {} seconds, and was stopped", timeout.as_secs_f64()));
+            }
+            Ok(None) => thread::sleep(Duration::from_millis(10)),
+            Err(e) => return Err(format!("failed to wait for the FontForge worker: {}", e)),
+        }
+    };
+
+    let stdout = stdout.join().ok().and_then(Result::ok);
+    let stderr = stderr.join().unwrap_or_default();
+    match (status.code(), stdout) {
+        (Some(0), Some(out)) => Ok(out),
+        (Some(0), None) => Err(String::from("the FontForge worker's output isn't UTF-8")),
+        // The worker reports its own errors; the last line says what went wrong.
+        (Some(_), _) => Err(stderr.lines().last().unwrap_or("the FontForge worker failed").to_string()),
+        (None, _) => Err(format!("FontForge crashed ({})", status)),
+    }
+}
+
+/// Strokes `path` with `nib` in FontForge, in a worker process if there's a `timeout`, see
+/// [`NibBackend::FontForge`](crate::nib_stroke::NibBackend::FontForge).
+pub(crate) fn stroke_nib_fontforge(path: &Glif<()>, nib: &Glif<()>, accuracy: f64, timeout: Option<Duration>) -> Result<Glif<()>, StrokeError> {
+    let nib_file = write_temp_glif(nib, "nib")?;
+    let path_file = match write_temp_glif(path, "path") {
+        Ok(path_file) => path_file,
+        Err(e) => {
+            let _ = fs::remove_file(&nib_file);
+            return Err(e);
+        }
+    };
+
+    let converted = match timeout {
+        Some(timeout) => run_worker(&nib_file, &path_file, accuracy, timeout),
+        None => {
+            let settings = NibSettings {
+                nib: nib_file.as_os_str().into(),
+                path: path_file.as_os_str().into(),
+                accuracy,
+                quiet: true,
+            };
+            let _guard = FONTFORGE.lock().unwrap();
+            convert_glif(&settings).ok_or_else(|| String::from("no output"))
+        }
+    };
+
+    let _ = fs::remove_file(&nib_file);
+    let _ = fs::remove_file(&path_file);
+
+    let glifstring = converted.map_err(|message| StrokeError::FontForge {
+        path: path.filename.clone(),
+        message,
+    })?;
+    let mut out: Glif<()> = glifparser::read(&glifstring).map_err(|e| StrokeError::FontForge {
+        path: path.filename.clone(),
+        message: format!("output isn't a valid glif: {}", e),
+    })?;
+    out.filename = path.filename.clone();
+    Ok(out)
+}
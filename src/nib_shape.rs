@@ -0,0 +1,158 @@
+// Built-in parametric nibs, so that NIB can be tried at any size and angle without drawing a nib file
+// for each. Every shape is made as FontForge wants its nibs: a single closed, clockwise, convex contour,
+// centered on the origin.
+
+use std::f64::consts::PI;
+use std::fmt;
+use std::str::FromStr;
+
+use glifparser::{Glif, Handle, Point, PointType};
+
+use crate::bezier::{Pt, KAPPA};
+
+/// A nib shape, as given to `--nib-shape`: `ellipse:WxH`, `rect:WxH` or `polygon:N:RADIUS`, each
+/// optionally rotated counterclockwise by `@ANGLE` (in degrees, with or without a `deg` suffix).
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum NibShape {
+    Ellipse { width: f64, height: f64, angle: f64 },
+    Rect { width: f64, height: f64, angle: f64 },
+    /// A regular polygon with `sides` corners at `radius` from its center, the first straight up.
+    Polygon { sides: usize, radius: f64, angle: f64 },
+}
+
+fn parse_size(s: &str) -> Result<Pt, String> {
+    let (w, h) = s.split_once('x').ok_or_else(|| format!("expected a size like 40x10, not {:?}", s))?;
+    let dimension = |d: &str| match d.parse::<f64>() {
+        Ok(d) if d > 0. => Ok(d),
+        _ => Err(format!("{:?} isn't a positive number", d)),
+    };
+    Ok((dimension(w)?, dimension(h)?))
+}
+
+impl FromStr for NibShape {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (shape, angle) = match s.split_once('@') {
+            Some((shape, angle)) => {
+                let degrees = angle.trim_end_matches("deg");
+                (shape, degrees.parse::<f64>().map_err(|_| format!("bad angle {:?}", angle))?)
+            }
+            None => (s, 0.),
+        };
+        let (kind, args) = shape.split_once(':').ok_or_else(|| format!("expected ellipse:WxH, rect:WxH or polygon:N:RADIUS, not {:?}", s))?;
+
+        match kind {
+            "ellipse" => {
+                let (width, height) = parse_size(args)?;
+                Ok(NibShape::Ellipse { width, height, angle })
+            }
+            "rect" => {
+                let (width, height) = parse_size(args)?;
+                Ok(NibShape::Rect { width, height, angle })
+            }
+            "polygon" => {
+                let (sides, radius) = args.split_once(':').ok_or_else(|| format!("expected polygon:N:RADIUS, not {:?}", s))?;
+                let sides: usize = sides.parse().map_err(|_| format!("bad number of sides {:?}", sides))?;
+                let radius: f64 = radius.parse().map_err(|_| format!("bad radius {:?}", radius))?;
+                if sides < 3 || radius <= 0. {
+                    return Err(String::from("a polygon nib needs at least 3 sides and a positive radius"));
+                }
+                Ok(NibShape::Polygon { sides, radius, angle })
+            }
+            _ => Err(format!("unknown nib shape {:?} (expected ellipse, rect or polygon)", kind)),
+        }
+    }
+}
+
+impl fmt::Display for NibShape {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            NibShape::Ellipse { width, height, angle } => write!(f, "ellipse:{}x{}@{}deg", width, height, angle),
+            NibShape::Rect { width, height, angle } => write!(f, "rect:{}x{}@{}deg", width, height, angle),
+            NibShape::Polygon { sides, radius, angle } => write!(f, "polygon:{}:{}@{}deg", sides, radius, angle),
+        }
+    }
+}
+
+impl NibShape {
+    /// The nib as a glif with a single closed, clockwise contour, centered on the origin.
+    pub fn to_glif(&self) -> Glif<()> {
+        let angle = match *self {
+            NibShape::Ellipse { angle, .. } | NibShape::Rect { angle, .. } | NibShape::Polygon { angle, .. } => angle.to_radians(),
+        };
+        let (sin, cos) = angle.sin_cos();
+        let rotate = |(x, y): Pt| ((x * cos - y * sin) as f32, (x * sin + y * cos) as f32);
+        let corner = |p: Pt| Point::from_x_y_type(rotate(p), PointType::Line);
+
+        let contour = match *self {
+            NibShape::Ellipse { width, height, .. } => {
+                // Four quarter arcs, clockwise from the right, each handle KAPPA of the way along the tangent.
+                let (rx, ry) = (width / 2., height / 2.);
+                (0..4)
+                    .map(|i| {
+                        let t = -(i as f64) * PI / 2.;
+                        let (p, d) = ((rx * t.cos(), ry * t.sin()), (-rx * t.sin(), ry * t.cos()));
+                        let mut point = Point::from_x_y_type(rotate(p), PointType::Curve);
+                        let (ax, ay) = rotate((p.0 - KAPPA * d.0, p.1 - KAPPA * d.1));
+                        let (bx, by) = rotate((p.0 + KAPPA * d.0, p.1 + KAPPA * d.1));
+                        point.a = Handle::At(ax, ay);
+                        point.b = Handle::At(bx, by);
+                        point
+                    })
+                    .collect()
+            }
+            NibShape::Rect { width, height, .. } => {
+                let (x, y) = (width / 2., height / 2.);
+                vec![corner((-x, y)), corner((x, y)), corner((x, -y)), corner((-x, -y))]
+            }
+            NibShape::Polygon { sides, radius, .. } => (0..sides)
+                .map(|i| {
+                    let t = PI / 2. - i as f64 * 2. * PI / sides as f64;
+                    corner((radius * t.cos(), radius * t.sin()))
+                })
+                .collect(),
+        };
+
+        let mut glif: Glif<()> = Glif::new();
+        glif.name = self.to_string();
+        glif.outline = Some(vec![contour]);
+        glif
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::bezier;
+
+    #[test]
+    fn polygons_are_parsed() {
+        assert_eq!("polygon:6:20".parse(), Ok(NibShape::Polygon { sides: 6, radius: 20., angle: 0. }));
+        assert_eq!("polygon:3:10@30deg".parse(), Ok(NibShape::Polygon { sides: 3, radius: 10., angle: 30. }));
+        assert_eq!("polygon:3:10@-45".parse(), Ok(NibShape::Polygon { sides: 3, radius: 10., angle: -45. }));
+        for bad in ["polygon:2:10", "polygon:5:0", "polygon:5:-1", "polygon:five:10", "polygon:5", "polygon:5:10@steep", "hexagon:6:10"] {
+            assert!(bad.parse::<NibShape>().is_err(), "{}", bad);
+        }
+    }
+
+    #[test]
+    fn shapes_round_trip_through_their_names() {
+        for shape in ["ellipse:40x10@30deg", "rect:5x20@0deg", "polygon:5:12.5@90deg"] {
+            assert_eq!(shape.parse::<NibShape>().unwrap().to_string(), shape);
+        }
+    }
+
+    #[test]
+    fn polygons_are_regular_clockwise_and_start_straight_up() {
+        let contour = &"polygon:5:10".parse::<NibShape>().unwrap().to_glif().outline.unwrap()[0];
+        assert_eq!(contour.len(), 5);
+        assert!(contour[0].x.abs() < 1e-4 && (contour[0].y - 10.).abs() < 1e-4);
+        assert!(contour.iter().all(|p| ((p.x * p.x + p.y * p.y).sqrt() - 10.).abs() < 1e-4));
+        assert!(bezier::signed_area(&bezier::flatten(contour, 0.5)) < 0.);
+
+        // Turned a quarter counterclockwise, the first corner points left.
+        let contour = &"polygon:5:10@90".parse::<NibShape>().unwrap().to_glif().outline.unwrap()[0];
+        assert!((contour[0].x + 10.).abs() < 1e-4 && contour[0].y.abs() < 1e-4);
+    }
+}
@@ -1,12 +1,28 @@
-use std::fs;
+use std::path::Path as FsPath;
+#[cfg(feature = "fontforge")]
+use std::time::Duration;
 
 use clap::{App, Arg};
-use MFEKmath;
+
+use crate::boolean::{self, BooleanOptions};
+use crate::error::catch_geometry;
+use crate::glif_io::{self, InputOptions};
+use crate::glyph_filter;
+#[cfg(feature = "fontforge")]
+use crate::nib_fontforge;
+use crate::nib_shape::NibShape;
+use crate::nib_sweep;
+use crate::preview::{self, PreviewOptions};
+use crate::proof;
+use crate::remove_overlap::{self, remove_overlap_if_requested};
+use crate::svg;
+use crate::ufo;
+use crate::StrokeError;
 
 pub fn clap_app() -> clap::App<'static> {
     App::new("NIB")
         .alias("nib")
-        .about("Takes a nib and a path, both in .glif format, and emulates a pen, with the chosen nib, stroking the path.\n\nImportant note: FontForge is used for this, so it may be more unstable than other modes as FontForge is implemented in C and not memory safe. To prevent bugs, we turn off simplification and overlap removal. Use MFEK for that.")
+        .about("Takes a nib and a path, both in .glif format, and emulates a pen, with the chosen nib, stroking the path.\n\nBy default the nib is swept natively. If MFEKstroke was built with FontForge, --backend fontforge uses it instead; it may be more unstable, as FontForge is implemented in C and not memory safe. To prevent bugs, we turn off its simplification and overlap removal. Use --remove-overlap for that.")
         .version("0.1.0")
         .author("Fredrick R. Brennan <copypasteⒶkittens⊙ph>; Skef Iterum (FontForge C code)")
         .arg(Arg::new("nib")
@@ -15,15 +31,22 @@ pub fn clap_app() -> clap::App<'static> {
             .long("nib")
             .takes_value(true)
             .allow_invalid_utf8(true) // OS may use non-utf8 encoding for paths
-            .help("The path to the nib file. FontForge is quite strict about these. The .glif must contain a single closed spline, running clockwise, which represents a convex shape.")
-            .required(true))
+            .help("The path to the nib file: a .glif (or .svg) with a single closed contour, which represents a convex shape. The native backend uses the convex hull of anything else; FontForge is quite strict, and also wants it to run clockwise.")
+            .required_unless_present("nib-shape"))
+        .arg(Arg::new("nib-shape")
+            .display_order(1)
+            .long("nib-shape")
+            .takes_value(true)
+            .conflicts_with("nib")
+            .validator(|s| s.parse::<NibShape>().map(|_| ()))
+            .help("A built-in nib instead of a nib file: ellipse:WxH, rect:WxH or polygon:N:RADIUS, each optionally rotated by @ANGLE degrees, e.g. ellipse:40x10@30deg."))
         .arg(Arg::new("input")
             .display_order(2)
             .short('i')
             .long("input")
             .takes_value(true)
             .allow_invalid_utf8(true) // OS may use non-utf8 encoding for paths
-            .help("The path to the input path file.")
+            .help("The path to the input path file (.glif, .glifjson or .svg), or a .ufo font to stroke every glyph.")
             .required(true))
        .arg(Arg::new("output")
             .display_order(3)
@@ -31,7 +54,7 @@ pub fn clap_app() -> clap::App<'static> {
             .long("output")
             .takes_value(true)
             .allow_invalid_utf8(true) // OS may use non-utf8 encoding for paths
-            .help("The path where the output .glif will be saved.")
+            .help("The path where the output .glif (or .svg drawing) will be saved. Must be a .ufo if the input is a .ufo.")
             .required(true))
        .arg(Arg::new("accuracy")
             .display_order(4)
@@ -39,27 +62,127 @@ pub fn clap_app() -> clap::App<'static> {
             .long("accuracy")
             .takes_value(true)
             .default_value("0.25")
-            .help("<f64> Accuracy target")
+            .help("<f64> Accuracy target: how far from the curves, in font units, the result may stray")
             .validator(super::arg_validator_positive_f64)
             .required(false))
+       .arg(Arg::new("backend")
+            .display_order(5)
+            .long("backend")
+            .takes_value(true)
+            .possible_values(&["native", "fontforge"])
+            .default_value("native")
+            .help("What sweeps the nib. fontforge requires MFEKstroke be built with the fontforge feature."))
+       .arg(Arg::new("timeout")
+            .display_order(6)
+            .long("timeout")
+            .takes_value(true)
+            .default_value("60")
+            .validator(super::arg_validator_positive_f64)
+            .help("<f64> Seconds FontForge may take to stroke a glyph, in its own process, before it's stopped and the glyph fails."))
+       .args(remove_overlap::clap_args())
+       .args(boolean::clap_args())
+       .args(glyph_filter::clap_args())
+       .args(svg::clap_args())
+       .args(glif_io::clap_args())
+       .args(svg::output_clap_args())
+       .args(preview::clap_args())
+       .args(proof::clap_args())
+}
+
+/// What sweeps the nib along the path.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum NibBackend {
+    /// MFEKstroke's own sweep, see [`stroke_nib`].
+    Native,
+    /// FontForge, through `libfontforge`. With a timeout, each glyph is stroked in a worker process
+    /// (this executable, run as `MFEKstroke FONTFORGE-WORKER`), so that a crash or a hang in FontForge
+    /// fails only that glyph; the worker is killed if it runs longer. Without, FontForge runs in this
+    /// process, one glyph at a time.
+    #[cfg(feature = "fontforge")]
+    FontForge { timeout: Option<Duration> },
+}
+
+/// Settings for nib stroking.
+#[derive(Debug, Clone)]
+pub struct NibOptions {
+    /// The nib: a single closed contour describing a convex shape (clockwise, for FontForge).
+    pub nib: glifparser::Glif<()>,
+    /// The accuracy target: how far from the curves, in font units, the result may stray.
+    pub accuracy: f64,
+    pub backend: NibBackend,
+    /// How the native backend unites the areas the nib covers.
+    pub boolean: BooleanOptions,
 }
 
-pub fn nib_cli(matches: &clap::ArgMatches) {
-    let nib_file = matches.value_of_os("nib").unwrap();
+/// Emulates a pen with the nib `options.nib` stroking every contour of `path`.
+///
+/// The native backend flattens a curved nib to within `options.accuracy`, and unites the areas the nib
+/// covers along the path, made of copies of the path's own curves, so the result is free of overlaps and
+/// its curves are Béziers rather than lines.
+pub fn stroke_nib(path: &glifparser::Glif<()>, options: &NibOptions) -> Result<glifparser::Glif<()>, StrokeError> {
+    glif_io::require_contours(path)?;
+    match options.backend {
+        NibBackend::Native => {
+            let nib = nib_sweep::nib_polygon(&options.nib, options.accuracy)?;
+            let outline = catch_geometry(|| nib_sweep::sweep_nib(path.outline.as_ref().unwrap(), &nib, &options.boolean))??;
+            Ok(glifparser::Glif {
+                outline: Some(outline),
+                ..path.clone()
+            })
+        }
+        #[cfg(feature = "fontforge")]
+        NibBackend::FontForge { timeout } => nib_fontforge::stroke_nib_fontforge(path, &options.nib, options.accuracy, timeout),
+    }
+}
+
+pub fn nib_cli(matches: &clap::ArgMatches) -> Result<(), StrokeError> {
     let input_file = matches.value_of_os("input").unwrap();
     let output_file = matches.value_of_os("output").unwrap();
     let accuracy = matches.value_of("accuracy").unwrap();
+    let input_options = InputOptions::from_matches(matches);
 
-    let settings = MFEKmath::nib_stroking::NibSettings {
-        nib: nib_file.into(),
-        path: input_file.into(),
+    let backend = match matches.value_of("backend").unwrap() {
+        "native" => NibBackend::Native,
+        #[cfg(feature = "fontforge")]
+        "fontforge" => NibBackend::FontForge {
+            timeout: Some(Duration::from_secs_f64(matches.value_of("timeout").unwrap().parse().unwrap())), // validated by super::arg_validator_positive_f64
+        },
+        _ => return Err(StrokeError::Usage(String::from("--backend fontforge requires MFEKstroke be built with the fontforge feature"))),
+    };
+
+    let (nib, nib_description) = match matches.value_of("nib-shape") {
+        Some(shape) => {
+            let shape: NibShape = shape.parse().unwrap(); // validated by clap
+            (shape.to_glif(), shape.to_string())
+        }
+        None => {
+            let nib_file = matches.value_of_os("nib").unwrap();
+            (glif_io::read_input(nib_file, &input_options)?, FsPath::new(nib_file).display().to_string())
+        }
+    };
+
+    let options = NibOptions {
+        nib,
         accuracy: accuracy.parse().unwrap(), //validated by super::arg_validator_positive_f64
-        quiet: true,
+        backend,
+        boolean: BooleanOptions::from_matches(matches),
     };
 
-    let converted = MFEKmath::nib_stroking::convert_glif(&settings);
-    match converted {
-        Some(glifstring) => fs::write(&output_file, glifstring).expect("Unable to write file"),
-        None => eprintln!("Failed to nib stroke"),
+    let preview = PreviewOptions::from_matches(matches)?;
+
+    if ufo::is_ufo(input_file) {
+        let settings = format!("nib: {}\naccuracy: {}\nbackend: {:?}", nib_description, options.accuracy, options.backend);
+        return ufo::stroke_ufo_cli(matches, input_file.as_ref(), output_file.as_ref(), &settings, |glif| stroke_nib(glif, &options));
+    }
+
+    proof::reject_for_glif(matches)?;
+
+    let path = glif_io::read_input(input_file, &input_options)?;
+    let out = stroke_nib(&path, &options)
+        .and_then(|out| remove_overlap_if_requested(matches, out))
+        .map_err(|e| e.in_file(input_file))?;
+    if let Some(preview) = &preview {
+        preview::write_preview(&path, &out, preview)?;
     }
+    glif_io::write_output(&out, matches.is_present("svg-skeleton").then_some(&path), output_file)
 }
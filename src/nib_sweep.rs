@@ -0,0 +1,260 @@
+// Native nib stroking. A convex nib dragged along a path covers, wherever the path's direction doesn't
+// cross that of one of the nib's edges, the area between two copies of the path: one moved to the nib's
+// corner furthest to its left, the other to the corner furthest to its right. The path is split where
+// those corners change, the areas between the copies are made for each piece, the nib (or where the
+// corners change smoothly, the sliver between them) is added where the pieces meet, and all are united.
+// The copies are of the path's own Béziers, so curves stay curves; only a curved nib is flattened.
+
+use glifparser::{Contour, Glif, Outline};
+
+use crate::bezier::{self, Cubic, Pt, Segment};
+use crate::boolean::{self, BooleanOptions};
+use crate::StrokeError;
+
+fn cross(o: Pt, a: Pt, b: Pt) -> f64 {
+    (a.0 - o.0) * (b.1 - o.1) - (a.1 - o.1) * (b.0 - o.0)
+}
+
+// One half of a convex hull, from the first of `points` up to (but without) the last.
+fn half_hull<'a>(points: impl Iterator<Item = &'a Pt>) -> Vec<Pt> {
+    let mut chain: Vec<Pt> = vec![];
+    for &p in points {
+        while chain.len() >= 2 && cross(chain[chain.len() - 2], chain[chain.len() - 1], p) <= 0. {
+            chain.pop();
+        }
+        chain.push(p);
+    }
+    chain.pop(); // it starts the other half
+    chain
+}
+
+// The convex hull of `points`, counterclockwise (Andrew's monotone chain).
+fn convex_hull(mut points: Vec<Pt>) -> Vec<Pt> {
+    points.sort_by(|a, b| a.0.total_cmp(&b.0).then(a.1.total_cmp(&b.1)));
+    points.dedup();
+    if points.len() < 3 {
+        return points;
+    }
+    let mut hull = half_hull(points.iter());
+    hull.extend(half_hull(points.iter().rev()));
+    hull
+}
+
+/// The nib as a convex polygon, counterclockwise and centered on the middle of its bounding box.
+/// Curved nibs are flattened to within `tolerance`; a nib which isn't convex is replaced by its hull.
+pub(crate) fn nib_polygon(nib: &Glif<()>, tolerance: f64) -> Result<Vec<Pt>, StrokeError> {
+    let points: Vec<Pt> = nib
+        .outline
+        .iter()
+        .flatten()
+        .flat_map(|contour| bezier::flatten(contour, tolerance))
+        .collect();
+    let hull = convex_hull(points.clone());
+    if hull.len() < 3 {
+        return Err(StrokeError::Usage(String::from("the nib has no area: it needs a closed contour with at least three points")));
+    }
+    if nib.outline.as_ref().map(|o| o.len()).unwrap_or(0) > 1 || hull.len() < points.len() {
+        log::warn!("The nib isn't a single convex contour, so its convex hull is used.");
+    }
+
+    let (x0, x1) = hull.iter().fold((f64::MAX, f64::MIN), |(lo, hi), p| (lo.min(p.0), hi.max(p.0)));
+    let (y0, y1) = hull.iter().fold((f64::MAX, f64::MIN), |(lo, hi), p| (lo.min(p.1), hi.max(p.1)));
+    let center = ((x0 + x1) / 2., (y0 + y1) / 2.);
+    Ok(hull.into_iter().map(|(x, y)| (x - center.0, y - center.1)).collect())
+}
+
+// `polygon`, counterclockwise, moved by `by`.
+fn polygon_at(polygon: &[Pt], by: Pt) -> Contour<()> {
+    let mut points: Vec<Pt> = polygon.iter().map(|&(x, y)| (x + by.0, y + by.1)).collect();
+    if bezier::signed_area(&points) < 0. {
+        points.reverse();
+    }
+    let sides: Vec<Segment> = (0..points.len()).map(|i| Segment::line(points[i], points[(i + 1) % points.len()])).collect();
+    bezier::closed_contour(&sides)
+}
+
+// The indices of the corners of `nib` furthest to the left and to the right of `direction`.
+fn supports(nib: &[Pt], direction: Pt) -> (usize, usize) {
+    let side = |i: &usize| direction.0 * nib[*i].1 - direction.1 * nib[*i].0;
+    let left = (0..nib.len()).max_by(|a, b| side(a).total_cmp(&side(b))).unwrap();
+    let right = (0..nib.len()).min_by(|a, b| side(a).total_cmp(&side(b))).unwrap();
+    (left, right)
+}
+
+// Where the direction of `curve` is that of one of `nib`'s edges, or it has none.
+fn support_changes(curve: Cubic, nib: &[Pt]) -> Vec<f64> {
+    let [p0, c1, c2, p3] = curve;
+    let d = [(c1.0 - p0.0, c1.1 - p0.1), (c2.0 - c1.0, c2.1 - c1.1), (p3.0 - c2.0, p3.1 - c2.1)];
+    let mut ts: Vec<f64> = (0..nib.len())
+        .flat_map(|i| {
+            let (a, b) = (nib[i], nib[(i + 1) % nib.len()]);
+            let edge = (b.0 - a.0, b.1 - a.1);
+            // The direction, crossed with the edge, as a quadratic Bézier in t.
+            let [a0, a1, a2] = d.map(|d| d.0 * edge.1 - d.1 * edge.0);
+            bezier::quadratic_roots(a0 - 2. * a1 + a2, 2. * (a1 - a0), a0)
+        })
+        .filter(|&t| t > 1e-9 && t < 1. - 1e-9)
+        .collect();
+    ts.sort_by(f64::total_cmp);
+    ts.dedup_by(|a, b| (*a - *b).abs() < 1e-9);
+    ts
+}
+
+// The area swept by the nib's `left` and `right` corners along `curve`, counterclockwise.
+fn swept_area(curve: Cubic, line: bool, left: Pt, right: Pt) -> Contour<()> {
+    let at = |by: Pt| curve.map(|(x, y)| (x + by.0, y + by.1));
+    let (l, r) = (at(left), at(right));
+    let copy = |mut c: Cubic, reverse: bool| {
+        if reverse {
+            c.reverse();
+        }
+        if line {
+            Segment::line(c[0], c[3])
+        } else {
+            Segment::cubic(c[0], c[1], c[2], c[3])
+        }
+    };
+    bezier::closed_contour(&[copy(r, false), Segment::line(r[3], l[3]), copy(l, true), Segment::line(l[0], r[0])])
+}
+
+// The areas the convex `nib` covers along every contour of `outline`, overlapping. Every one is
+// counterclockwise, so under the nonzero rule their overlaps add up rather than cancel out.
+fn swept_areas(outline: &Outline<()>, nib: &[Pt]) -> Outline<()> {
+    let mut areas = Outline::new();
+    for contour in outline.iter().filter(|c| !c.is_empty()) {
+        let at = |i: usize| (contour[i].x as f64, contour[i].y as f64);
+        // The whole nib at every point, for the ends and corners of the path.
+        areas.extend((0..contour.len()).map(|i| polygon_at(nib, at(i))));
+
+        for (prev, cur) in bezier::segment_ends(contour) {
+            let curve = bezier::segment(contour, prev, cur);
+            let line = bezier::is_line(contour, prev, cur);
+            let mut ts = vec![0.];
+            ts.extend(support_changes(curve, nib));
+            ts.push(1.);
+
+            let mut last: Option<(usize, usize)> = None;
+            for span in ts.windows(2) {
+                let (t0, t1) = (span[0], span[1]);
+                let piece = bezier::split_cubic(bezier::split_cubic(curve, t1).0, t0 / t1).1;
+                let direction = bezier::derivative(curve, (t0 + t1) / 2.);
+                if direction.0.hypot(direction.1) <= f64::EPSILON {
+                    continue;
+                }
+                let (left, right) = supports(nib, direction);
+
+                // Where the corners change, the nib there fills the gap between the pieces: the slivers
+                // between the corners before and after, if they're neighbours, else (at a cusp, where the
+                // path turns back on itself) the whole nib.
+                let neighbours = |a: usize, b: usize| (a + nib.len() - b) % nib.len() <= 1 || (b + nib.len() - a) % nib.len() <= 1;
+                let p = piece[0];
+                match last {
+                    Some((last_left, last_right)) if neighbours(last_left, left) && neighbours(last_right, right) => {
+                        for (a, b) in [(last_left, left), (last_right, right)].into_iter().filter(|(a, b)| a != b) {
+                            areas.push(polygon_at(&[(0., 0.), nib[a], nib[b]], p));
+                        }
+                    }
+                    Some(_) => areas.push(polygon_at(nib, p)),
+                    None => (),
+                }
+                last = Some((left, right));
+                areas.push(swept_area(piece, line, nib[left], nib[right]));
+            }
+        }
+    }
+
+    areas
+}
+
+/// Sweeps the convex `nib` (see [`nib_polygon`]) along every contour of `outline`, returning the area it
+/// covers, with the path's curves kept as curves. The swept areas are united by [`boolean::simplify`].
+pub(crate) fn sweep_nib(outline: &Outline<()>, nib: &[Pt], options: &BooleanOptions) -> Result<Outline<()>, StrokeError> {
+    boolean::simplify(&swept_areas(outline, nib), options)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use glifparser::{Handle, Point, PointType};
+
+    // An open contour of one cubic.
+    fn open_cubic([p0, c1, c2, p3]: Cubic) -> Contour<()> {
+        let mut start = Point::from_x_y_type((p0.0 as f32, p0.1 as f32), PointType::Move);
+        start.a = Handle::At(c1.0 as f32, c1.1 as f32);
+        let mut end = Point::from_x_y_type((p3.0 as f32, p3.1 as f32), PointType::Curve);
+        end.b = Handle::At(c2.0 as f32, c2.1 as f32);
+        vec![start, end]
+    }
+
+    // Checks the swept areas against the nib placed all along `outline`: points well inside it somewhere
+    // must be covered, and points well outside it everywhere mustn't.
+    fn assert_sweeps(outline: &Outline<()>, nib: &[Pt]) {
+        let areas: Vec<Vec<Pt>> = swept_areas(outline, nib).iter().map(|area| bezier::flatten(area, 0.01)).collect();
+        let placed: Vec<Pt> = outline
+            .iter()
+            .flat_map(|contour| {
+                bezier::segment_ends(contour)
+                    .flat_map(|(prev, cur)| {
+                        let curve = bezier::segment(contour, prev, cur);
+                        (0..=1000).map(move |i| bezier::cubic_at(curve, i as f64 / 1000.))
+                    })
+                    .collect::<Vec<_>>()
+            })
+            .collect();
+        let nib_at = |p: Pt, scale: f64| nib.iter().map(|&(x, y)| (p.0 + x * scale, p.1 + y * scale)).collect::<Vec<_>>();
+        let ((x0, y0), (x1, y1)) = bezier::bounds(outline).unwrap();
+        for i in 0..=60 {
+            for j in 0..=60 {
+                let q = (x0 - 20. + (x1 - x0 + 40.) * i as f64 / 60., y0 - 20. + (y1 - y0 + 40.) * j as f64 / 60.);
+                let covered = areas.iter().any(|area| bezier::polygon_contains(area, q));
+                if placed.iter().any(|&p| bezier::polygon_contains(&nib_at(p, 0.95), q)) {
+                    assert!(covered, "{:?} should be covered", q);
+                } else if !placed.iter().any(|&p| bezier::polygon_contains(&nib_at(p, 1.05), q)) {
+                    assert!(!covered, "{:?} shouldn't be covered", q);
+                }
+            }
+        }
+    }
+
+    // A square nib, turned so that none of its edges are level.
+    fn tilted_square() -> Vec<Pt> {
+        let (sin, cos) = 0.5_f64.sin_cos();
+        [(-10., -4.), (10., -4.), (10., 4.), (-10., 4.)].map(|(x, y)| (x * cos - y * sin, x * sin + y * cos)).to_vec()
+    }
+
+    #[test]
+    fn sweeps_curves() {
+        let s_curve = open_cubic([(0., 0.), (120., 0.), (-20., 100.), (100., 100.)]);
+        assert_sweeps(&vec![s_curve], &tilted_square());
+    }
+
+    #[test]
+    fn sweeps_cusps() {
+        // Its handles cross, so it turns back on itself.
+        let cusp = open_cubic([(0., 0.), (100., 60.), (0., 60.), (100., 0.)]);
+        assert_sweeps(&vec![cusp], &tilted_square());
+    }
+
+    #[test]
+    fn sweeps_closed_contours_with_corners() {
+        let triangle = [(0., 0.), (100., 0.), (30., 80.)];
+        let sides: Vec<Segment> = (0..3).map(|i| Segment::line(triangle[i], triangle[(i + 1) % 3])).collect();
+        let hexagon: Vec<Pt> = (0..6).map(|i| (i as f64 * std::f64::consts::PI / 3.).sin_cos()).map(|(s, c)| (6. * c, 6. * s)).collect();
+        assert_sweeps(&vec![bezier::closed_contour(&sides)], &hexagon);
+    }
+
+    #[test]
+    fn keeps_curves() {
+        let arc = open_cubic([(0., 0.), (0., 55.), (45., 100.), (100., 100.)]);
+        let areas = swept_areas(&vec![arc], &tilted_square());
+        assert!(areas.iter().flatten().any(|p| matches!(p.a, Handle::At(..))));
+    }
+
+    #[test]
+    fn unites_the_swept_areas() {
+        let arc = open_cubic([(0., 0.), (0., 55.), (45., 100.), (100., 100.)]);
+        let swept = sweep_nib(&vec![arc], &tilted_square(), &BooleanOptions::default()).unwrap();
+        assert_eq!(swept.len(), 1);
+        assert!(swept[0].iter().any(|p| matches!(p.a, Handle::At(..))));
+    }
+}
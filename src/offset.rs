@@ -0,0 +1,206 @@
+// Offsetting: growing or shrinking the filled areas of a glyph by a distance, for bolder or lighter
+// drafts of a weight. Each closed contour is stroked with MFEKmath's VWS engine, as CWS would at twice
+// the distance, and the boolean engine unites the stroke with the glyph's filled area to grow it, or
+// takes it out of the area to shrink it. That leaves one contour for every edge of the result, where a
+// stroke has one on either side of the path, and as the stroke is centered on the path, the direction
+// each contour's points run in doesn't matter.
+
+use std::path::Path as FsPath;
+
+use clap::{App, AppSettings, Arg, ArgMatches};
+use glifparser::glif::contour_operations::vws::{InterpolationType, VWSHandle};
+use glifparser::{CapType, Contour, Glif, JoinType, Outline, VWSContour};
+use MFEKmath::variable_width_stroking::VWSSettings;
+
+use crate::bezier;
+use crate::boolean::{self, BooleanOp, BooleanOptions};
+use crate::error::{catch_geometry, parse_arg};
+use crate::glif_io::{self, InputOptions};
+use crate::glyph_filter;
+use crate::miter_limit::{self, MiterLimit};
+use crate::preview::{self, PreviewOptions};
+use crate::proof;
+use crate::remove_overlap::{self, remove_overlap_if_requested};
+use crate::svg;
+use crate::ufo::{self, Stroked};
+use crate::variable_width_stroke::stroke_vws_contours;
+use crate::StrokeError;
+
+pub fn clap_app() -> clap::App<'static> {
+    App::new("OFFSET")
+        .setting(AppSettings::DeriveDisplayOrder)
+        .setting(AppSettings::AllowNegativeNumbers)
+        .alias("offset")
+        .about("Grows (emboldens) or shrinks (lightens) the filled areas of a glyph's closed contours by a distance.")
+        .version("0.1.0")
+        .author("MFEK Authors")
+        .arg(
+            Arg::new("input")
+                .short('i')
+                .long("input")
+                .takes_value(true)
+                .allow_invalid_utf8(true)
+                .help("The path to the input .glif, .glifjson or .svg file, or a .ufo font to offset every glyph.")
+                .required(true),
+        )
+        .arg(
+            Arg::new("output")
+                .short('o')
+                .long("output")
+                .takes_value(true)
+                .allow_invalid_utf8(true)
+                .help("The path where the output .glif (or .svg drawing) will be saved. Must be a .ufo if the input is a .ufo.")
+                .required(true),
+        )
+        .arg(
+            Arg::new("distance")
+                .short('d')
+                .long("distance")
+                .takes_value(true)
+                .validator(super::arg_validator_f64)
+                .help("<f64> How far to move the edges of filled areas: outwards if positive, growing the glyph and shrinking its counters, or inwards if negative.")
+                .required(true),
+        )
+        .arg(
+            Arg::new("jointype")
+                .long("jointype")
+                .short('j')
+                .takes_value(true)
+                .possible_values(&["round", "miter", "bevel"])
+                .default_value("round")
+                .help("The shape of corners moved away from the glyph's filled area (outer corners growing, inner corners shrinking)."),
+        )
+        .args(miter_limit::clap_args())
+        .args(remove_overlap::clap_args())
+        .args(boolean::clap_args())
+        .args(glyph_filter::clap_args())
+        .args(svg::clap_args())
+        .args(glif_io::clap_args())
+        .args(svg::output_clap_args())
+        .args(preview::clap_args())
+        .args(proof::clap_args())
+}
+
+/// Settings for offsetting.
+#[derive(Debug, Clone)]
+pub struct OffsetOptions {
+    /// How far the edges of filled areas move: outwards if positive, inwards if negative.
+    pub distance: f64,
+    /// The shape of the corners made where the edges move apart.
+    pub jointype: JoinType,
+    /// The limit on the length of miter joins, if `jointype` is `JoinType::Miter`.
+    pub miter_limit: Option<MiterLimit>,
+    /// How the edges are combined with the glyph.
+    pub boolean: BooleanOptions,
+}
+
+/// Offsets the area filled by `closed`, which must all be closed contours.
+pub(crate) fn offset_outline(closed: &Outline<()>, options: &OffsetOptions) -> Result<Outline<()>, StrokeError> {
+    let width = options.distance.abs();
+    let handle = VWSHandle {
+        left_offset: width,
+        right_offset: width,
+        tangent_offset: 0.0,
+        interpolation: InterpolationType::Linear,
+    };
+    let vws_contours: Vec<VWSContour> = closed
+        .iter()
+        .map(|contour| VWSContour {
+            join_type: options.jointype,
+            cap_start_type: CapType::Circle, // closed contours have no caps
+            cap_end_type: CapType::Circle,
+            handles: vec![handle.clone(); contour.len() + 1],
+            remove_internal: false,
+            remove_external: false,
+        })
+        .collect();
+    let mut skeleton: Glif<()> = Glif::new();
+    skeleton.outline = Some(closed.clone());
+    let vws_settings = VWSSettings {
+        cap_custom_start: None,
+        cap_custom_end: None,
+    };
+
+    let mut stroke = stroke_vws_contours(&skeleton, &vws_contours, &vws_settings, false);
+    if let (Some(limit), JoinType::Miter) = (options.miter_limit.as_ref(), options.jointype) {
+        miter_limit::limit_miters(&mut stroke, closed, &[width], limit);
+    }
+
+    let op = if options.distance > 0. { BooleanOp::Union } else { BooleanOp::Difference };
+    boolean::boolean(closed, &stroke, op, &options.boolean)
+}
+
+fn bounds_area(contour: &Contour<()>) -> bool {
+    contour.len() > 1 && !bezier::is_open(contour)
+}
+
+/// Moves the edges of the areas filled by `path`'s closed contours `options.distance` outwards (or
+/// inwards, if negative). Open contours bound no area, and are copied as they are.
+pub fn offset(path: &Glif<()>, options: &OffsetOptions) -> Result<Glif<()>, StrokeError> {
+    glif_io::require_contours(path)?;
+    let (closed, open): (Outline<()>, Outline<()>) = path.outline.iter().flatten().cloned().partition(bounds_area);
+
+    let mut output_outline = if closed.is_empty() || options.distance == 0. {
+        closed
+    } else {
+        catch_geometry(|| offset_outline(&closed, options))??
+    };
+    output_outline.extend(open);
+
+    Ok(Glif {
+        outline: Some(output_outline),
+        ..path.clone()
+    })
+}
+
+// The indices of the open contours of `path`, which `offset` copies as they are.
+fn open_contours(path: &Glif<()>) -> Vec<usize> {
+    path.outline.iter().flatten().enumerate().filter(|(_, contour)| !bounds_area(contour)).map(|(i, _)| i).collect()
+}
+
+pub fn offset_cli(matches: &ArgMatches) -> Result<(), StrokeError> {
+    let input_file = matches.value_of_os("input").unwrap();
+    let output_file = matches.value_of_os("output").unwrap();
+    let input_options = InputOptions::from_matches(matches);
+
+    let options = OffsetOptions {
+        distance: matches.value_of("distance").unwrap().parse().unwrap(), // validated by arg_validator_f64
+        jointype: parse_arg("jointype", matches.value_of("jointype").unwrap())?,
+        miter_limit: MiterLimit::from_matches(matches)?,
+        boolean: BooleanOptions::from_matches(matches),
+    };
+
+    let preview = PreviewOptions::from_matches(matches)?;
+
+    if ufo::is_ufo(input_file) {
+        let settings = format!(
+            "distance: {}\njoin: {}\nmiter limit: {}",
+            options.distance,
+            matches.value_of("jointype").unwrap(),
+            miter_limit::describe(options.miter_limit.as_ref())
+        );
+        return ufo::stroke_ufo_cli(matches, input_file.as_ref(), output_file.as_ref(), &settings, |glif| {
+            Ok(Stroked {
+                glif: offset(glif, &options)?,
+                copied_contours: open_contours(glif),
+            })
+        });
+    }
+
+    proof::reject_for_glif(matches)?;
+
+    let path = glif_io::read_input(input_file, &input_options)?;
+    let out = offset(&path, &options)
+        .and_then(|out| remove_overlap_if_requested(matches, out))
+        .map_err(|e| e.in_file(input_file))?;
+    let open: Vec<String> = open_contours(&path).iter().map(usize::to_string).collect();
+    if !open.is_empty() {
+        eprintln!("{}: open contour(s) {} bound no area, and were copied as they are", FsPath::new(input_file).display(), open.join(", "));
+    }
+    glif_io::write_output(&out, matches.is_present("svg-skeleton").then_some(&path), output_file)?;
+    if let Some(preview) = &preview {
+        preview::write_preview(&path, &out, preview)?;
+    }
+
+    Ok(())
+}
@@ -1,9 +1,8 @@
-use std::fs;
-
 use float_cmp::ApproxEq as _;
 use float_cmp::F64Margin;
 use MFEKmath::pattern_along_glif;
-use glifparser::glif::contour_operations::pap::{PatternCopies, PatternStretch, PatternSubdivide};
+use glifparser::glif::contour_operations::pap::{PatternCopies, PatternCulling, PatternStretch, PatternSubdivide};
+use glifparser::{Contour, JoinType, Outline};
 use MFEKmath::vec2;
 use MFEKmath::vector::Vector;
 use MFEKmath::EvalScale;
@@ -12,6 +11,19 @@ use MFEKmath::PatternSettings;
 
 use clap::{App, AppSettings, Arg};
 
+use crate::bezier;
+use crate::boolean::{self, BooleanOp, BooleanOptions};
+use crate::error::catch_geometry;
+use crate::glif_io::{self, InputOptions};
+use crate::glyph_filter;
+use crate::offset::{self, OffsetOptions};
+use crate::preview::{self, PreviewOptions};
+use crate::proof;
+use crate::remove_overlap::{self, remove_overlap_if_requested};
+use crate::svg;
+use crate::ufo;
+use crate::StrokeError;
+
 pub fn clap_app() -> clap::App<'static> {
     App::new("PAP")
             .setting(AppSettings::DeriveDisplayOrder)
@@ -29,7 +41,7 @@ pub fn clap_app() -> clap::App<'static> {
                 //.allow_invalid_utf8(true)
                 .required_unless_present_any(&["dot-pattern", "dash-pattern"])
                 .conflicts_with_all(&["dot-pattern", "dash-pattern"])
-                .help("The path to the input pattern file. You may also provide either --dot-pattern or --dash-pattern to use built-in patterns."))
+                .help("The path to the input pattern file (.glif, .glifjson or .svg). You may also provide either --dot-pattern or --dash-pattern to use built-in patterns."))
             .arg(Arg::new("dash-pattern")
                 .long("dash-pattern")
                 .short('=')
@@ -49,7 +61,7 @@ pub fn clap_app() -> clap::App<'static> {
                 .short('P')
                 .takes_value(true)
                 //.allow_invalid_utf8(true)
-                .help("The path to the input path file.")
+                .help("The path to the input path file (.glif, .glifjson or .svg), or a .ufo font to pattern every glyph.")
                 .required(true))
             .arg(Arg::new("output")
                 .long("output")
@@ -57,7 +69,7 @@ pub fn clap_app() -> clap::App<'static> {
                 .short('o')
                 .takes_value(true)
                 //.allow_invalid_utf8(true)
-                .help("The path where the output will be saved. If omitted, or `-`, stdout.\n\n\n"))
+                .help("The path where the output .glif (or .svg drawing) will be saved. If omitted, or `-`, stdout. Required, and must be a .ufo, if --path is a .ufo.\n\n\n"))
             .arg(Arg::new("contour")
                 .long("contour")
                 .short('c')
@@ -138,12 +150,12 @@ pub fn clap_app() -> clap::App<'static> {
             .arg(Arg::new("simplify")
                 .short('S')
                 .long("simplify")
-                .help("<boolean> if we should run the result through Skia's (buggy) simplify routine."))
+                .help("<boolean> if we should unite the overlapping contours of the result (see --boolean-tolerance)."))
             .arg(Arg::new("remove_overlapping")
                 .long("remove-overlapping")
                 .short('O')
                 .conflicts_with_all(&["erase_overlapping_stroke_width", "erase_overlapping", "erase_overlapping_area_percent"])
-                .help("Remove patterns that would overlap."))
+                .help("Remove patterns that would overlap. (--remove-overlap instead unites the overlapping contours of the result.)"))
             .arg(Arg::new("erase_overlapping")
                 .long("erase-overlapping")
                 .short('Z')
@@ -167,6 +179,7 @@ pub fn clap_app() -> clap::App<'static> {
             .arg(Arg::new("one-pass")
                 .long("one-pass")
                 .short('Q')
+                .conflicts_with("erase_overlapping")
                 .help("<boolean> whether we should not reflow the path after culling during overdraw (faster but worse)."))
             .arg(Arg::new("no-center-pattern")
                 .long("no-center-pattern")
@@ -181,16 +194,130 @@ pub fn clap_app() -> clap::App<'static> {
                 .long("reverse-culling")
                 .short('R')
                 .help("<boolean> true will reverse the order we check for overlaps during overlap culling.\n\n\n"))
+            .args(remove_overlap::clap_args())
+            .args(boolean::clap_args())
+            .args(glyph_filter::clap_args())
+            .args(svg::clap_args())
+            .args(glif_io::clap_args())
+            .args(svg::output_clap_args())
+            .args(preview::clap_args())
+            .args(proof::clap_args())
+}
+
+/// Settings for pattern along path.
+#[derive(Debug)]
+pub struct PapOptions {
+    /// The glyph to repeat along the path.
+    pub pattern: glifparser::Glif<()>,
+    /// Erasing overlaps (`PatternCulling::EraseOverlapping`) is done in one pass, whatever
+    /// `two_pass_culling` says.
+    pub settings: PatternSettings,
+    /// Only pattern this contour of the path, by index. If `None`, or out of range, every contour is patterned.
+    pub target_contour: Option<usize>,
+    /// How `settings.simplify` unites the copies of the pattern, and `PatternCulling::EraseOverlapping`
+    /// cuts them.
+    pub boolean: BooleanOptions,
+}
+
+fn area(outline: &Outline<()>) -> f64 {
+    outline.iter().map(|contour| bezier::signed_area(&bezier::flatten(contour, 0.5))).sum::<f64>().abs()
+}
+
+fn bounds_overlap(a: &[Contour<()>], b: &[Contour<()>]) -> bool {
+    match (bezier::bounds(a), bezier::bounds(b)) {
+        (Some((a0, a1)), Some((b0, b1))) => a0.0 <= b1.0 && b0.0 <= a1.0 && a0.1 <= b1.1 && b0.1 <= a1.1,
+        _ => false,
+    }
+}
+
+// Erases from each copy of the pattern the area underneath the copies after it (before it, if
+// `reverse`), grown by half of `stroke_width` all round. What's left of a copy is dropped if it's less
+// than `area_percent` percent of the copy's area.
+fn erase_overlapping(
+    mut copies: Vec<Outline<()>>,
+    stroke_width: f64,
+    area_percent: f64,
+    reverse: bool,
+    options: &BooleanOptions,
+) -> Result<Outline<()>, StrokeError> {
+    if reverse {
+        copies.reverse();
+    }
+    let grow = OffsetOptions {
+        distance: stroke_width / 2.,
+        jointype: JoinType::Round,
+        miter_limit: None,
+        boolean: *options,
+    };
+    let areas: Vec<f64> = copies.iter().map(area).collect();
+
+    let mut erased: Vec<Outline<()>> = vec![];
+    for copy in copies {
+        let eraser = if stroke_width > 0. { offset::offset_outline(&copy, &grow)? } else { copy.clone() };
+        for earlier in erased.iter_mut().filter(|earlier| bounds_overlap(earlier, &eraser)) {
+            *earlier = boolean::boolean(earlier, &eraser, BooleanOp::Difference, options)?;
+        }
+        erased.push(copy);
+    }
+    Ok(erased
+        .into_iter()
+        .zip(areas)
+        .filter(|(copy, copy_area)| area(copy) >= copy_area * area_percent / 100.)
+        .flat_map(|(copy, _)| copy)
+        .collect())
+}
+
+/// Maps `options.pattern` along every contour (or the targeted contour) of `path`.
+pub fn stroke_pattern(path: &glifparser::Glif<()>, options: &PapOptions) -> Result<glifparser::Glif<()>, StrokeError> {
+    let contours = path.outline.as_ref().map(|o| o.len()).unwrap_or(0);
+    let target_contour = options.target_contour.filter(|&idx| idx < contours);
+    if target_contour != options.target_contour {
+        log::warn!("Contour {:?} out of range, patterning every contour", options.target_contour);
+    }
+
+    glif_io::require_contours(path)?;
+    glif_io::require_contours(&options.pattern)?;
+
+    // Simplifying and erasing overlaps are done here, with the boolean engine, rather than by MFEKmath
+    // with Skia's path ops.
+    let erase = match options.settings.cull_overlap {
+        PatternCulling::EraseOverlapping(stroke_width, area_percent) => Some((stroke_width, area_percent)),
+        _ => None,
+    };
+    let mut settings = options.settings.clone();
+    settings.simplify = false;
+    if erase.is_some() {
+        settings.cull_overlap = PatternCulling::Off;
+    }
+    let mut out = catch_geometry(|| pattern_along_glif(path, &options.pattern, &settings, target_contour))?;
+
+    if let Some((stroke_width, area_percent)) = erase {
+        // Each copy of the pattern is as many contours as the pattern, one after the other.
+        let copy_len = options.pattern.outline.as_ref().map(|o| o.len()).unwrap_or(0);
+        let outline = out.outline.take().unwrap_or_default();
+        if copy_len > 0 && outline.len() % copy_len == 0 {
+            let copies = outline.chunks(copy_len).map(|copy| copy.to_vec()).collect();
+            let reverse = options.settings.reverse_culling;
+            out.outline = Some(catch_geometry(|| erase_overlapping(copies, stroke_width, area_percent, reverse, &options.boolean))??);
+        } else {
+            log::warn!("Couldn't tell the copies of the pattern apart, so they're erased by MFEKmath");
+            settings.cull_overlap = PatternCulling::EraseOverlapping(stroke_width, area_percent);
+            out = catch_geometry(|| pattern_along_glif(path, &options.pattern, &settings, target_contour))?;
+        }
+    }
+    if options.settings.simplify {
+        if let Some(outline) = out.outline.as_mut() {
+            *outline = boolean::simplify(outline, &options.boolean)?;
+        }
+    }
+    Ok(out)
 }
 
-pub fn pap_cli(matches: &clap::ArgMatches) {
+pub fn pap_cli(matches: &clap::ArgMatches) -> Result<(), StrokeError> {
     let path_string = matches.value_of("path").unwrap(); // required options shouldn't panic
     let pattern_string = matches.value_of("pattern");
     let output_string = matches.value_of("output");
-
-    // TODO: Handle errors properly!
-    let path: glifparser::Glif<()> = glifparser::read(&fs::read_to_string(path_string).expect("Failed to read path file!"))
-        .expect("glifparser couldn't parse input path glif. Invalid glif?");
+    let input_options = InputOptions::from_matches(matches);
 
     let pattern: glifparser::Glif<()> = match pattern_string {
         None => {
@@ -213,8 +340,7 @@ pub fn pap_cli(matches: &clap::ArgMatches) {
                 unreachable!()
             }
         }
-        Some(pattern) => glifparser::read(&fs::read_to_string(pattern).expect("Failed to read pattern file!"))
-            .expect("glifparser couldn't parse input pattern glif. Invalid glif?"),
+        Some(pattern) => glif_io::read_input(pattern, &input_options)?,
     };
 
     let mut settings = PatternSettings {
@@ -316,6 +442,54 @@ pub fn pap_cli(matches: &clap::ArgMatches) {
         settings.stretch = PatternStretch::Off;
     }
 
+    settings.two_pass_culling = !matches.is_present("one-pass");
+    settings.reverse_path = matches.is_present("reverse");
+    settings.reverse_culling = matches.is_present("reverse-culling");
+
+    let preview = PreviewOptions::from_matches(matches)?;
+
+    if ufo::is_ufo(path_string) {
+        let output_ufo = match output_string {
+            Some(o) if o != "-" => o,
+            _ => return Err(StrokeError::Usage(String::from("An output .ufo must be given with --output when --path is a .ufo"))),
+        };
+        let options = PapOptions {
+            pattern,
+            settings,
+            target_contour: matches.value_of("contour").and_then(|c| usize::try_from(c.parse::<isize>().unwrap()).ok()),
+            boolean: BooleanOptions::from_matches(matches),
+        };
+        let s = &options.settings;
+        let subdivide = match s.subdivide {
+            PatternSubdivide::Off => String::from("off"),
+            PatternSubdivide::Simple(n) => format!("{} times", n),
+            PatternSubdivide::Angle(a) => format!("at angles over {}", a),
+        };
+        let overlaps = match s.cull_overlap {
+            PatternCulling::Off => String::from("kept"),
+            PatternCulling::RemoveOverlapping => format!("removed{}", if s.two_pass_culling { " in two passes" } else { "" }),
+            PatternCulling::EraseOverlapping(width, percent) => format!("erased {} wide, dropping what's left under {}%", width, percent),
+        };
+        let settings = [
+            format!("pattern: {}", pattern_string.unwrap_or("(built in)")),
+            format!("contour: {}", options.target_contour.map(|c| c.to_string()).unwrap_or_else(|| String::from("all"))),
+            format!("mode: {:?}", s.copies).to_lowercase(),
+            format!("scale: {} x, {} y", s.pattern_scale.x, s.pattern_scale.y),
+            format!("spacing: {}, stretch: {:?}", s.spacing, s.stretch).to_lowercase(),
+            format!("offset: {} normal, {} tangent", s.normal_offset, s.tangent_offset),
+            format!("subdivide: {}", subdivide),
+            format!("overlaps: {}{}", overlaps, if s.reverse_culling { ", last first" } else { "" }),
+            format!("simplify: {}\nwarp: {}\ncentered: {}", s.simplify, s.warp_pattern, s.center_pattern),
+            format!("reversed: {}\nsplit at discontinuities: {}", s.reverse_path, s.split_path),
+        ]
+        .join("\n");
+        return ufo::stroke_ufo_cli(matches, path_string.as_ref(), output_ufo.as_ref(), &settings, |glif| stroke_pattern(glif, &options));
+    }
+
+    proof::reject_for_glif(matches)?;
+
+    let path = glif_io::read_input(path_string, &input_options)?;
+
     let mut target_contour = None;
     if let Some(contour) = matches.value_of("contour") {
         let idx = contour.parse::<isize>().unwrap();
@@ -327,19 +501,29 @@ pub fn pap_cli(matches: &clap::ArgMatches) {
         }
     }
 
-    settings.two_pass_culling = !matches.is_present("one-pass");
-    settings.reverse_path = matches.is_present("reverse");
-    settings.reverse_culling = matches.is_present("reverse-culling");
-
-    let output = pattern_along_glif(&path, &pattern, &settings, target_contour);
-    let glifstring = glifparser::write(&output).expect("glifparser failed to understand output of PaP?"); // TODO: Proper error handling.
+    let options = PapOptions {
+        pattern,
+        settings,
+        target_contour,
+        boolean: BooleanOptions::from_matches(matches),
+    };
+    let output = stroke_pattern(&path, &options)
+        .and_then(|out| remove_overlap_if_requested(matches, out))
+        .map_err(|e| e.in_file(path_string))?;
+    if let Some(preview) = &preview {
+        preview::write_preview(&path, &output, preview)?;
+    }
+    if let Some(output_file) = output_string.filter(svg::is_svg) {
+        return glif_io::write_output(&output, matches.is_present("svg-skeleton").then_some(&path), output_file);
+    }
+    let glifstring = glif_io::glif_to_string(&output, output_string.unwrap_or("-"))?;
     if let Some(output_file) = output_string {
         if output_file != "-" {
             // common stand-in for stdout on *nix
-            fs::write(output_file, &glifstring).expect("Unable to write output file");
-            return;
+            return glif_io::write_file(output_file, &glifstring);
         }
     }
 
     print!("{}", glifstring);
+    Ok(())
 }
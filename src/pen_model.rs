@@ -0,0 +1,210 @@
+// A broad nib pen, generating VWS handles. Gerrit Noordzij describes two ways a pen makes contrast: by
+// translation, where a nib of fixed width held at a fixed angle makes thick strokes across the nib and
+// thin ones along it; and by expansion, where pressure spreads the nib, so the width follows it instead.
+//
+// A rectangular nib moving along the path in direction φ, held at angle θ, is widest across the path at
+// one of its corners, and the opposite corner makes the other side of the stroke. So each side is as far
+// from the path as that corner, turned from the normal by the corner's angle to it, which is what a VWS
+// handle's offsets and tangent offset describe. The handles sit on the points of the path, subdivided
+// as for width curves, so they can follow its direction around curves.
+
+use std::fmt;
+use std::str::FromStr;
+
+use glifparser::glif::contour_operations::vws::{InterpolationType, VWSHandle};
+use glifparser::Contour;
+
+use crate::bezier::{self, Pt};
+
+/// A pen setting which may change along each contour: linearly from `start`, at its beginning, to `end`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Ramp {
+    pub start: f64,
+    pub end: f64,
+}
+
+impl Ramp {
+    pub fn constant(value: f64) -> Self {
+        Ramp { start: value, end: value }
+    }
+
+    /// The setting `t` of the way along the contour.
+    pub fn at(&self, t: f64) -> f64 {
+        self.start + (self.end - self.start) * t
+    }
+}
+
+/// Parses `VALUE` or `START:END`.
+impl FromStr for Ramp {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let number = |n: &str| n.trim().parse::<f64>().map_err(|_| format!("{:?} isn't a number", n));
+        match s.split_once(':') {
+            Some((start, end)) => Ok(Ramp {
+                start: number(start)?,
+                end: number(end)?,
+            }),
+            None => number(s).map(Ramp::constant),
+        }
+    }
+}
+
+impl fmt::Display for Ramp {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if self.start == self.end {
+            write!(f, "{}", self.start)
+        } else {
+            write!(f, "{}:{}", self.start, self.end)
+        }
+    }
+}
+
+/// How the pen makes contrast.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum PenModel {
+    /// The nib keeps its size; the width of the stroke comes from its direction against the nib's.
+    Translation,
+    /// The nib's width is scaled by `pressure` (1 being its own width), as a pointed pen spreads.
+    Expansion { pressure: Ramp },
+}
+
+/// A broad nib pen, see [`VwsOptions::pen`](crate::VwsOptions::pen).
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Pen {
+    /// The width of the nib's edge.
+    pub width: Ramp,
+    /// The thickness of the nib, across its edge; 0 for an ideal, flat nib.
+    pub thickness: Ramp,
+    /// The angle of the nib's edge, in degrees counterclockwise from the baseline.
+    pub angle: Ramp,
+    pub model: PenModel,
+}
+
+// The direction of `contour` at each of its points, and at its end: leaving each point, but for the end
+// of the last segment, which is arrived at.
+fn directions(contour: &Contour<()>) -> Vec<Pt> {
+    let segments: Vec<[Pt; 4]> = bezier::segment_ends(contour).map(|(prev, cur)| bezier::segment(contour, prev, cur)).collect();
+    let last = segments.last().map(|s| bezier::end_direction(*s)).unwrap_or((1., 0.));
+    (0..=contour.len()).map(|i| segments.get(i).map(|s| bezier::start_direction(*s)).unwrap_or(last)).collect()
+}
+
+impl Pen {
+    /// The handle of a stroke by this pen, `t` of the way along the path, going in `direction`.
+    fn handle(&self, t: f64, direction: Pt, interpolation: InterpolationType) -> VWSHandle {
+        let pressure = match self.model {
+            PenModel::Translation => 1.,
+            PenModel::Expansion { pressure } => pressure.at(t).max(0.),
+        };
+        let (half_width, half_thickness) = (self.width.at(t).max(0.) * pressure / 2., self.thickness.at(t).max(0.) / 2.);
+
+        // The nib's edge and its thickness, each as (along the path, across it to the left).
+        let relative = self.angle.at(t).to_radians() - direction.1.atan2(direction.0);
+        let (sin, cos) = relative.sin_cos();
+        let (edge, across) = ((cos, sin), (-sin, cos));
+        // The corner furthest to the left; the opposite one is as far to the right.
+        let sign = |x: f64| if x < 0. { -1. } else { 1. };
+        let (e, a) = (half_width * sign(edge.1), half_thickness * sign(across.1));
+        let corner = (e * edge.0 + a * across.0, e * edge.1 + a * across.1);
+
+        let offset = corner.0.hypot(corner.1);
+        VWSHandle {
+            left_offset: offset,
+            right_offset: offset,
+            tangent_offset: if offset > 0. { corner.0.atan2(corner.1) } else { 0. },
+            interpolation,
+        }
+    }
+
+    /// The VWS handles of a stroke of `contour` by this pen, one per point and one for its end, each
+    /// interpolated to the next by `interpolation`. The contour should have points close enough together
+    /// for the handles to follow its curves.
+    pub(crate) fn handles(&self, contour: &Contour<()>, interpolation: InterpolationType) -> Vec<VWSHandle> {
+        let lengths = bezier::point_arc_lengths(contour);
+        let total = *lengths.last().unwrap();
+        lengths
+            .iter()
+            .zip(directions(contour))
+            .map(|(length, direction)| {
+                let t = if total > 0. { length / total } else { 0. };
+                self.handle(t, direction, interpolation)
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::f64::consts::FRAC_1_SQRT_2;
+
+    use glifparser::{Point, PointType};
+
+    use super::*;
+
+    fn pen(width: &str, thickness: &str, angle: &str, model: PenModel) -> Pen {
+        Pen {
+            width: width.parse().unwrap(),
+            thickness: thickness.parse().unwrap(),
+            angle: angle.parse().unwrap(),
+            model,
+        }
+    }
+
+    // How far the left side of the stroke is from the path, across it.
+    fn half_width(handle: &VWSHandle) -> f64 {
+        handle.left_offset * handle.tangent_offset.cos()
+    }
+
+    fn assert_near(a: f64, b: f64) {
+        assert!((a - b).abs() < 1e-9, "{} isn't {}", a, b);
+    }
+
+    #[test]
+    fn a_flat_nib_is_widest_across_its_angle() {
+        let pen = pen("40", "0", "30", PenModel::Translation);
+        let width = |direction: Pt| half_width(&pen.handle(0., direction, InterpolationType::Linear));
+        // Going right, half the nib's 20 units, turned 30° from the path, are across it.
+        assert_near(width((1., 0.)), 10.);
+        assert_near(width((0., 1.)), 20. * 60f64.to_radians().sin());
+        assert_near(width((-1., 0.)), 10.);
+        // Along the nib's edge, the stroke is a hairline; across it, the nib's whole width.
+        assert_near(width((30f64.to_radians().cos(), 30f64.to_radians().sin())), 0.);
+        assert_near(width((-30f64.to_radians().sin(), 30f64.to_radians().cos())), 20.);
+        // Either way, the handle reaches the nib's corner.
+        assert_near(pen.handle(0., (FRAC_1_SQRT_2, FRAC_1_SQRT_2), InterpolationType::Linear).left_offset, 20.);
+    }
+
+    #[test]
+    fn a_thick_nib_leaves_no_hairline() {
+        let pen = pen("40", "6", "0", PenModel::Translation);
+        let handle = pen.handle(0., (1., 0.), InterpolationType::Linear);
+        assert_near(half_width(&handle), 3.);
+        assert_near(handle.left_offset, 20f64.hypot(3.));
+        assert_near(half_width(&pen.handle(0., (0., 1.), InterpolationType::Linear)), 20.);
+    }
+
+    #[test]
+    fn settings_follow_their_ramps() {
+        let pen = pen("40", "0", "0:90", PenModel::Expansion { pressure: "1:2".parse().unwrap() });
+        // Going up, the nib starts across the path, and ends along it at twice its width.
+        assert_near(half_width(&pen.handle(0., (0., 1.), InterpolationType::Linear)), 20.);
+        assert_near(half_width(&pen.handle(1., (0., 1.), InterpolationType::Linear)), 0.);
+        assert_near(pen.handle(1., (0., 1.), InterpolationType::Linear).left_offset, 40.);
+    }
+
+    #[test]
+    fn handles_follow_the_path() {
+        let contour: Contour<()> = [(0., 0., PointType::Move), (100., 0., PointType::Line), (100., 100., PointType::Line)]
+            .iter()
+            .map(|&(x, y, ptype)| Point::from_x_y_type((x, y), ptype))
+            .collect();
+        let handles = pen("40", "0", "30", PenModel::Translation).handles(&contour, InterpolationType::Linear);
+        assert_eq!(handles.len(), 4);
+        let widths: Vec<f64> = handles.iter().map(half_width).collect();
+        assert_near(widths[0], 10.);
+        // The corner's handle leaves it going up, as does the end's.
+        for width in &widths[1..] {
+            assert_near(*width, 20. * 60f64.to_radians().sin());
+        }
+    }
+}
@@ -0,0 +1,132 @@
+// PNG previews of a stroking run: the unstroked path and the stroked result side by side, rasterized
+// on the CPU with the Skia that MFEKmath already links, so results can be checked (e.g. in CI
+// artifacts) without opening an editor.
+
+use std::io;
+use std::path::PathBuf as FsPathBuf;
+
+use clap::{Arg, ArgMatches};
+use glifparser::Glif;
+use MFEKmath::skia_safe::{png_encoder, surfaces, Color, Paint, PaintStyle, Rect};
+
+use crate::glif_io;
+use crate::skia_outline;
+use crate::svg::{self, PathOp};
+use crate::StrokeError;
+
+const MAX_SIZE: u32 = 8192;
+
+pub fn clap_args() -> Vec<Arg<'static>> {
+    vec![
+        Arg::new("preview")
+            .long("preview")
+            .takes_value(true)
+            .allow_invalid_utf8(true)
+            .help_heading("PREVIEW")
+            .help("Also render the input path and the result side by side to this .png."),
+        Arg::new("preview-size")
+            .long("preview-size")
+            .takes_value(true)
+            .default_value("512")
+            .validator(super::arg_validator_usize)
+            .help_heading("PREVIEW")
+            .help("<usize> Height in pixels of the preview; each half is square."),
+    ]
+}
+
+/// Where to write a preview, and how big.
+#[derive(Debug, Clone)]
+pub struct PreviewOptions {
+    pub path: FsPathBuf,
+    /// Height of the image in pixels. It's twice as wide.
+    pub size: u32,
+}
+
+impl PreviewOptions {
+    /// The preview requested with --preview, if any.
+    pub fn from_matches(matches: &ArgMatches) -> Result<Option<Self>, StrokeError> {
+        let path = match matches.value_of_os("preview") {
+            Some(path) => FsPathBuf::from(path),
+            None => return Ok(None),
+        };
+        let size: u32 = matches.value_of("preview-size").unwrap().parse().unwrap_or(0); // validated by arg_validator_usize
+        if size == 0 || size > MAX_SIZE {
+            return Err(StrokeError::Usage(format!("--preview-size must be between 1 and {}", MAX_SIZE)));
+        }
+        Ok(Some(PreviewOptions { path, size }))
+    }
+}
+
+/// Fails with a usage error if a preview was requested of a .ufo run, which has no single result to show.
+pub fn reject_for_ufo(matches: &ArgMatches) -> Result<(), StrokeError> {
+    if matches.is_present("preview") {
+        Err(StrokeError::Usage(String::from("--preview can't be used when stroking a .ufo")))
+    } else {
+        Ok(())
+    }
+}
+
+fn paint(color: Color, style: PaintStyle, stroke_width: f32) -> Paint {
+    let mut paint = Paint::default();
+    paint.set_anti_alias(true).set_color(color).set_style(style).set_stroke_width(stroke_width);
+    paint
+}
+
+/// Renders `skeleton` (left, outlined) and `result` (right, filled) side by side as a PNG, `size` pixels
+/// high. Both halves share one scale, fitted to the advance width, the baseline and both outlines.
+pub fn render_preview(skeleton: &Glif<()>, result: &Glif<()>, size: u32) -> Option<Vec<u8>> {
+    let empty = vec![];
+    let skeleton_ops = svg::outline_ops(skeleton.outline.as_ref().unwrap_or(&empty));
+    let result_ops = svg::outline_ops(result.outline.as_ref().unwrap_or(&empty));
+
+    let width = result.width.or(skeleton.width).unwrap_or(0) as f64;
+    let both: Vec<PathOp> = skeleton_ops.iter().chain(result_ops.iter()).copied().collect();
+    let (x0, y0, x1, y1) = match svg::ops_bounds(&both) {
+        Some((x0, y0, x1, y1)) => (x0.min(0.), y0.min(0.), x1.max(width), y1.max(0.)),
+        None => (0., 0., width, 0.),
+    };
+
+    // Fit the drawing into each square half, leaving a margin of 5% all around.
+    let half = size as f32;
+    let extent = (x1 - x0).max(y1 - y0).max(1.) as f32;
+    let scale = half * 0.9 / extent;
+    let (cx, cy) = (((x0 + x1) / 2.) as f32, ((y0 + y1) / 2.) as f32);
+
+    let mut surface = surfaces::raster_n32_premul(((size * 2) as i32, size as i32))?;
+    let canvas = surface.canvas();
+    canvas.clear(Color::WHITE);
+    let guide = paint(Color::from_rgb(0xcc, 0xcc, 0xcc), PaintStyle::Stroke, 1. / scale);
+
+    let halves = [
+        (&skeleton_ops, PaintStyle::Stroke, Color::from_rgb(0xe0, 0x30, 0x1e)),
+        (&result_ops, PaintStyle::Fill, Color::BLACK),
+    ];
+    for (i, (ops, style, color)) in halves.into_iter().enumerate() {
+        canvas.save();
+        canvas.translate((half * (i as f32 + 0.5), half / 2.));
+        canvas.scale((scale, -scale));
+        canvas.translate((-cx, -cy));
+
+        // The baseline and the advance width.
+        canvas.draw_line((x0 as f32, 0.), (x1 as f32, 0.), &guide);
+        canvas.draw_line((0., y0 as f32), (0., y1 as f32), &guide);
+        canvas.draw_line((width as f32, y0 as f32), (width as f32, y1 as f32), &guide);
+
+        canvas.draw_path(&skia_outline::ops_to_skia_path(ops), &paint(color, style, 2. / scale));
+        canvas.restore();
+    }
+    canvas.draw_rect(Rect::from_xywh(half - 0.5, 0., 1., half), &paint(Color::from_rgb(0x99, 0x99, 0x99), PaintStyle::Fill, 0.));
+
+    let pixmap = surface.peek_pixels()?;
+    let mut png = vec![];
+    png_encoder::encode(&pixmap, &mut png, &png_encoder::Options::default()).then_some(png)
+}
+
+/// Renders a preview of stroking `skeleton` into `result`, and writes it as requested.
+pub fn write_preview(skeleton: &Glif<()>, result: &Glif<()>, options: &PreviewOptions) -> Result<(), StrokeError> {
+    let png = render_preview(skeleton, result, options.size).ok_or_else(|| StrokeError::Write {
+        path: options.path.clone(),
+        source: io::Error::new(io::ErrorKind::Other, "failed to render preview"),
+    })?;
+    glif_io::write_file(&options.path, png)
+}
@@ -0,0 +1,159 @@
+// Proof sheets for batch runs: a single self-contained HTML page showing every glyph that was stroked
+// (or failed to be), before and after, with its point and contour counts, the settings used, and
+// anything that looks wrong with the result.
+
+use std::path::Path as FsPath;
+
+use clap::{Arg, ArgMatches};
+use glifparser::{Glif, PointType};
+
+use crate::glif_io;
+use crate::svg::{self, escape};
+use crate::ufo::{BatchReport, GlyphReport, GlyphStatus};
+use crate::StrokeError;
+
+pub fn clap_args() -> Vec<Arg<'static>> {
+    vec![Arg::new("proof")
+        .long("proof")
+        .takes_value(true)
+        .allow_invalid_utf8(true)
+        .help_heading("PROOF SHEET (.ufo INPUT ONLY)")
+        .help("Also write an HTML proof sheet of the run, showing every glyph before and after stroking.")]
+}
+
+/// Fails with a usage error if a proof sheet was requested of a single glyph.
+pub fn reject_for_glif(matches: &ArgMatches) -> Result<(), StrokeError> {
+    if matches.is_present("proof") {
+        Err(StrokeError::Usage(String::from("--proof can only be used when stroking a .ufo")))
+    } else {
+        Ok(())
+    }
+}
+
+const STYLE: &str = "
+body { font-family: sans-serif; margin: 2em; }
+pre { background: #f4f4f4; padding: 1em; overflow-x: auto; }
+.glyphs { display: flex; flex-wrap: wrap; gap: 1em; }
+.glyph { border: 1px solid #ccc; padding: 0.5em; }
+.glyph.failed { border-color: #e0301e; }
+.glyph h3 { margin: 0 0 0.5em; font-size: 1em; }
+.glyph svg { height: 150px; width: auto; border: 1px solid #eee; margin-right: 0.5em; }
+.glyph td, .glyph th { padding: 0 0.5em; text-align: right; }
+.warning { color: #b35c00; }
+.error { color: #e0301e; }
+";
+
+// (contours, points, open contours)
+fn counts(glif: &Glif<()>) -> (usize, usize, usize) {
+    let outline = match glif.outline.as_ref() {
+        Some(outline) => outline,
+        None => return (0, 0, 0),
+    };
+    let points = outline.iter().map(|c| c.len()).sum();
+    let open = outline.iter().filter(|c| c.first().map(|p| p.ptype == PointType::Move).unwrap_or(false)).count();
+    (outline.len(), points, open)
+}
+
+// Things about a stroked glyph worth a second look.
+fn warnings(report: &GlyphReport) -> Vec<String> {
+    let mut warnings = vec![];
+    if let Some(output) = report.output.as_ref() {
+        let (contours, _, open) = counts(output);
+        if contours == 0 {
+            warnings.push(String::from("The result has no contours."));
+        }
+        if open > 0 {
+            warnings.push(format!("The result has {} open contour(s).", open));
+        }
+        if output.width != report.input.as_ref().and_then(|i| i.width) {
+            warnings.push(String::from("The advance width changed."));
+        }
+    }
+    warnings
+}
+
+fn glyph_entry(report: &GlyphReport, html: &mut String, batch: &BatchReport) {
+    let failed = matches!(report.status, GlyphStatus::Failed(_));
+    html.push_str(&format!("<div class=\"glyph{}\">\n<h3>{}</h3>\n<div>", if failed { " failed" } else { "" }, escape(&report.glyph.name)));
+    for glif in [&report.input, &report.output].into_iter().flatten() {
+        html.push_str(&svg::glif_to_svg(glif, None, batch.metrics));
+    }
+    html.push_str("</div>\n<table>\n<tr><th></th><th>contours</th><th>points</th></tr>\n");
+    for (label, glif) in [("before", &report.input), ("after", &report.output)] {
+        if let Some(glif) = glif {
+            let (contours, points, _) = counts(glif);
+            html.push_str(&format!("<tr><td>{}</td><td>{}</td><td>{}</td></tr>\n", label, contours, points));
+        }
+    }
+    html.push_str("</table>\n");
+    if let GlyphStatus::Failed(e) = &report.status {
+        html.push_str(&format!("<p class=\"error\">{}</p>\n", escape(&e.to_string())));
+    }
+    for warning in warnings(report) {
+        html.push_str(&format!("<p class=\"warning\">{}</p>\n", escape(&warning)));
+    }
+    html.push_str("</div>\n");
+}
+
+/// Renders the proof sheet of a batch run as a standalone HTML page. `report` must have been made with
+/// its glifs kept. `settings` describes the stroking settings used.
+pub fn proof_sheet_html(report: &BatchReport, title: &str, settings: &str) -> String {
+    let count = |f: fn(&GlyphStatus) -> bool| report.glyphs.iter().filter(|g| f(&g.status)).count();
+    let stroked = count(|s| matches!(s, GlyphStatus::Stroked));
+    let empty = count(|s| matches!(s, GlyphStatus::Empty));
+    let unselected = count(|s| matches!(s, GlyphStatus::Unselected));
+
+    let mut html = format!(
+        "<!DOCTYPE html>\n<html>\n<head>\n<meta charset=\"utf-8\">\n<title>MFEKstroke proof: {0}</title>\n<style>{1}</style>\n</head>\n<body>\n<h1>{0}</h1>\n",
+        escape(title),
+        STYLE
+    );
+    html.push_str(&format!(
+        "<p>Of {} glyphs, {} were stroked and {} failed; {} had no contours and {} weren't selected.</p>\n",
+        report.glyphs.len(),
+        stroked,
+        report.failed(),
+        empty,
+        unselected
+    ));
+    html.push_str(&format!("<h2>Settings</h2>\n<pre>{}</pre>\n", escape(settings)));
+
+    html.push_str("<h2>Failed glyphs</h2>\n");
+    let failures: Vec<String> = report
+        .glyphs
+        .iter()
+        .filter_map(|g| match &g.status {
+            GlyphStatus::Failed(e) => Some(format!("<li><b>{}</b>: {}</li>\n", escape(&g.glyph.name), escape(&e.to_string()))),
+            _ => None,
+        })
+        .collect();
+    if failures.is_empty() {
+        html.push_str("<p>None.</p>\n");
+    } else {
+        html.push_str(&format!("<ul>\n{}</ul>\n", failures.concat()));
+    }
+
+    html.push_str("<h2>Glyphs</h2>\n<div class=\"glyphs\">\n");
+    for glyph in report.glyphs.iter().filter(|g| matches!(g.status, GlyphStatus::Stroked | GlyphStatus::Failed(_))) {
+        glyph_entry(glyph, &mut html, report);
+    }
+    html.push_str("</div>\n");
+
+    let empty_names: Vec<String> = report
+        .glyphs
+        .iter()
+        .filter(|g| matches!(g.status, GlyphStatus::Empty))
+        .map(|g| escape(&g.glyph.name))
+        .collect();
+    if !empty_names.is_empty() {
+        html.push_str(&format!("<h2>Without contours (copied unchanged)</h2>\n<p>{}</p>\n", empty_names.join(", ")));
+    }
+
+    html.push_str("</body>\n</html>\n");
+    html
+}
+
+/// Writes the proof sheet of a batch run to `path`, see [`proof_sheet_html`].
+pub fn write_proof_sheet(path: &FsPath, report: &BatchReport, title: &str, settings: &str) -> Result<(), StrokeError> {
+    glif_io::write_file(path, proof_sheet_html(report, title, settings))
+}
@@ -0,0 +1,90 @@
+// Overlap removal, for the output of any mode: the closed contours of an outline are united into the
+// area they fill by the nonzero rule, as a font renderer fills them, leaving contours which neither
+// cross nor overlap. Those are then turned to run the way PostScript and the UFO spec expect, outer
+// contours counterclockwise and counters clockwise, whichever way the mode left them.
+
+use clap::{Arg, ArgMatches};
+use glifparser::{Glif, Outline};
+
+use crate::bezier;
+use crate::boolean::{self, BooleanOptions};
+use crate::StrokeError;
+
+// How closely contours are flattened to judge their winding.
+const TOLERANCE: f64 = 0.5;
+
+pub fn clap_args() -> Vec<Arg<'static>> {
+    vec![Arg::new("remove-overlap")
+        .long("remove-overlap")
+        .takes_value(false)
+        .help("Unite the output's overlapping contours, leaving outer contours counterclockwise and counters clockwise.")]
+}
+
+/// Unites the closed contours of `outline`, filled by the nonzero rule, into contours which don't
+/// overlap, outer contours running counterclockwise and counters clockwise. Open contours bound no area,
+/// and are kept as they are, after the closed ones.
+pub fn remove_overlap(outline: &Outline<()>, options: &BooleanOptions) -> Result<Outline<()>, StrokeError> {
+    let open = outline.iter().filter(|contour| contour.len() < 2 || bezier::is_open(contour)).cloned();
+    let mut united = boolean::simplify(outline, options)?;
+
+    // The boolean engine's contours already run this way, but not those of its fallbacks.
+    let fills = bezier::fills_inside(&united, TOLERANCE);
+    for (contour, fills_inside) in united.iter_mut().zip(fills) {
+        let counterclockwise = bezier::signed_area(&bezier::flatten(contour, TOLERANCE)) > 0.;
+        if counterclockwise != fills_inside {
+            *contour = bezier::reversed(contour);
+        }
+    }
+
+    united.extend(open);
+    Ok(united)
+}
+
+/// Removes the overlaps of `glif`'s outline (see [`remove_overlap`]) if --remove-overlap was given.
+pub(crate) fn remove_overlap_if_requested(matches: &ArgMatches, glif: Glif<()>) -> Result<Glif<()>, StrokeError> {
+    if !matches.is_present("remove-overlap") {
+        return Ok(glif);
+    }
+    let outline = match glif.outline.as_ref() {
+        Some(outline) => remove_overlap(outline, &BooleanOptions::from_matches(matches))?,
+        None => return Ok(glif),
+    };
+    Ok(Glif {
+        outline: Some(outline),
+        ..glif
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::bezier::{Pt, Segment};
+    use glifparser::Contour;
+
+    fn square((x, y): Pt, size: f64, clockwise: bool) -> Contour<()> {
+        let mut corners = [(x, y), (x + size, y), (x + size, y + size), (x, y + size)];
+        if clockwise {
+            corners.reverse();
+        }
+        let sides: Vec<Segment> = (0..4).map(|i| Segment::line(corners[i], corners[(i + 1) % 4])).collect();
+        bezier::closed_contour(&sides)
+    }
+
+    #[test]
+    fn overlapping_squares_become_one_counterclockwise_contour() {
+        // Both clockwise, so they add up rather than cancel out, and the result is turned around.
+        let outline = vec![square((0., 0.), 100., true), square((50., 50.), 100., true)];
+        let united = remove_overlap(&outline, &BooleanOptions::default()).unwrap();
+        assert_eq!(united.len(), 1);
+        let polygon = bezier::flatten(&united[0], TOLERANCE);
+        // The union of two 100 × 100 squares overlapping by 50 × 50.
+        assert!((bezier::signed_area(&polygon) - 17_500.).abs() < 1e-6);
+        assert_eq!(polygon.len(), 8);
+    }
+
+    #[test]
+    fn failing_is_an_error() {
+        let outline = vec![square((0., 0.), 100., false), square((f64::NAN, 50.), 100., false)];
+        assert!(matches!(remove_overlap(&outline, &BooleanOptions::default()), Err(StrokeError::Geometry { .. })));
+    }
+}
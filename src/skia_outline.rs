@@ -0,0 +1,98 @@
+// Conversions between glif outlines and Skia paths, for rendering previews and for Skia's path ops.
+
+use glifparser::{Contour, Handle, Outline, Point, PointType};
+use MFEKmath::skia_safe::path::{Iter, Verb};
+use MFEKmath::skia_safe::{self, Path, PathOp as SkPathOp};
+
+use crate::boolean::BooleanOp;
+use crate::svg::{self, PathOp};
+
+/// Converts drawing commands to a Skia path.
+pub(crate) fn ops_to_skia_path(ops: &[PathOp]) -> Path {
+    let pt = |(x, y): (f64, f64)| (x as f32, y as f32);
+    let mut path = Path::new();
+    for op in ops {
+        match *op {
+            PathOp::Move(p) => path.move_to(pt(p)),
+            PathOp::Line(p) => path.line_to(pt(p)),
+            PathOp::Quad(c, p) => path.quad_to(pt(c), pt(p)),
+            PathOp::Cubic(c1, c2, p) => path.cubic_to(pt(c1), pt(c2), pt(p)),
+            PathOp::Close => path.close(),
+        };
+    }
+    path
+}
+
+pub(crate) fn outline_to_skia_path(outline: &Outline<()>) -> Path {
+    ops_to_skia_path(&svg::outline_ops(outline))
+}
+
+// Ends the contour being built: a closed contour's last point is dropped if it's back on its first, as
+// glifs don't repeat it.
+fn finish_contour(contour: &mut Contour<()>, closed: bool, outline: &mut Outline<()>) {
+    if contour.is_empty() {
+        return;
+    }
+    let mut contour = std::mem::take(contour);
+    if closed {
+        let (first, last) = (&contour[0], &contour[contour.len() - 1]);
+        if contour.len() > 1 && first.x == last.x && first.y == last.y {
+            let last = contour.pop().unwrap();
+            contour[0].b = last.b;
+            contour[0].ptype = last.ptype;
+        } else {
+            contour[0].ptype = PointType::Line;
+        }
+    }
+    outline.push(contour);
+}
+
+/// Converts a Skia path to an outline. Quadratics (and conics, whose weight is dropped) become cubics.
+pub(crate) fn skia_path_to_outline(path: &Path) -> Outline<()> {
+    let at = |p: skia_safe::Point| (p.x, p.y);
+    let mut outline = Outline::new();
+    let mut contour: Contour<()> = vec![];
+
+    let cubic_to = |contour: &mut Contour<()>, c1: (f32, f32), c2: (f32, f32), p: (f32, f32)| {
+        if let Some(last) = contour.last_mut() {
+            last.a = Handle::At(c1.0, c1.1);
+        }
+        let mut point = Point::from_x_y_type(p, PointType::Curve);
+        point.b = Handle::At(c2.0, c2.1);
+        contour.push(point);
+    };
+
+    for (verb, points) in Iter::new(path, false) {
+        match verb {
+            Verb::Move => {
+                finish_contour(&mut contour, false, &mut outline);
+                contour.push(Point::from_x_y_type(at(points[0]), PointType::Move));
+            }
+            Verb::Line => contour.push(Point::from_x_y_type(at(points[1]), PointType::Line)),
+            Verb::Quad | Verb::Conic => {
+                // Degree elevation: each cubic handle is two thirds of the way to the quadratic's.
+                let (p0, c, p) = (at(points[0]), at(points[1]), at(points[2]));
+                let third = |a: (f32, f32), b: (f32, f32)| (a.0 + (b.0 - a.0) * 2. / 3., a.1 + (b.1 - a.1) * 2. / 3.);
+                cubic_to(&mut contour, third(p0, c), third(p, c), p);
+            }
+            Verb::Cubic => cubic_to(&mut contour, at(points[1]), at(points[2]), at(points[3])),
+            Verb::Close => finish_contour(&mut contour, true, &mut outline),
+            _ => {}
+        }
+    }
+    finish_contour(&mut contour, false, &mut outline);
+    outline
+}
+
+/// The result of `op` on the areas of `a` and `b`, each filled by the nonzero rule, or `None` if Skia
+/// fails.
+pub(crate) fn boolean(a: &Outline<()>, b: &Outline<()>, op: BooleanOp) -> Option<Outline<()>> {
+    let op = match op {
+        BooleanOp::Union => SkPathOp::Union,
+        BooleanOp::Difference => SkPathOp::Difference,
+        BooleanOp::Intersection => SkPathOp::Intersect,
+        BooleanOp::Xor => SkPathOp::XOR,
+    };
+    let result = outline_to_skia_path(a).op(&outline_to_skia_path(b), op)?;
+    Some(skia_path_to_outline(&result))
+}
@@ -0,0 +1,870 @@
+// SVG input and output.
+//
+// Input reads the outlines of an .svg (path data and basic shapes, under any transforms) into a glif,
+// so that paths, patterns and caps can be drawn in Inkscape and friends. Output draws a stroked glif
+// as a standalone .svg, for documentation, specimens and quick review.
+//
+// SVG's y axis points down, while a font's points up. By default coordinates are taken as they are;
+// --svg-flip-y flips them about the bottom of the viewBox (so its bottom edge lands on the baseline),
+// and --svg-upm scales the viewBox's height to that many font units.
+
+use std::f64::consts::PI;
+use std::fs;
+use std::path::Path as FsPath;
+
+use clap::{Arg, ArgMatches};
+use glifparser::outline::RefigurePointTypes as _;
+use glifparser::{Contour, Glif, Handle, Outline, Point, PointType};
+use xmltree::{Element, XMLNode};
+
+use crate::bezier::{Pt, Segment, KAPPA};
+use crate::ufo::FontMetrics;
+use crate::StrokeError;
+
+pub fn clap_args() -> Vec<Arg<'static>> {
+    vec![
+        Arg::new("svg-flip-y")
+            .long("svg-flip-y")
+            .help_heading("SVG INPUT")
+            .help("Flip .svg inputs vertically, so that the bottom of their viewBox lands on the baseline."),
+        Arg::new("svg-upm")
+            .long("svg-upm")
+            .takes_value(true)
+            .validator(super::arg_validator_positive_f64)
+            .help_heading("SVG INPUT")
+            .help("<f64> Scale .svg inputs so that the height of their viewBox is this many font units."),
+    ]
+}
+
+pub fn output_clap_args() -> Vec<Arg<'static>> {
+    vec![Arg::new("svg-skeleton")
+        .long("svg-skeleton")
+        .help_heading("SVG OUTPUT")
+        .help("When writing an .svg, draw the unstroked input path over the result.")]
+}
+
+/// How to map SVG user units to font units.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct SvgOptions {
+    /// Flip the y axis about the bottom of the viewBox.
+    pub flip_y: bool,
+    /// Scale the viewBox's height to this many units.
+    pub upm: Option<f64>,
+}
+
+impl SvgOptions {
+    pub fn from_matches(matches: &ArgMatches) -> Self {
+        SvgOptions {
+            flip_y: matches.is_present("svg-flip-y"),
+            upm: matches.value_of("svg-upm").map(|u| u.parse().unwrap()), // validated by arg_validator_positive_f64
+        }
+    }
+}
+
+// A 2D affine transform, [a b c d e f] as in SVG's matrix().
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct Affine([f64; 6]);
+
+impl Affine {
+    const IDENTITY: Affine = Affine([1., 0., 0., 1., 0., 0.]);
+
+    fn apply(&self, (x, y): Pt) -> Pt {
+        let [a, b, c, d, e, f] = self.0;
+        (a * x + c * y + e, b * x + d * y + f)
+    }
+
+    // self ∘ other: applies `other` first.
+    fn then_inner(&self, other: &Affine) -> Affine {
+        let [sa, sb, sc, sd, se, sf] = self.0;
+        let [oa, ob, oc, od, oe, of] = other.0;
+        Affine([
+            sa * oa + sc * ob,
+            sb * oa + sd * ob,
+            sa * oc + sc * od,
+            sb * oc + sd * od,
+            sa * oe + sc * of + se,
+            sb * oe + sd * of + sf,
+        ])
+    }
+}
+
+#[derive(Debug, Clone, Default)]
+struct SubPath {
+    segments: Vec<Segment>,
+    closed: bool,
+}
+
+// Tokenizer for path data and points lists. Numbers may run together ("1.5.5", "-1-2"), and arc flags
+// may be packed without separators ("a1 1 0 011 1").
+struct Lexer<'a> {
+    s: &'a [u8],
+    pos: usize,
+}
+
+impl<'a> Lexer<'a> {
+    fn new(s: &'a str) -> Self {
+        Lexer { s: s.as_bytes(), pos: 0 }
+    }
+
+    fn skip_separators(&mut self) {
+        while self.pos < self.s.len() && (self.s[self.pos].is_ascii_whitespace() || self.s[self.pos] == b',') {
+            self.pos += 1;
+        }
+    }
+
+    fn at_end(&mut self) -> bool {
+        self.skip_separators();
+        self.pos >= self.s.len()
+    }
+
+    fn command(&mut self) -> Option<u8> {
+        self.skip_separators();
+        match self.s.get(self.pos) {
+            Some(c) if c.is_ascii_alphabetic() && *c != b'e' && *c != b'E' => {
+                self.pos += 1;
+                Some(*c)
+            }
+            _ => None,
+        }
+    }
+
+    fn number(&mut self) -> Result<f64, String> {
+        self.skip_separators();
+        let start = self.pos;
+        let digits = |l: &mut Self| {
+            let from = l.pos;
+            while l.pos < l.s.len() && l.s[l.pos].is_ascii_digit() {
+                l.pos += 1;
+            }
+            l.pos > from
+        };
+
+        if matches!(self.s.get(self.pos), Some(b'+') | Some(b'-')) {
+            self.pos += 1;
+        }
+        let mut any = digits(self);
+        if self.s.get(self.pos) == Some(&b'.') {
+            self.pos += 1;
+            any |= digits(self);
+        }
+        if any && matches!(self.s.get(self.pos), Some(b'e') | Some(b'E')) {
+            let mantissa_end = self.pos;
+            self.pos += 1;
+            if matches!(self.s.get(self.pos), Some(b'+') | Some(b'-')) {
+                self.pos += 1;
+            }
+            if !digits(self) {
+                self.pos = mantissa_end;
+            }
+        }
+
+        let token = std::str::from_utf8(&self.s[start..self.pos]).unwrap();
+        match token.parse::<f64>() {
+            Ok(n) if any => Ok(n),
+            _ => Err(format!("expected a number at byte {}", start)),
+        }
+    }
+
+    fn flag(&mut self) -> Result<bool, String> {
+        self.skip_separators();
+        let ret = match self.s.get(self.pos) {
+            Some(b'0') => false,
+            Some(b'1') => true,
+            _ => return Err(format!("expected an arc flag at byte {}", self.pos)),
+        };
+        self.pos += 1;
+        Ok(ret)
+    }
+
+    fn point(&mut self) -> Result<Pt, String> {
+        Ok((self.number()?, self.number()?))
+    }
+}
+
+// Converts an SVG elliptical arc to cubics, after the SVG spec's implementation notes (F.6.5–F.6.6).
+fn arc_to_segments(p0: Pt, rx: f64, ry: f64, x_axis_rotation: f64, large_arc: bool, sweep: bool, p1: Pt) -> Vec<Segment> {
+    if p0 == p1 {
+        return vec![];
+    }
+    let (mut rx, mut ry) = (rx.abs(), ry.abs());
+    if rx == 0. || ry == 0. {
+        return vec![Segment::line(p0, p1)];
+    }
+
+    let phi = x_axis_rotation.to_radians();
+    let (sin_phi, cos_phi) = phi.sin_cos();
+    let (dx2, dy2) = ((p0.0 - p1.0) / 2., (p0.1 - p1.1) / 2.);
+    let x1p = cos_phi * dx2 + sin_phi * dy2;
+    let y1p = -sin_phi * dx2 + cos_phi * dy2;
+
+    let lambda = (x1p * x1p) / (rx * rx) + (y1p * y1p) / (ry * ry);
+    if lambda > 1. {
+        rx *= lambda.sqrt();
+        ry *= lambda.sqrt();
+    }
+
+    let num = rx * rx * ry * ry - rx * rx * y1p * y1p - ry * ry * x1p * x1p;
+    let den = rx * rx * y1p * y1p + ry * ry * x1p * x1p;
+    let coef = (num / den).max(0.).sqrt() * if large_arc == sweep { -1. } else { 1. };
+    let cxp = coef * rx * y1p / ry;
+    let cyp = -coef * ry * x1p / rx;
+    let cx = cos_phi * cxp - sin_phi * cyp + (p0.0 + p1.0) / 2.;
+    let cy = sin_phi * cxp + cos_phi * cyp + (p0.1 + p1.1) / 2.;
+
+    let angle = |u: Pt, v: Pt| (u.0 * v.1 - u.1 * v.0).atan2(u.0 * v.0 + u.1 * v.1);
+    let u = ((x1p - cxp) / rx, (y1p - cyp) / ry);
+    let v = ((-x1p - cxp) / rx, (-y1p - cyp) / ry);
+    let theta1 = angle((1., 0.), u);
+    let mut dtheta = angle(u, v);
+    if !sweep && dtheta > 0. {
+        dtheta -= 2. * PI;
+    } else if sweep && dtheta < 0. {
+        dtheta += 2. * PI;
+    }
+
+    let n = (dtheta.abs() / (PI / 2.)).ceil().max(1.) as usize;
+    let delta = dtheta / n as f64;
+    let k = 4. / 3. * (delta / 4.).tan();
+    let at = |t: f64| (cx + rx * t.cos() * cos_phi - ry * t.sin() * sin_phi, cy + rx * t.cos() * sin_phi + ry * t.sin() * cos_phi);
+    let derivative = |t: f64| (-rx * t.sin() * cos_phi - ry * t.cos() * sin_phi, -rx * t.sin() * sin_phi + ry * t.cos() * cos_phi);
+
+    (0..n)
+        .map(|i| {
+            let (t1, t2) = (theta1 + i as f64 * delta, theta1 + (i + 1) as f64 * delta);
+            let (from, to) = (if i == 0 { p0 } else { at(t1) }, if i == n - 1 { p1 } else { at(t2) });
+            let (d1, d2) = (derivative(t1), derivative(t2));
+            Segment::cubic(from, (from.0 + k * d1.0, from.1 + k * d1.1), (to.0 - k * d2.0, to.1 - k * d2.1), to)
+        })
+        .collect()
+}
+
+// Ends the subpath being built, if it has anything in it.
+fn finish_subpath(current: &mut SubPath, subpaths: &mut Vec<SubPath>) {
+    let subpath = std::mem::take(current);
+    if !subpath.segments.is_empty() {
+        subpaths.push(subpath);
+    }
+}
+
+fn parse_path_data(d: &str) -> Result<Vec<SubPath>, String> {
+    let mut lexer = Lexer::new(d);
+    let mut subpaths: Vec<SubPath> = vec![];
+    let mut current = SubPath::default();
+    let (mut cur, mut start): (Pt, Pt) = ((0., 0.), (0., 0.));
+    // Reflection points for S/s and T/t.
+    let (mut last_c2, mut last_qc): (Option<Pt>, Option<Pt>) = (None, None);
+    let mut command: Option<u8> = None;
+
+    while !lexer.at_end() {
+        let cmd = match lexer.command() {
+            Some(c) => c,
+            // Repeated commands may omit the letter; a repeated moveto is a lineto.
+            None => match command {
+                Some(b'M') => b'L',
+                Some(b'm') => b'l',
+                Some(c) if c != b'Z' && c != b'z' => c,
+                _ => return Err(format!("expected a command at byte {}", lexer.pos)),
+            },
+        };
+        command = Some(cmd);
+        let relative = cmd.is_ascii_lowercase();
+        let rel = |p: Pt| if relative { (cur.0 + p.0, cur.1 + p.1) } else { p };
+
+        let (mut c2, mut qc) = (None, None);
+        match cmd.to_ascii_uppercase() {
+            b'M' => {
+                finish_subpath(&mut current, &mut subpaths);
+                cur = rel(lexer.point()?);
+                start = cur;
+            }
+            b'Z' => {
+                if cur != start {
+                    current.segments.push(Segment::line(cur, start));
+                }
+                current.closed = true;
+                finish_subpath(&mut current, &mut subpaths);
+                cur = start;
+            }
+            b'L' => {
+                let to = rel(lexer.point()?);
+                current.segments.push(Segment::line(cur, to));
+                cur = to;
+            }
+            b'H' => {
+                let x = lexer.number()?;
+                let to = (if relative { cur.0 + x } else { x }, cur.1);
+                current.segments.push(Segment::line(cur, to));
+                cur = to;
+            }
+            b'V' => {
+                let y = lexer.number()?;
+                let to = (cur.0, if relative { cur.1 + y } else { y });
+                current.segments.push(Segment::line(cur, to));
+                cur = to;
+            }
+            b'C' | b'S' => {
+                let c1 = if cmd.eq_ignore_ascii_case(&b'C') {
+                    rel(lexer.point()?)
+                } else {
+                    last_c2.map(|c| (2. * cur.0 - c.0, 2. * cur.1 - c.1)).unwrap_or(cur)
+                };
+                let (c2_, to) = (rel(lexer.point()?), rel(lexer.point()?));
+                current.segments.push(Segment::cubic(cur, c1, c2_, to));
+                c2 = Some(c2_);
+                cur = to;
+            }
+            b'Q' | b'T' => {
+                let q = if cmd.eq_ignore_ascii_case(&b'Q') {
+                    rel(lexer.point()?)
+                } else {
+                    last_qc.map(|c| (2. * cur.0 - c.0, 2. * cur.1 - c.1)).unwrap_or(cur)
+                };
+                let to = rel(lexer.point()?);
+                // Degree elevation: a quadratic is exactly a cubic with its controls 2/3 of the way to q.
+                let c1 = (cur.0 + 2. / 3. * (q.0 - cur.0), cur.1 + 2. / 3. * (q.1 - cur.1));
+                let c2_ = (to.0 + 2. / 3. * (q.0 - to.0), to.1 + 2. / 3. * (q.1 - to.1));
+                current.segments.push(Segment::cubic(cur, c1, c2_, to));
+                qc = Some(q);
+                cur = to;
+            }
+            b'A' => {
+                let (rx, ry, rotation) = (lexer.number()?, lexer.number()?, lexer.number()?);
+                let (large_arc, sweep) = (lexer.flag()?, lexer.flag()?);
+                let to = rel(lexer.point()?);
+                current.segments.extend(arc_to_segments(cur, rx, ry, rotation, large_arc, sweep, to));
+                cur = to;
+            }
+            _ => return Err(format!("unknown path command {:?}", cmd as char)),
+        }
+        last_c2 = c2;
+        last_qc = qc;
+    }
+    finish_subpath(&mut current, &mut subpaths);
+
+    Ok(subpaths)
+}
+
+fn parse_transform(s: &str) -> Result<Affine, String> {
+    let mut ret = Affine::IDENTITY;
+    let mut rest = s.trim();
+
+    while !rest.is_empty() {
+        let open = rest.find('(').ok_or_else(|| format!("invalid transform {:?}", s))?;
+        let close = rest.find(')').ok_or_else(|| format!("invalid transform {:?}", s))?;
+        let name = rest[..open].trim_matches(|c: char| c.is_whitespace() || c == ',');
+        let mut lexer = Lexer::new(&rest[open + 1..close]);
+        let mut args = vec![];
+        while !lexer.at_end() {
+            args.push(lexer.number()?);
+        }
+
+        let t = match (name, args.as_slice()) {
+            ("matrix", &[a, b, c, d, e, f]) => Affine([a, b, c, d, e, f]),
+            ("translate", &[tx]) => Affine([1., 0., 0., 1., tx, 0.]),
+            ("translate", &[tx, ty]) => Affine([1., 0., 0., 1., tx, ty]),
+            ("scale", &[sx]) => Affine([sx, 0., 0., sx, 0., 0.]),
+            ("scale", &[sx, sy]) => Affine([sx, 0., 0., sy, 0., 0.]),
+            ("rotate", &[a]) | ("rotate", &[a, _, _]) => {
+                let (sin, cos) = a.to_radians().sin_cos();
+                let rotate = Affine([cos, sin, -sin, cos, 0., 0.]);
+                match args.as_slice() {
+                    &[_, cx, cy] => Affine([1., 0., 0., 1., cx, cy])
+                        .then_inner(&rotate)
+                        .then_inner(&Affine([1., 0., 0., 1., -cx, -cy])),
+                    _ => rotate,
+                }
+            }
+            ("skewX", &[a]) => Affine([1., 0., a.to_radians().tan(), 1., 0., 0.]),
+            ("skewY", &[a]) => Affine([1., a.to_radians().tan(), 0., 1., 0., 0.]),
+            _ => return Err(format!("invalid transform {:?}", &rest[..=close])),
+        };
+        ret = ret.then_inner(&t);
+        rest = rest[close + 1..].trim_start_matches(|c: char| c.is_whitespace() || c == ',');
+    }
+
+    Ok(ret)
+}
+
+// Parses a length attribute, ignoring any unit (px, pt, mm… are all taken as user units).
+fn length(el: &Element, attr: &str) -> Option<f64> {
+    let value = el.attributes.get(attr)?;
+    let mut lexer = Lexer::new(value);
+    lexer.number().ok()
+}
+
+// The elliptical arcs of an ellipse (or, with a corner radius, the rounded corners of a rect) as four
+// cubic quarter arcs of the standard 0.5523 kappa, clockwise in SVG space from the rightmost point.
+fn ellipse_segments(cx: f64, cy: f64, rx: f64, ry: f64) -> Vec<Segment> {
+    let (kx, ky) = (rx * KAPPA, ry * KAPPA);
+    vec![
+        Segment::cubic((cx + rx, cy), (cx + rx, cy + ky), (cx + kx, cy + ry), (cx, cy + ry)),
+        Segment::cubic((cx, cy + ry), (cx - kx, cy + ry), (cx - rx, cy + ky), (cx - rx, cy)),
+        Segment::cubic((cx - rx, cy), (cx - rx, cy - ky), (cx - kx, cy - ry), (cx, cy - ry)),
+        Segment::cubic((cx, cy - ry), (cx + kx, cy - ry), (cx + rx, cy - ky), (cx + rx, cy)),
+    ]
+}
+
+fn rect_segments(x: f64, y: f64, w: f64, h: f64, rx: f64, ry: f64) -> Vec<Segment> {
+    if rx <= 0. || ry <= 0. {
+        let corners = [(x, y), (x + w, y), (x + w, y + h), (x, y + h)];
+        return (0..4).map(|i| Segment::line(corners[i], corners[(i + 1) % 4])).collect();
+    }
+
+    let (kx, ky) = (rx * (1. - KAPPA), ry * (1. - KAPPA));
+    let (r, b) = (x + w, y + h);
+    vec![
+        Segment::line((x + rx, y), (r - rx, y)),
+        Segment::cubic((r - rx, y), (r - kx, y), (r, y + ky), (r, y + ry)),
+        Segment::line((r, y + ry), (r, b - ry)),
+        Segment::cubic((r, b - ry), (r, b - ky), (r - kx, b), (r - rx, b)),
+        Segment::line((r - rx, b), (x + rx, b)),
+        Segment::cubic((x + rx, b), (x + kx, b), (x, b - ky), (x, b - ry)),
+        Segment::line((x, b - ry), (x, y + ry)),
+        Segment::cubic((x, y + ry), (x, y + ky), (x + kx, y), (x + rx, y)),
+    ]
+    .into_iter()
+    .filter(|s| s.curve[0] != s.curve[3])
+    .collect()
+}
+
+fn points_segments(points: &str, close: bool) -> Result<SubPath, String> {
+    let mut lexer = Lexer::new(points);
+    let mut pts = vec![];
+    while !lexer.at_end() {
+        pts.push(lexer.point()?);
+    }
+    let mut segments: Vec<Segment> = pts.windows(2).map(|w| Segment::line(w[0], w[1])).collect();
+    if close && pts.len() > 2 && pts.first() != pts.last() {
+        segments.push(Segment::line(*pts.last().unwrap(), pts[0]));
+    }
+    Ok(SubPath { segments, closed: close })
+}
+
+// The subpaths drawn by a single shape element, in its own user space.
+fn shape_subpaths(el: &Element) -> Result<Vec<SubPath>, String> {
+    let len = |attr| length(el, attr).unwrap_or(0.);
+    let closed = |segments| vec![SubPath { segments, closed: true }];
+
+    Ok(match el.name.as_str() {
+        "path" => parse_path_data(el.attributes.get("d").map(String::as_str).unwrap_or(""))?,
+        "rect" => {
+            let (w, h) = (len("width"), len("height"));
+            if w <= 0. || h <= 0. {
+                return Ok(vec![]);
+            }
+            // A missing rx or ry takes the other's value; both are clamped to half the rect.
+            let (rx, ry) = match (length(el, "rx"), length(el, "ry")) {
+                (Some(rx), Some(ry)) => (rx, ry),
+                (Some(r), None) | (None, Some(r)) => (r, r),
+                (None, None) => (0., 0.),
+            };
+            closed(rect_segments(len("x"), len("y"), w, h, rx.min(w / 2.), ry.min(h / 2.)))
+        }
+        "circle" if len("r") > 0. => closed(ellipse_segments(len("cx"), len("cy"), len("r"), len("r"))),
+        "ellipse" if len("rx") > 0. && len("ry") > 0. => closed(ellipse_segments(len("cx"), len("cy"), len("rx"), len("ry"))),
+        "line" => vec![SubPath {
+            segments: vec![Segment::line((len("x1"), len("y1")), (len("x2"), len("y2")))],
+            closed: false,
+        }],
+        "polyline" => vec![points_segments(el.attributes.get("points").map(String::as_str).unwrap_or(""), false)?],
+        "polygon" => vec![points_segments(el.attributes.get("points").map(String::as_str).unwrap_or(""), true)?],
+        _ => vec![],
+    })
+}
+
+// Elements whose contents are never rendered directly.
+const NOT_RENDERED: &[&str] = &["defs", "clipPath", "mask", "marker", "pattern", "symbol", "style", "script", "title", "desc", "metadata"];
+
+fn collect_subpaths(el: &Element, parent: &Affine, out: &mut Vec<SubPath>) -> Result<(), String> {
+    if NOT_RENDERED.contains(&el.name.as_str()) || el.attributes.get("display").map(|d| d == "none").unwrap_or(false) {
+        return Ok(());
+    }
+
+    let transform = match el.attributes.get("transform") {
+        Some(t) => parent.then_inner(&parse_transform(t)?),
+        None => *parent,
+    };
+
+    for subpath in shape_subpaths(el)? {
+        out.push(SubPath {
+            segments: subpath.segments.iter().map(|s| s.map(|p| transform.apply(p))).collect(),
+            closed: subpath.closed,
+        });
+    }
+
+    // Either would draw something this reader can't place, which is better refused than left out.
+    if el.name == "use" {
+        return Err(String::from("<use> elements aren't supported"));
+    }
+
+    for child in el.children.iter() {
+        if let XMLNode::Element(child) = child {
+            if child.name == "svg" {
+                return Err(String::from("nested <svg> elements (and their viewBoxes) aren't supported"));
+            }
+            collect_subpaths(child, &transform, out)?;
+        }
+    }
+
+    Ok(())
+}
+
+fn to_contour(subpath: &SubPath, map: &impl Fn(Pt) -> Pt) -> Contour<()> {
+    let handle = |p: Pt| {
+        let (x, y) = map(p);
+        Handle::At(x as f32, y as f32)
+    };
+    let on_curve = |p: Pt, ptype: PointType| {
+        let (x, y) = map(p);
+        Point::from_x_y_type((x as f32, y as f32), ptype)
+    };
+
+    let segments = &subpath.segments;
+    let mut contour: Contour<()> = segments
+        .iter()
+        .enumerate()
+        .map(|(i, seg)| {
+            let ptype = if i == 0 && !subpath.closed { PointType::Move } else { PointType::Curve };
+            let mut point = on_curve(seg.curve[0], ptype);
+            if !seg.line {
+                point.a = handle(seg.curve[1]);
+            }
+            let prev = if i > 0 { segments.get(i - 1) } else if subpath.closed { segments.last() } else { None };
+            if let Some(prev) = prev.filter(|p| !p.line) {
+                point.b = handle(prev.curve[2]);
+            }
+            point
+        })
+        .collect();
+
+    if !subpath.closed {
+        let last = segments.last().unwrap();
+        let mut point = on_curve(last.curve[3], PointType::Curve);
+        if !last.line {
+            point.b = handle(last.curve[2]);
+        }
+        contour.push(point);
+    }
+
+    contour
+}
+
+/// Reads the outlines of the .svg at `path` into a glif named after the file, mapping coordinates to
+/// font units according to `options`. The advance width is the (mapped) width of the viewBox.
+pub fn read_svg<P: AsRef<FsPath>>(path: P, options: &SvgOptions) -> Result<Glif<()>, StrokeError> {
+    let path = path.as_ref();
+    let parse_err = |message: String| StrokeError::SvgParse { path: path.to_path_buf(), message };
+    let file = fs::File::open(path).map_err(|source| StrokeError::Read { path: path.to_path_buf(), source })?;
+    let root = Element::parse(file).map_err(|e| parse_err(e.to_string()))?;
+    if root.name != "svg" {
+        return Err(parse_err(format!("root element is <{}>, not <svg>", root.name)));
+    }
+
+    let mut subpaths = vec![];
+    collect_subpaths(&root, &Affine::IDENTITY, &mut subpaths).map_err(parse_err)?;
+    subpaths.retain(|s| !s.segments.is_empty());
+
+    // The viewBox, else the width and height, else (for flipping) the bounds of the drawing.
+    let viewbox: Option<[f64; 4]> = root.attributes.get("viewBox").and_then(|vb| {
+        let mut lexer = Lexer::new(vb);
+        Some([lexer.number().ok()?, lexer.number().ok()?, lexer.number().ok()?, lexer.number().ok()?])
+    });
+    let (min_x, min_y, width, height) = match (viewbox, length(&root, "width"), length(&root, "height")) {
+        (Some([x, y, w, h]), _, _) => (x, y, Some(w), Some(h)),
+        (None, w, h) => (0., 0., w, h),
+    };
+    let height = height.unwrap_or_else(|| {
+        subpaths
+            .iter()
+            .flat_map(|s| s.segments.iter().flat_map(|seg| seg.curve.iter().map(|p| p.1)))
+            .fold(min_y, f64::max)
+            - min_y
+    });
+
+    let scale = match options.upm {
+        Some(upm) if height > 0. => upm / height,
+        _ => 1.,
+    };
+    let map = |(x, y): Pt| {
+        let y = if options.flip_y { min_y + height - y } else { y - min_y };
+        ((x - min_x) * scale, y * scale)
+    };
+
+    let mut outline: Outline<()> = subpaths.iter().map(|s| to_contour(s, &map)).collect();
+    outline.refigure_point_types();
+
+    let mut glif: Glif<()> = Glif::new();
+    glif.name = path.file_stem().map(|s| s.to_string_lossy().into_owned()).unwrap_or_default();
+    glif.width = width.map(|w| (w * scale).round().max(0.) as u64);
+    glif.outline = Some(outline);
+    glif.filename = Some(path.to_path_buf());
+    Ok(glif)
+}
+
+/// Whether `path` should be read as an SVG rather than a glif.
+pub fn is_svg<P: AsRef<FsPath>>(path: P) -> bool {
+    path.as_ref().extension().map(|e| e.eq_ignore_ascii_case("svg")).unwrap_or(false)
+}
+
+// One drawing command of a glif outline, in font units.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub(crate) enum PathOp {
+    Move(Pt),
+    Line(Pt),
+    Quad(Pt, Pt),
+    Cubic(Pt, Pt, Pt),
+    Close,
+}
+
+/// Converts an outline to drawing commands. Open contours are those starting with a move point; a
+/// closed contour's closing segment is only drawn explicitly if it's a curve.
+pub(crate) fn outline_ops(outline: &Outline<()>) -> Vec<PathOp> {
+    let at = |p: &Point<()>| (p.x as f64, p.y as f64);
+    let handle = |h: Handle, or: Pt| match h {
+        Handle::At(x, y) => (x as f64, y as f64),
+        Handle::Colocated => or,
+    };
+
+    let mut ops = vec![];
+    for contour in outline.iter().filter(|c| !c.is_empty()) {
+        let open = contour[0].ptype == PointType::Move;
+        ops.push(PathOp::Move(at(&contour[0])));
+        let segments = if open { contour.len() - 1 } else { contour.len() };
+        for i in 1..=segments {
+            let (prev, cur) = (&contour[i - 1], &contour[i % contour.len()]);
+            let op = match (prev.a, cur.b) {
+                (Handle::Colocated, Handle::Colocated) => PathOp::Line(at(cur)),
+                (a, b) if cur.ptype == PointType::QCurve => PathOp::Quad(handle(a, handle(b, at(cur))), at(cur)),
+                (a, b) => PathOp::Cubic(handle(a, at(prev)), handle(b, at(cur)), at(cur)),
+            };
+            if !(i == contour.len() && matches!(op, PathOp::Line(_))) {
+                ops.push(op);
+            }
+        }
+        if !open {
+            ops.push(PathOp::Close);
+        }
+    }
+    ops
+}
+
+// The box around every point (on- or off-curve) of `ops`, as (min x, min y, max x, max y).
+pub(crate) fn ops_bounds(ops: &[PathOp]) -> Option<(f64, f64, f64, f64)> {
+    ops.iter()
+        .flat_map(|op| match *op {
+            PathOp::Move(p) | PathOp::Line(p) => vec![p],
+            PathOp::Quad(c, p) => vec![c, p],
+            PathOp::Cubic(c1, c2, p) => vec![c1, c2, p],
+            PathOp::Close => vec![],
+        })
+        .fold(None, |bounds, (x, y)| match bounds {
+            None => Some((x, y, x, y)),
+            Some((x0, y0, x1, y1)) => Some((x0.min(x), y0.min(y), x1.max(x), y1.max(y))),
+        })
+}
+
+// Numbers for SVG attributes: at most three decimals, and no trailing zeros.
+fn num(n: f64) -> String {
+    format!("{}", (n * 1000.).round() / 1000.)
+}
+
+pub(crate) fn escape(s: &str) -> String {
+    s.replace('&', "&amp;").replace('<', "&lt;").replace('>', "&gt;").replace('"', "&quot;")
+}
+
+/// SVG path data (the `d` attribute) for an outline, in font units (y up).
+pub fn path_data(outline: &Outline<()>) -> String {
+    let pt = |(x, y): Pt| format!("{} {}", num(x), num(y));
+    outline_ops(outline)
+        .into_iter()
+        .map(|op| match op {
+            PathOp::Move(p) => format!("M{}", pt(p)),
+            PathOp::Line(p) => format!("L{}", pt(p)),
+            PathOp::Quad(c, p) => format!("Q{} {}", pt(c), pt(p)),
+            PathOp::Cubic(c1, c2, p) => format!("C{} {} {}", pt(c1), pt(c2), pt(p)),
+            PathOp::Close => String::from("Z"),
+        })
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+/// Draws `glif` as a standalone SVG document, filled in black, with `skeleton` (if given) outlined in
+/// red over it.
+///
+/// The viewBox spans the advance width horizontally, and the font's ascender to descender vertically
+/// when `metrics` are known (else the baseline and the outline's extent), widened to fit the drawing.
+pub fn glif_to_svg(glif: &Glif<()>, skeleton: Option<&Glif<()>>, metrics: Option<FontMetrics>) -> String {
+    let empty: Outline<()> = vec![];
+    let outline = glif.outline.as_ref().unwrap_or(&empty);
+    let skeleton_outline = skeleton.map(|s| s.outline.as_ref().unwrap_or(&empty));
+
+    let mut ops = outline_ops(outline);
+    if let Some(s) = skeleton_outline {
+        ops.extend(outline_ops(s));
+    }
+    let (mut x0, mut y0, mut x1, mut y1): (f64, f64, f64, f64) = match metrics {
+        Some(m) => (0., m.descender, 0., m.ascender),
+        None => (0., 0., 0., 0.),
+    };
+    if let Some(width) = glif.width {
+        x1 = width as f64;
+    }
+    if let Some((bx0, by0, bx1, by1)) = ops_bounds(&ops) {
+        (x0, y0, x1, y1) = (x0.min(bx0), y0.min(by0), x1.max(bx1), y1.max(by1));
+    }
+    let (width, height) = ((x1 - x0).max(1.), (y1 - y0).max(1.));
+
+    // The glyph is drawn y up, then flipped into SVG's y down space, so the viewBox's top is -y1.
+    let mut svg = format!(
+        "<svg xmlns=\"http://www.w3.org/2000/svg\" viewBox=\"{} {} {} {}\" width=\"{}\" height=\"{}\">\n",
+        num(x0),
+        num(-y1),
+        num(width),
+        num(height),
+        num(width),
+        num(height)
+    );
+    if !glif.name.is_empty() {
+        svg.push_str(&format!("  <title>{}</title>\n", escape(&glif.name)));
+    }
+    svg.push_str("  <g transform=\"scale(1 -1)\">\n");
+    svg.push_str(&format!("    <path d=\"{}\" fill=\"#000\"/>\n", path_data(outline)));
+    if let Some(s) = skeleton_outline {
+        svg.push_str(&format!(
+            "    <path d=\"{}\" fill=\"none\" stroke=\"#e0301e\" stroke-width=\"1.5\" vector-effect=\"non-scaling-stroke\"/>\n",
+            path_data(s)
+        ));
+    }
+    svg.push_str("  </g>\n</svg>\n");
+    svg
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn close(a: Pt, b: Pt) -> bool {
+        (a.0 - b.0).abs() < 1e-9 && (a.1 - b.1).abs() < 1e-9
+    }
+
+    // The on-curve points of each subpath of `d`, in order.
+    fn ends(d: &str) -> Vec<Vec<Pt>> {
+        parse_path_data(d)
+            .unwrap()
+            .iter()
+            .map(|s| s.segments.iter().map(|seg| seg.curve[0]).chain(s.segments.last().map(|seg| seg.curve[3])).collect())
+            .collect()
+    }
+
+    #[test]
+    fn relative_commands_match_absolute_ones() {
+        let absolute = parse_path_data("M10 10 L30 10 H40 V30 C40 40 30 50 20 50 Q10 50 10 40 Z").unwrap();
+        let relative = parse_path_data("m10 10 l20 0 h10 v20 c0 10 -10 20 -20 20 q-10 0 -10 -10 z").unwrap();
+        assert_eq!(absolute.len(), 1);
+        assert_eq!(relative.len(), 1);
+        assert!(absolute[0].closed && relative[0].closed);
+        assert_eq!(absolute[0].segments.len(), relative[0].segments.len());
+        for (a, r) in absolute[0].segments.iter().zip(relative[0].segments.iter()) {
+            assert_eq!(a.line, r.line);
+            assert!(a.curve.iter().zip(r.curve.iter()).all(|(&a, &r)| close(a, r)), "{:?} != {:?}", a, r);
+        }
+    }
+
+    #[test]
+    fn implicit_repeats() {
+        // A moveto's extra pairs are linetos, relative if it was; other commands just repeat.
+        assert_eq!(ends("M0 0 10 0 10 10"), vec![vec![(0., 0.), (10., 0.), (10., 10.)]]);
+        assert_eq!(ends("m5 5 10 0 0 10"), vec![vec![(5., 5.), (15., 5.), (15., 15.)]]);
+        assert_eq!(ends("M0 0 h10 10 v5 5"), vec![vec![(0., 0.), (10., 0.), (20., 0.), (20., 5.), (20., 10.)]]);
+        let cubics = parse_path_data("M0 0 C0 10 10 10 10 0 10 -10 20 -10 20 0").unwrap();
+        assert_eq!(cubics[0].segments.len(), 2);
+        assert_eq!(cubics[0].segments[1].curve, [(10., 0.), (10., -10.), (20., -10.), (20., 0.)]);
+    }
+
+    #[test]
+    fn numbers_run_together() {
+        assert_eq!(ends("M.5.5L-1-2 1e1,2E-1"), vec![vec![(0.5, 0.5), (-1., -2.), (10., 0.2)]]);
+        assert!(parse_path_data("M0 0 L1").is_err());
+        assert!(parse_path_data("M0 0 X1 1").is_err());
+    }
+
+    #[test]
+    fn smooth_curves_reflect_the_last_control_point() {
+        let subpaths = parse_path_data("M0 0 C0 10 10 10 10 0 S20 -10 20 0").unwrap();
+        assert_eq!(subpaths[0].segments[1].curve, [(10., 0.), (10., -10.), (20., -10.), (20., 0.)]);
+        // Without a curve before it, S's first control point is the current point.
+        let subpaths = parse_path_data("M0 0 S10 10 20 0").unwrap();
+        assert_eq!(subpaths[0].segments[0].curve[1], (0., 0.));
+    }
+
+    #[test]
+    fn arcs() {
+        // A half circle of radius 10 from (0, 0) to (20, 0), with the sweep flag set: in SVG's y down
+        // space, that's through (10, -10).
+        let subpaths = parse_path_data("M0 0 A10 10 0 0 1 20 0").unwrap();
+        let segments = &subpaths[0].segments;
+        assert_eq!(segments.len(), 2);
+        assert!(close(segments[0].curve[0], (0., 0.)));
+        assert!(close(segments[1].curve[3], (20., 0.)));
+        assert!(close(segments[0].curve[3], (10., -10.)));
+        for seg in segments {
+            let mid = crate::bezier::cubic_at(seg.curve, 0.5);
+            assert!(((mid.0 - 10.).hypot(mid.1) - 10.).abs() < 0.01);
+        }
+
+        // Cleared sweep flag, packed flags, relative end point.
+        let subpaths = parse_path_data("m0 0a10 10 0 0020 0").unwrap();
+        assert!(close(subpaths[0].segments[0].curve[3], (10., 10.)));
+
+        // Radii too small to reach are scaled up; zero radii make a line.
+        let subpaths = parse_path_data("M0 0 A1 1 0 0 1 20 0").unwrap();
+        assert!(close(subpaths[0].segments[0].curve[3], (10., -10.)));
+        let subpaths = parse_path_data("M0 0 A0 10 0 0 1 20 0").unwrap();
+        assert!(subpaths[0].segments[0].line);
+    }
+
+    #[test]
+    fn transforms() {
+        let apply = |t: &str, p: Pt| parse_transform(t).unwrap().apply(p);
+        assert!(close(apply("translate(10)", (1., 1.)), (11., 1.)));
+        // The rightmost transform applies first.
+        assert!(close(apply("translate(10, 20) scale(2)", (1., 1.)), (12., 22.)));
+        assert!(close(apply("scale(2),translate(10 20)", (1., 1.)), (22., 42.)));
+        assert!(close(apply("scale(2 3)", (1., 1.)), (2., 3.)));
+        assert!(close(apply("rotate(90)", (1., 0.)), (0., 1.)));
+        assert!(close(apply("rotate(90 1 1)", (2., 1.)), (1., 2.)));
+        assert!(close(apply("skewX(45)", (0., 1.)), (1., 1.)));
+        assert!(close(apply("skewY(45)", (1., 0.)), (1., 1.)));
+        assert!(close(apply("matrix(1 2 3 4 5 6)", (1., 1.)), (9., 12.)));
+        assert!(parse_transform("spin(1)").is_err());
+        assert!(parse_transform("scale(1 2 3)").is_err());
+        assert!(parse_transform("translate(1").is_err());
+    }
+
+    fn subpaths(svg: &str) -> Result<Vec<SubPath>, String> {
+        let mut out = vec![];
+        collect_subpaths(&Element::parse(svg.as_bytes()).unwrap(), &Affine::IDENTITY, &mut out).map(|_| out)
+    }
+
+    #[test]
+    fn shapes_under_transforms() {
+        let out = subpaths(r#"<svg><g transform="translate(100 0)"><rect x="0" y="0" width="10" height="20" transform="scale(2)"/></g></svg>"#).unwrap();
+        assert_eq!(out.len(), 1);
+        assert!(out[0].closed);
+        let corners: Vec<Pt> = out[0].segments.iter().map(|s| s.curve[0]).collect();
+        assert_eq!(corners, vec![(100., 0.), (120., 0.), (120., 40.), (100., 40.)]);
+
+        let out = subpaths(r#"<svg><circle cx="5" cy="5" r="5"/><defs><path d="M0 0 L1 1"/></defs><line x1="0" y1="0" x2="1" y2="1"/></svg>"#).unwrap();
+        assert_eq!(out.len(), 2);
+        assert_eq!(out[0].segments[0].curve[0], (10., 5.));
+        assert!(!out[1].closed);
+    }
+
+    #[test]
+    fn use_and_nested_svg_are_refused() {
+        assert!(subpaths(r##"<svg><path id="a" d="M0 0 L1 1"/><use href="#a"/></svg>"##).is_err());
+        assert!(subpaths(r#"<svg><g><svg viewBox="0 0 10 10"><path d="M0 0 L1 1"/></svg></g></svg>"#).is_err());
+        // Unless they're never rendered.
+        assert!(subpaths(r##"<svg><defs><use href="#a"/><svg/></defs></svg>"##).is_ok());
+    }
+}
@@ -0,0 +1,517 @@
+// Batch stroking of entire UFO fonts.
+//
+// Every mode knows how to stroke one glyph. This module walks the default layer's
+// `glyphs/contents.plist`, hands each glyph to the mode, and writes out a complete new UFO. Everything
+// that isn't a .glif in the default layer (metainfo.plist, fontinfo.plist, groups.plist, kerning.plist,
+// features.fea, lib.plist, other layers, data/, images/…) is carried over byte for byte.
+
+use std::ffi::OsStr;
+use std::fs;
+use std::io;
+use std::path::{Path as FsPath, PathBuf as FsPathBuf};
+
+use clap::ArgMatches;
+use glifparser::Glif;
+use rayon::prelude::*;
+use xmltree::{Element, XMLNode};
+
+use crate::glif_io;
+use crate::glyph_filter::GlyphFilter;
+use crate::preview;
+use crate::proof;
+use crate::remove_overlap::remove_overlap_if_requested;
+use crate::StrokeError;
+
+/// Name of the directory holding the default layer of a UFO.
+pub const DEFAULT_LAYER_DIR: &str = "glyphs";
+const CONTENTS_PLIST: &str = "contents.plist";
+
+/// A glyph as listed in a layer's contents.plist.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct UfoGlyph {
+    pub name: String,
+    pub filename: String,
+}
+
+/// True if `path` looks like a UFO font (a directory ending in .ufo, or one containing metainfo.plist).
+pub fn is_ufo<P: AsRef<OsStr> + ?Sized>(path: &P) -> bool {
+    let path = FsPath::new(path);
+    path.is_dir() && (path.extension().map(|e| e.eq_ignore_ascii_case("ufo")).unwrap_or(false) || path.join("metainfo.plist").is_file())
+}
+
+/// Reads the glyph name → file name mapping of a layer directory, in file order.
+pub fn read_contents_plist(layer_dir: &FsPath) -> io::Result<Vec<UfoGlyph>> {
+    let invalid = |msg: &str| io::Error::new(io::ErrorKind::InvalidData, format!("{}: {}", layer_dir.join(CONTENTS_PLIST).display(), msg));
+
+    let plist = Element::parse(fs::File::open(layer_dir.join(CONTENTS_PLIST))?).map_err(|e| invalid(&e.to_string()))?;
+    let dict = plist.get_child("dict").ok_or_else(|| invalid("no <dict> in plist"))?;
+
+    let mut glyphs = vec![];
+    let mut name: Option<String> = None;
+    for child in dict.children.iter() {
+        let el = match child {
+            XMLNode::Element(el) => el,
+            _ => continue,
+        };
+        let text = el.get_text().map(|t| t.into_owned()).unwrap_or_default();
+        match (el.name.as_str(), name.take()) {
+            ("key", None) => name = Some(text),
+            ("string", Some(name)) => glyphs.push(UfoGlyph { name, filename: text }),
+            _ => return Err(invalid("expected alternating <key> and <string> elements")),
+        }
+    }
+
+    Ok(glyphs)
+}
+
+/// Vertical metrics of a UFO font, from its fontinfo.plist.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct FontMetrics {
+    pub ascender: f64,
+    pub descender: f64,
+}
+
+/// Reads the ascender and descender from the fontinfo.plist of the UFO at `ufo`, if it has both.
+pub fn read_font_metrics(ufo: &FsPath) -> Option<FontMetrics> {
+    let plist = Element::parse(fs::File::open(ufo.join("fontinfo.plist")).ok()?).ok()?;
+    let dict = plist.get_child("dict")?;
+
+    let (mut ascender, mut descender) = (None, None);
+    let mut key: Option<String> = None;
+    for child in dict.children.iter() {
+        let el = match child {
+            XMLNode::Element(el) => el,
+            _ => continue,
+        };
+        let text = el.get_text().map(|t| t.trim().to_string()).unwrap_or_default();
+        match (el.name.as_str(), key.take()) {
+            ("key", _) => key = Some(text),
+            ("integer" | "real", Some(key)) if key == "ascender" => ascender = text.parse().ok(),
+            ("integer" | "real", Some(key)) if key == "descender" => descender = text.parse().ok(),
+            _ => {}
+        }
+    }
+
+    Some(FontMetrics {
+        ascender: ascender?,
+        descender: descender?,
+    })
+}
+
+/// The metrics of the UFO which `glif_path` belongs to, if it's in one of a UFO's layer directories.
+pub fn font_metrics_for_glif(glif_path: &FsPath) -> Option<FontMetrics> {
+    let ufo = glif_path.parent()?.parent()?;
+    if is_ufo(ufo) {
+        read_font_metrics(ufo)
+    } else {
+        None
+    }
+}
+
+fn is_glif(path: &FsPath) -> bool {
+    path.extension().map(|e| e.eq_ignore_ascii_case("glif")).unwrap_or(false)
+}
+
+// `path` made absolute with symbolic links resolved, as far as it exists.
+fn absolute(path: &FsPath) -> FsPathBuf {
+    if let Ok(path) = path.canonicalize() {
+        return path;
+    }
+    match (path.parent(), path.file_name()) {
+        (Some(parent), Some(name)) if !parent.as_os_str().is_empty() => absolute(parent).join(name),
+        (_, Some(name)) => absolute(FsPath::new(".")).join(name),
+        _ => path.to_path_buf(),
+    }
+}
+
+// Removes the .glif files from `layer_dir`, if it exists.
+fn remove_glifs(layer_dir: &FsPath) -> io::Result<()> {
+    if !layer_dir.is_dir() {
+        return Ok(());
+    }
+    for entry in fs::read_dir(layer_dir)? {
+        let path = entry?.path();
+        if is_glif(&path) && path.is_file() {
+            fs::remove_file(&path)?;
+        }
+    }
+    Ok(())
+}
+
+// Copies `src` into `dst` recursively, leaving out the files for which `skip` returns true.
+fn copy_dir<F: Fn(&FsPath) -> bool>(src: &FsPath, dst: &FsPath, skip: &F) -> io::Result<()> {
+    fs::create_dir_all(dst)?;
+    for entry in fs::read_dir(src)? {
+        let entry = entry?;
+        let path = entry.path();
+        if skip(&path) {
+            continue;
+        }
+        let target = dst.join(entry.file_name());
+        if entry.file_type()?.is_dir() {
+            copy_dir(&path, &target, skip)?;
+        } else {
+            fs::copy(&path, &target)?;
+        }
+    }
+    Ok(())
+}
+
+/// What became of one glyph of a batch run.
+#[derive(Debug)]
+pub enum GlyphStatus {
+    /// Not selected by the glyph filter; copied unchanged.
+    Unselected,
+    /// Nothing to stroke (e.g. space); copied unchanged.
+    Empty,
+    Stroked,
+    /// Failed to read or stroke; reported, and copied unchanged.
+    Failed(StrokeError),
+}
+
+/// One glyph of a batch run. The glifs before and after stroking are only kept if asked for, see
+/// [`stroke_ufo_report`].
+#[derive(Debug)]
+pub struct GlyphReport {
+    pub glyph: UfoGlyph,
+    pub status: GlyphStatus,
+    pub input: Option<Glif<()>>,
+    pub output: Option<Glif<()>>,
+    /// The contours of the input, by index, which the mode copied as they are rather than stroking them.
+    pub copied_contours: Vec<usize>,
+}
+
+/// A glyph as a mode strokes it for a batch run: the stroked glif, along with the contours of the input,
+/// by index, it copied as they are (as OFFSET does open contours), which the run reports. Modes which
+/// stroke every contour give a plain `Glif`.
+#[derive(Debug)]
+pub struct Stroked {
+    pub glif: Glif<()>,
+    pub copied_contours: Vec<usize>,
+}
+
+impl From<Glif<()>> for Stroked {
+    fn from(glif: Glif<()>) -> Self {
+        Stroked { glif, copied_contours: vec![] }
+    }
+}
+
+/// The outcome of a batch run, glyph by glyph in contents.plist order.
+#[derive(Debug)]
+pub struct BatchReport {
+    pub glyphs: Vec<GlyphReport>,
+    /// The font's vertical metrics, if its fontinfo.plist has them.
+    pub metrics: Option<FontMetrics>,
+}
+
+impl BatchReport {
+    /// Number of glyphs which failed to read or stroke.
+    pub fn failed(&self) -> usize {
+        self.glyphs.iter().filter(|g| matches!(g.status, GlyphStatus::Failed(_))).count()
+    }
+
+    /// `StrokeError::Batch` if any glyph failed.
+    pub fn result(&self) -> Result<(), StrokeError> {
+        match self.failed() {
+            0 => Ok(()),
+            failed => Err(StrokeError::Batch { failed, total: self.glyphs.len() }),
+        }
+    }
+}
+
+/// Strokes every glyph of the default layer of the UFO at `input` selected by `filter`, writing a new
+/// UFO to `output`. See [`stroke_ufo_report`] for the details; this fails with `StrokeError::Batch` if
+/// any glyph failed.
+pub fn stroke_ufo<F, S>(input: &FsPath, output: &FsPath, filter: &GlyphFilter, stroke: F) -> Result<(), StrokeError>
+where
+    F: Fn(&Glif<()>) -> Result<S, StrokeError> + Sync,
+    S: Into<Stroked>,
+{
+    stroke_ufo_report(input, output, filter, false, stroke)?.result()
+}
+
+/// Strokes every glyph of the default layer of the UFO at `input` selected by `filter`, writing a new
+/// UFO to `output`, and reports what became of each glyph. If `keep_glifs`, the report holds every
+/// stroked glyph before and after stroking.
+///
+/// `stroke` is called once per glyph with the parsed glif; its `filename` is set to the glyph's file in
+/// the input UFO. Glyphs are stroked concurrently on rayon's thread pool (see `--jobs`), but written out
+/// and reported in contents.plist order, so the output doesn't depend on the number of threads.
+///
+/// Glyphs which aren't selected or have no contours are copied over unchanged. So are glyphs which
+/// fail to read or stroke; each of those is reported on stderr as well as in the returned report, and
+/// the rest of the font is still written, so the output is always a complete font. Only errors
+/// affecting the whole font (an unreadable contents.plist, an unwritable output…) are returned as `Err`.
+/// Contours a glyph's stroke copied as they are (see [`Stroked`]) are reported on stderr after the run,
+/// as well as in the returned report.
+pub fn stroke_ufo_report<F, S>(input: &FsPath, output: &FsPath, filter: &GlyphFilter, keep_glifs: bool, stroke: F) -> Result<BatchReport, StrokeError>
+where
+    F: Fn(&Glif<()>) -> Result<S, StrokeError> + Sync,
+    S: Into<Stroked>,
+{
+    let read_err = |path: &FsPath| {
+        let path = path.to_path_buf();
+        move |source| StrokeError::Read { path, source }
+    };
+    let write_err = |path: &FsPath| {
+        let path = path.to_path_buf();
+        move |source| StrokeError::Write { path, source }
+    };
+
+    // An output inside the input would be copied into itself without end.
+    if absolute(output).starts_with(input.canonicalize().map_err(read_err(input))?) {
+        let source = io::Error::new(io::ErrorKind::InvalidInput, "refusing to write the output UFO over or inside the input UFO");
+        return Err(StrokeError::Write { path: output.to_path_buf(), source });
+    }
+
+    let in_layer = input.join(DEFAULT_LAYER_DIR);
+    let out_layer = output.join(DEFAULT_LAYER_DIR);
+    let glyphs = read_contents_plist(&in_layer).map_err(read_err(&in_layer.join(CONTENTS_PLIST)))?;
+
+    // Glyphs left in an existing output by an earlier run would otherwise stay in the font without
+    // being listed in its contents.plist.
+    remove_glifs(&out_layer).map_err(write_err(&out_layer))?;
+
+    // Everything but the default layer's glyphs is carried over untouched; contents.plist and
+    // layerinfo.plist are copied too, as glyph names and file names don't change.
+    copy_dir(input, output, &|p: &FsPath| p.parent() == Some(in_layer.as_path()) && is_glif(p)).map_err(write_err(output))?;
+
+    // Each glyph's report, and its serialized output if it was stroked.
+    let results: Vec<(GlyphReport, Option<String>)> = glyphs
+        .par_iter()
+        .map(|glyph| {
+            let in_file: FsPathBuf = in_layer.join(&glyph.filename);
+            let read = || glif_io::read_glif(&in_file);
+            let report = |status, before, after| GlyphReport {
+                glyph: glyph.clone(),
+                status,
+                input: before,
+                output: after,
+                copied_contours: vec![],
+            };
+
+            // We only parse unselected glyphs if we need their code points to know they're unselected. A
+            // glyph whose code points can't be read can't be told to be unselected, so it fails.
+            let glif: Option<Glif<()>> = match filter.needs_unicodes().then(read).transpose() {
+                Ok(glif) => glif,
+                Err(e) => return (report(GlyphStatus::Failed(e), None, None), None),
+            };
+            let unicodes = glif.as_ref().map(|glif| glif.unicode.as_slice()).unwrap_or(&[]);
+            if !filter.selects(&glyph.name, unicodes) {
+                return (report(GlyphStatus::Unselected, None, None), None);
+            }
+
+            let glif = match glif.map(Ok).unwrap_or_else(read) {
+                Ok(glif) if !glif_io::has_contours(&glif) => return (report(GlyphStatus::Empty, None, None), None),
+                Ok(glif) => glif,
+                Err(e) => return (report(GlyphStatus::Failed(e), None, None), None),
+            };
+
+            let result = stroke(&glif).map(Into::<Stroked>::into).map_err(|e| e.in_file(&in_file)).and_then(|out| {
+                glif_io::glif_to_string(&out.glif, out_layer.join(&glyph.filename)).map(|glifstring| (glifstring, out))
+            });
+
+            match result {
+                Ok((glifstring, out)) => (
+                    GlyphReport {
+                        copied_contours: out.copied_contours,
+                        ..report(GlyphStatus::Stroked, keep_glifs.then_some(glif), keep_glifs.then_some(out.glif))
+                    },
+                    Some(glifstring),
+                ),
+                Err(e) => (report(GlyphStatus::Failed(e), keep_glifs.then_some(glif), None), None),
+            }
+        })
+        .collect();
+
+    let mut reports = Vec::with_capacity(results.len());
+    for (report, glifstring) in results {
+        let in_file: FsPathBuf = in_layer.join(&report.glyph.filename);
+        let out_file: FsPathBuf = out_layer.join(&report.glyph.filename);
+
+        match (&report.status, glifstring) {
+            (GlyphStatus::Stroked, Some(glifstring)) => glif_io::write_file(&out_file, glifstring)?,
+            (status, _) => {
+                if let GlyphStatus::Failed(e) = status {
+                    eprintln!("{} (glyph {} copied unchanged)", e, report.glyph.name);
+                }
+                fs::copy(&in_file, &out_file).map_err(write_err(&out_file))?;
+            }
+        }
+        reports.push(report);
+    }
+
+    // Reported once the whole font is written, in order, rather than as each glyph is stroked, where
+    // they would be interleaved.
+    for report in reports.iter().filter(|r| !r.copied_contours.is_empty()) {
+        let contours: Vec<String> = report.copied_contours.iter().map(usize::to_string).collect();
+        eprintln!("glyph {}: contour(s) {} copied as they are", report.glyph.name, contours.join(", "));
+    }
+
+    let report = BatchReport {
+        glyphs: reports,
+        metrics: read_font_metrics(input),
+    };
+    let stroked = report.glyphs.iter().filter(|g| matches!(g.status, GlyphStatus::Stroked)).count();
+    log::info!("Stroked {} of {} glyphs ({} failed)", stroked, report.glyphs.len(), report.failed());
+
+    Ok(report)
+}
+
+/// The command line's .ufo run: strokes the glyphs of `input` chosen by the glyph selection arguments
+/// into `output`, removing the overlaps of each if --remove-overlap was given, and writing a proof sheet
+/// too if --proof was given. `settings` describes the stroking settings in the proof sheet.
+pub fn stroke_ufo_cli<F, S>(matches: &ArgMatches, input: &FsPath, output: &FsPath, settings: &str, stroke: F) -> Result<(), StrokeError>
+where
+    F: Fn(&Glif<()>) -> Result<S, StrokeError> + Sync,
+    S: Into<Stroked>,
+{
+    preview::reject_for_ufo(matches)?;
+    let filter = GlyphFilter::from_matches(matches)?;
+    let proof_file = matches.value_of_os("proof").map(FsPathBuf::from);
+
+    let stroke = |glif: &Glif<()>| -> Result<Stroked, StrokeError> {
+        let out: Stroked = stroke(glif)?.into();
+        Ok(Stroked {
+            glif: remove_overlap_if_requested(matches, out.glif)?,
+            ..out
+        })
+    };
+    let report = stroke_ufo_report(input, output, &filter, proof_file.is_some(), stroke)?;
+    if let Some(proof_file) = proof_file {
+        let title = format!("{} → {}", input.display(), output.display());
+        let settings = format!("{}\nremove overlap: {}", settings, matches.is_present("remove-overlap"));
+        proof::write_proof_sheet(&proof_file, &report, &title, &settings)?;
+    }
+    report.result()
+}
+
+#[cfg(test)]
+mod tests {
+    use std::env;
+    use std::process;
+    use std::thread;
+    use std::time::Duration;
+
+    use glifparser::{Point, PointType};
+
+    use super::*;
+
+    fn plist(dict: &str) -> String {
+        format!("<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n<plist version=\"1.0\">\n<dict>\n{}</dict>\n</plist>\n", dict)
+    }
+
+    fn square(x: f32) -> Vec<Point<()>> {
+        [(x, 0.), (x + 100., 0.), (x + 100., 100.), (x, 100.)].iter().map(|&p| Point::from_x_y_type(p, PointType::Line)).collect()
+    }
+
+    // A UFO in the temporary directory, with a glyph of a square for each of `names`, but for "space",
+    // which has no contours.
+    fn tiny_ufo(test: &str, names: &[String]) -> FsPathBuf {
+        let dir = env::temp_dir().join(format!("MFEKstroke-{}-{}", process::id(), test));
+        let _ = fs::remove_dir_all(&dir);
+        let ufo = dir.join("in.ufo");
+        let layer = ufo.join(DEFAULT_LAYER_DIR);
+        fs::create_dir_all(&layer).unwrap();
+        fs::write(ufo.join("metainfo.plist"), plist("<key>formatVersion</key>\n<integer>3</integer>\n")).unwrap();
+
+        let mut contents = String::new();
+        for name in names {
+            let mut glif: Glif<()> = Glif::new();
+            glif.name = name.clone();
+            if name != "space" {
+                glif.outline = Some(vec![square(0.)]);
+            }
+            glif_io::write_glif(&glif, layer.join(format!("{}.glif", name))).unwrap();
+            contents += &format!("<key>{0}</key>\n<string>{0}.glif</string>\n", name);
+        }
+        fs::write(layer.join(CONTENTS_PLIST), plist(&contents)).unwrap();
+        ufo
+    }
+
+    // Adds a second square beside the first, or fails for the glyph named "fails".
+    fn stroke(glif: &Glif<()>) -> Result<Glif<()>, StrokeError> {
+        if glif.name == "fails" {
+            return Err(StrokeError::Geometry { path: None, message: String::from("can't stroke this") });
+        }
+        let mut out = glif.clone();
+        out.outline.as_mut().unwrap().push(square(200.));
+        Ok(out)
+    }
+
+    fn contours(glif: &FsPath) -> usize {
+        glif_io::read_glif(glif).unwrap().outline.map(|o| o.len()).unwrap_or(0)
+    }
+
+    #[test]
+    fn failures_and_empty_glyphs_are_copied_unchanged() {
+        let names: Vec<String> = ["A", "fails", "space", "B"].iter().map(|n| n.to_string()).collect();
+        let input = tiny_ufo("failures", &names);
+        let output = input.with_file_name("out.ufo");
+
+        let report = stroke_ufo_report(&input, &output, &GlyphFilter::default(), false, stroke).unwrap();
+        let statuses: Vec<_> = report.glyphs.iter().map(|g| (g.glyph.name.as_str(), &g.status)).collect();
+        assert!(matches!(
+            statuses[..],
+            [
+                ("A", GlyphStatus::Stroked),
+                ("fails", GlyphStatus::Failed(StrokeError::Geometry { .. })),
+                ("space", GlyphStatus::Empty),
+                ("B", GlyphStatus::Stroked)
+            ]
+        ));
+        assert!(matches!(report.result(), Err(StrokeError::Batch { failed: 1, total: 4 })));
+
+        // The rest of the font is still written.
+        let (in_layer, out_layer) = (input.join(DEFAULT_LAYER_DIR), output.join(DEFAULT_LAYER_DIR));
+        assert_eq!(contours(&out_layer.join("A.glif")), 2);
+        assert_eq!(contours(&out_layer.join("B.glif")), 2);
+        for unchanged in ["fails.glif", "space.glif", CONTENTS_PLIST] {
+            assert_eq!(fs::read(in_layer.join(unchanged)).unwrap(), fs::read(out_layer.join(unchanged)).unwrap(), "{}", unchanged);
+        }
+        assert_eq!(fs::read(input.join("metainfo.plist")).unwrap(), fs::read(output.join("metainfo.plist")).unwrap());
+
+        fs::remove_dir_all(input.parent().unwrap()).unwrap();
+    }
+
+    #[test]
+    fn glyphs_stay_in_order_on_many_threads() {
+        let names: Vec<String> = (0..16).map(|i| format!("g{:02}", i)).collect();
+        let input = tiny_ufo("order", &names);
+        let output = input.with_file_name("out.ufo");
+
+        // The first glyphs take longest, so they finish last.
+        let slow = |glif: &Glif<()>| {
+            let i: u64 = glif.name[1..].parse().unwrap();
+            thread::sleep(Duration::from_millis(2 * (16 - i)));
+            stroke(glif)
+        };
+        let pool = rayon::ThreadPoolBuilder::new().num_threads(4).build().unwrap();
+        let report = pool.install(|| stroke_ufo_report(&input, &output, &GlyphFilter::default(), true, slow)).unwrap();
+
+        let reported: Vec<&str> = report.glyphs.iter().map(|g| g.glyph.name.as_str()).collect();
+        assert_eq!(reported, names);
+        assert!(report.glyphs.iter().all(|g| g.output.as_ref().map(|o| o.name == g.glyph.name).unwrap_or(false)));
+        let written: Vec<String> = read_contents_plist(&output.join(DEFAULT_LAYER_DIR)).unwrap().into_iter().map(|g| g.name).collect();
+        assert_eq!(written, names);
+
+        fs::remove_dir_all(input.parent().unwrap()).unwrap();
+    }
+
+    #[test]
+    fn copied_contours_are_reported() {
+        let input = tiny_ufo("copied", &[String::from("A")]);
+        let output = input.with_file_name("out.ufo");
+
+        let copy_first = |glif: &Glif<()>| -> Result<Stroked, StrokeError> {
+            Ok(Stroked {
+                glif: glif.clone(),
+                copied_contours: vec![0],
+            })
+        };
+        let report = stroke_ufo_report(&input, &output, &GlyphFilter::default(), false, copy_first).unwrap();
+        assert_eq!(report.glyphs[0].copied_contours, [0]);
+
+        fs::remove_dir_all(input.parent().unwrap()).unwrap();
+    }
+}
@@ -1,10 +1,32 @@
-use std::fs;
+use std::path::Path as FsPath;
 
-use clap::{App, Arg};
-use MFEKmath::{variable_width_stroking::{variable_width_stroke_glif, VWSSettings}};
+use clap::{App, AppSettings, Arg};
+use glifparser::glif::contour_operations::vws::{InterpolationType, VWSHandle};
+use glifparser::glif::contour_operations::ContourOperations;
+use glifparser::glif::mfek::MFEKGlif;
+use glifparser::{CapType, Glif, JoinType, Outline, VWSContour};
+use rayon::prelude::*;
+use MFEKmath::variable_width_stroking::{parse_vws_lib, variable_width_stroke, VWSSettings};
+use MFEKmath::Piecewise;
+
+use crate::bezier;
+use crate::boolean::{self, BooleanOptions};
+use crate::custom_join;
+use crate::error::{catch_geometry, parse_arg};
+use crate::glif_io::{self, InputOptions};
+use crate::glyph_filter;
+use crate::pen_model::{Pen, PenModel, Ramp};
+use crate::preview::{self, PreviewOptions};
+use crate::proof;
+use crate::remove_overlap::{self, remove_overlap_if_requested};
+use crate::svg;
+use crate::ufo;
+use crate::width_profile::{self, WidthCurve, WidthProfile};
+use crate::StrokeError;
 
 pub fn clap_app() -> clap::App<'static> {
     App::new("VWS")
+        .setting(AppSettings::DeriveDisplayOrder)
         .alias("variable")
         .alias("vws")
         .about("Takes a .glif file and strokes it with variable width.")
@@ -13,34 +35,651 @@ pub fn clap_app() -> clap::App<'static> {
         .arg(
             Arg::new("input")
                 .short('i')
+                .long("input")
                 .takes_value(true)
                 .allow_invalid_utf8(true)
-                .help("The path to the input file.")
+                .help("The path to the input file. May be a .glif, .glifjson or .svg, or a .ufo font to stroke every glyph.")
                 .required(true),
         )
         .arg(
             Arg::new("output")
                 .short('o')
+                .long("output")
                 .takes_value(true)
                 .allow_invalid_utf8(true)
-                .help("The path where the output will be saved.")
+                .help("The path where the output will be saved: a .glif, a .glifjson, or an .svg drawing. Must be a .ufo if the input is a .ufo.")
                 .required(true),
         )
+        .arg(
+            Arg::new("startcap")
+                .long("startcap")
+                .short('s')
+                .takes_value(true)
+                .help(r#"Either the constant strings "circle", "round" or "square", or a .glif or .svg file. Overrides the stored caps."#),
+        )
+        .arg(
+            Arg::new("endcap")
+                .long("endcap")
+                .short('e')
+                .takes_value(true)
+                .help(r#"Either the constant strings "circle", "round" or "square", or a .glif or .svg file. Overrides the stored caps."#),
+        )
+        .arg(
+            Arg::new("jointype")
+                .long("jointype")
+                .short('j')
+                .takes_value(true)
+                .help(r#"How to join discontinuous splines: "round", "circle", "miter" or "bevel", or a .glif or .svg file of a shape to draw at each corner. Overrides the stored joins."#),
+        )
+        .arg(
+            Arg::new("width")
+                .long("width")
+                .short('w')
+                .takes_value(true)
+                .help(r#"<f64> Stroke width at every handle, overriding the stored widths."#)
+                .validator(super::arg_validator_positive_f64)
+                .conflicts_with("left")
+                .conflicts_with("right"),
+        )
+        .arg(
+            Arg::new("left")
+                .long("left")
+                .short('l')
+                .takes_value(true)
+                .help(r#"<f64> Stroke width (left) at every handle, overriding the stored widths."#)
+                .validator(super::arg_validator_positive_f64)
+                .requires("right"),
+        )
+        .arg(
+            Arg::new("right")
+                .long("right")
+                .short('r')
+                .takes_value(true)
+                .help(r#"<f64> Stroke width (right) at every handle, overriding the stored widths."#)
+                .validator(super::arg_validator_positive_f64)
+                .requires("left"),
+        )
+        .arg(
+            Arg::new("profile")
+                .long("profile")
+                .short('p')
+                .takes_value(true)
+                .allow_invalid_utf8(true)
+                .conflicts_with_all(&["width", "left", "right"])
+                .help("A width profile (.csv or .json) giving the widths along each contour it lists, instead of the stored ones."),
+        )
+        .arg(
+            Arg::new("interpolation")
+                .long("interpolation")
+                .takes_value(true)
+                .possible_values(&["linear", "none"])
+                .default_value("linear")
+                .help("How the width changes towards handles which have no stored VWS data."),
+        )
+        .arg(
+            Arg::new("width-curve")
+                .long("width-curve")
+                .takes_value(true)
+                .possible_values(&WidthCurve::NAMES)
+                .default_value("linear")
+                .help("The curve the width follows from handle to handle (or profile row to row). All but linear subdivide the path."),
+        )
+        .arg(
+            Arg::new("width-samples")
+                .long("width-samples")
+                .takes_value(true)
+                .validator(super::arg_validator_usize)
+                .default_value("8")
+                .help("<usize> Segments each Bézier segment is split into to follow a --width-curve other than linear, or a pen."),
+        )
+        .arg(
+            Arg::new("pen-width")
+                .long("pen-width")
+                .takes_value(true)
+                .validator(|s| s.parse::<Ramp>().map(|_| ()))
+                .conflicts_with_all(&["width", "left", "right", "profile", "width-curve"])
+                .help("<f64[:f64]> Stroke as a broad nib pen this wide would, instead of with the stored widths. START:END changes it along each contour."),
+        )
+        .arg(
+            Arg::new("pen-thickness")
+                .long("pen-thickness")
+                .takes_value(true)
+                .validator(|s| s.parse::<Ramp>().map(|_| ()))
+                .default_value("0")
+                .help("<f64[:f64]> The pen's nib's thickness, across its edge."),
+        )
+        .arg(
+            Arg::new("pen-angle")
+                .long("pen-angle")
+                .takes_value(true)
+                .validator(|s| s.parse::<Ramp>().map(|_| ()))
+                .default_value("30")
+                .help("<f64[:f64]> The angle the pen is held at, in degrees counterclockwise from the baseline."),
+        )
+        .arg(
+            Arg::new("pressure")
+                .long("pressure")
+                .takes_value(true)
+                .validator(|s| s.parse::<Ramp>().map(|_| ()))
+                .requires("pen-width")
+                .help("<f64[:f64]> Use the expansion model: the pen's width is scaled by this pressure (1 for its own width), rather than kept."),
+        )
+        .arg(
+            Arg::new("remove-internal")
+                .long("remove-internal")
+                .short('I')
+                .takes_value(false)
+                .help(r#"Remove internal contour"#),
+        )
+        .arg(
+            Arg::new("remove-external")
+                .long("remove-external")
+                .short('E')
+                .takes_value(false)
+                .help(r#"Remove external contour"#),
+        )
+        .arg(
+            Arg::new("segmentwise")
+                .long("segmentwise")
+                .short('S')
+                .takes_value(false)
+                .conflicts_with("jointype")
+                .help(r#"Join all segments with caps (stroke all Bézier segments one by one)"#),
+        )
+        .args(remove_overlap::clap_args())
+        .args(boolean::clap_args())
+        .args(glyph_filter::clap_args())
+        .args(svg::clap_args())
+        .args(glif_io::clap_args())
+        .args(svg::output_clap_args())
+        .args(preview::clap_args())
+        .args(proof::clap_args())
 }
 
-pub fn vws_cli(matches: &clap::ArgMatches) {
-    let input_string = matches.value_of("input").unwrap();
-    let output_string = matches.value_of("output").unwrap();
+/// Settings for variable width stroking. The widths come from the VWS data in the glif's lib (as written
+/// by MFEKglif); every `Some` setting here overrides the stored one for all contours.
+#[derive(Debug)]
+pub struct VwsOptions {
+    /// Custom cap glifs, used when `startcap`/`endcap` are `CapType::Custom`.
+    pub vws_settings: VWSSettings<()>,
+    pub startcap: Option<CapType>,
+    pub endcap: Option<CapType>,
+    pub jointype: Option<JoinType>,
+    /// A shape drawn at every corner of the path, pointing out of it, scaled so that its width is the
+    /// stroke's there; `jointype` should then be `Some(JoinType::Bevel)`, for the shape to make the corner.
+    /// Not drawn when `segmentwise`, as there are no corners then.
+    pub join_custom: Option<Glif<()>>,
+    /// Remove the internal contour of every stroke, not only of those stored so.
+    pub remove_internal: bool,
+    /// Remove the external contour of every stroke, not only of those stored so.
+    pub remove_external: bool,
+    /// Stroke every Bézier segment on its own, capping both ends of each.
+    pub segmentwise: bool,
+    /// Stroke width to the left of the path at every handle.
+    pub left: Option<f64>,
+    /// Stroke width to the right of the path at every handle.
+    pub right: Option<f64>,
+    /// Widths for the contours it has rows for, see [`WidthProfile`].
+    pub profile: Option<WidthProfile>,
+    /// Interpolation of handles with no stored VWS data. Contours with none at all can only be stroked
+    /// when `left` and `right` are given, or `profile` has rows for them.
+    pub default_interpolation: InterpolationType,
+    /// The curve the widths follow between handles, unless a profile row names its own.
+    pub width_curve: WidthCurve,
+    /// How many segments each Bézier segment is split into, when any curve but `WidthCurve::Linear` or a
+    /// pen is used, so that the handles follow it.
+    pub width_samples: usize,
+    /// Generates the widths of every contour, as drawn with a broad nib pen, instead of using the stored
+    /// ones or any of the above. Stored caps and joins are kept.
+    pub pen: Option<Pen>,
+    /// How `join_custom` is united with the stroke.
+    pub boolean: BooleanOptions,
+}
 
-    let input: glifparser::Glif<()> = glifparser::read(&fs::read_to_string(input_string).expect("Failed to read path file!")).unwrap(); // TODO: Proper error handling!
+impl Default for VwsOptions {
+    fn default() -> Self {
+        VwsOptions {
+            vws_settings: VWSSettings {
+                cap_custom_start: None,
+                cap_custom_end: None,
+            },
+            startcap: None,
+            endcap: None,
+            jointype: None,
+            join_custom: None,
+            remove_internal: false,
+            remove_external: false,
+            segmentwise: false,
+            left: None,
+            right: None,
+            profile: None,
+            default_interpolation: InterpolationType::Linear,
+            width_curve: WidthCurve::Linear,
+            width_samples: 8,
+            pen: None,
+            boolean: BooleanOptions::default(),
+        }
+    }
+}
 
-    // TODO: Copy logic from CWS here
-    let settings = VWSSettings::<()> {
-        cap_custom_end: None,
-        cap_custom_start: None,
+/// Reads a cap argument: `None` for the built in cap types, or else the .glif or .svg file it names,
+/// which must have contours.
+pub(crate) fn custom_cap_if_requested(ct: CapType, input_file: &str, input_options: &InputOptions) -> Result<Option<Glif<()>>, StrokeError> {
+    if ct == CapType::Custom {
+        let invalid_cap = |message: String| StrokeError::InvalidCap { path: input_file.into(), message };
+        let path = glif_io::read_input(input_file, input_options).map_err(|e| match e {
+            StrokeError::Read { source, .. } => invalid_cap(format!(r#"not "circle", "round" or "square", nor a readable .glif or .svg ({})"#, source)),
+            e => invalid_cap(e.to_string()),
+        })?;
+        if !glif_io::has_contours(&path) {
+            return Err(invalid_cap(String::from("cap glif has no contours")));
+        }
+        Ok(Some(path))
+    } else {
+        Ok(None)
+    }
+}
+
+// The VWS data stored in the lib of `path`, one per contour, if any.
+fn stored_vws_contours(path: &Glif<()>) -> Vec<VWSContour> {
+    parse_vws_lib(path).map(|(contours, _)| contours).unwrap_or_default()
+}
+
+// A contour's VWS data when it has none stored, before any handles are made for it.
+fn default_vws_contour() -> VWSContour {
+    VWSContour {
+        join_type: JoinType::Round,
+        cap_start_type: CapType::Circle,
+        cap_end_type: CapType::Circle,
+        handles: vec![],
+        remove_internal: false,
+        remove_external: false,
+    }
+}
+
+// Applies the cap, join and removal overrides of `options` to `vws_contour`.
+fn override_contour_settings(vws_contour: &mut VWSContour, options: &VwsOptions) {
+    vws_contour.cap_start_type = options.startcap.unwrap_or(vws_contour.cap_start_type);
+    vws_contour.cap_end_type = options.endcap.unwrap_or(vws_contour.cap_end_type);
+    vws_contour.join_type = options.jointype.unwrap_or(vws_contour.join_type);
+    vws_contour.remove_internal |= options.remove_internal;
+    vws_contour.remove_external |= options.remove_external;
+}
+
+// The stored VWS data of each contour of `path`, with the overrides of `options` applied, and a handle for
+// every point (plus one, for the end of closed contours). Returns `path` along with them, subdivided if
+// the widths follow a curve or a pen.
+fn make_vws_contours(path: &Glif<()>, options: &VwsOptions) -> Result<(Glif<()>, Vec<VWSContour>), StrokeError> {
+    let stored = stored_vws_contours(path);
+    let outline = match path.outline.as_ref() {
+        Some(outline) => outline,
+        None => return Ok((path.clone(), vec![])),
     };
+    let widths = options.left.zip(options.right);
+    if let Some(profile) = options.profile.as_ref() {
+        profile.check_contours(outline.len())?;
+    }
+
+    let contours: Vec<(_, VWSContour)> = outline
+        .iter()
+        .enumerate()
+        .map(|(cidx, contour)| {
+            if let Some(pen) = options.pen.as_ref() {
+                let contour = bezier::subdivide(contour, options.width_samples);
+                let mut vws_contour = stored.get(cidx).cloned().unwrap_or_else(default_vws_contour);
+                vws_contour.handles = pen.handles(&contour, options.default_interpolation);
+                override_contour_settings(&mut vws_contour, options);
+                return Ok((contour, vws_contour));
+            }
+
+            let stops = match options.profile.as_ref() {
+                Some(profile) => profile.stops(cidx, contour, options.width_curve)?,
+                None => None,
+            };
+            let profiled = stops.as_ref().map(|stops| width_profile::handles_at(stops, contour, options.default_interpolation));
+            let mut vws_contour = match (stored.get(cidx), widths.is_some() || profiled.is_some()) {
+                (Some(vws_contour), _) => vws_contour.clone(),
+                (None, true) => default_vws_contour(),
+                (None, false) => {
+                    return Err(StrokeError::NoVwsData {
+                        path: path.filename.clone(),
+                        contour: cidx,
+                    })
+                }
+            };
+            if let Some(handles) = profiled {
+                vws_contour.handles = handles;
+            }
+
+            // Missing handles continue at the width of the last stored one.
+            let fill = match (vws_contour.handles.last(), widths) {
+                (Some(last), _) => VWSHandle {
+                    interpolation: options.default_interpolation,
+                    ..*last
+                },
+                (None, Some((left, right))) => VWSHandle {
+                    left_offset: left,
+                    right_offset: right,
+                    tangent_offset: 0.0,
+                    interpolation: options.default_interpolation,
+                },
+                (None, None) => {
+                    return Err(StrokeError::NoVwsData {
+                        path: path.filename.clone(),
+                        contour: cidx,
+                    })
+                }
+            };
+            vws_contour.handles.resize(contour.len() + 1, fill);
+
+            if let Some((left, right)) = widths {
+                for handle in vws_contour.handles.iter_mut() {
+                    handle.left_offset = left;
+                    handle.right_offset = right;
+                }
+            }
+            override_contour_settings(&mut vws_contour, options);
+
+            let stops = match stops {
+                Some(stops) if widths.is_none() => stops,
+                _ => width_profile::stops_from_handles(contour, &vws_contour.handles, options.width_curve),
+            };
+            // Stops between the points get points of their own, for their handles to sit on. Linear
+            // widths need nothing more, and if every stop is on a point already, the handles are right.
+            let total = bezier::point_arc_lengths(contour).last().copied().unwrap_or(0.);
+            let split = bezier::split_at_lengths(contour, &stops.iter().map(|s| s.at * total).collect::<Vec<_>>());
+            let linear = stops.iter().all(|s| s.curve == WidthCurve::Linear);
+            if linear && split.len() == contour.len() {
+                return Ok((contour.clone(), vws_contour));
+            }
+            let contour = if linear { split } else { bezier::subdivide(&split, options.width_samples) };
+            vws_contour.handles = width_profile::handles_at(&stops, &contour, options.default_interpolation);
+            Ok((contour, vws_contour))
+        })
+        .collect::<Result<_, StrokeError>>()?;
+
+    let (outline, vws_contours) = contours.into_iter().unzip();
+    Ok((
+        Glif {
+            outline: Some(outline),
+            ..path.clone()
+        },
+        vws_contours,
+    ))
+}
+
+/// Attaches each of `vws_contours` to the corresponding contour of `path` as a variable width stroke
+/// operation, for further editing in MFEKglif.
+pub(crate) fn attach_vws_operations(path: &Glif<()>, vws_contours: &[VWSContour]) -> MFEKGlif<()> {
+    let mut ret: MFEKGlif<()> = path.clone().into();
+    for (contour, vws_contour) in ret.layers[0].outline.iter_mut().zip(vws_contours) {
+        contour.set_operation(Some(ContourOperations::VariableWidthStroke { data: vws_contour.clone() }));
+    }
+    ret
+}
+
+/// Strokes each contour of `path` with the corresponding one of `vws_contours`. If `segmentwise`, every
+/// Bézier segment is stroked (and capped) on its own, between the handles of its two ends.
+pub(crate) fn stroke_vws_contours(path: &Glif<()>, vws_contours: &[VWSContour], settings: &VWSSettings<()>, segmentwise: bool) -> Outline<()> {
+    if path.outline.as_ref().map(|o| o.len() == 0).unwrap_or(true) {
+        return Outline::new();
+    }
+    // convert our path and pattern to piecewise collections of beziers
+    let piece_path = Piecewise::from(path.outline.as_ref().unwrap());
+    let mut output_outline: Outline<()> = Vec::new();
+
+    // Contours are stroked independently of each other, so we stroke them concurrently. Collecting keeps
+    // them in input order, so the output is the same as a serial run's.
+    let results: Vec<_> = piece_path
+        .segs
+        .par_iter()
+        .enumerate()
+        .map(|(i, pwpath_contour)| {
+            let vws_contour = &vws_contours[i];
+
+            if segmentwise {
+                pwpath_contour
+                    .segs
+                    .iter()
+                    .enumerate()
+                    .map(|(j, p)| variable_width_stroke(&Piecewise::new(vec![p.clone()], None), &segment_vws_contour(vws_contour, j), settings))
+                    .collect()
+            } else {
+                vec![variable_width_stroke(&pwpath_contour, &vws_contour, settings)]
+            }
+        })
+        .collect();
+
+    for result_outline in results.into_iter().flatten() {
+        for result_contour in result_outline.segs.iter() {
+            output_outline.push(result_contour.to_contour());
+        }
+    }
+    output_outline
+}
+
+/// The stroke data of segment `j` of a contour stroked with `vws_contour`: the handles of its two ends. A closed
+/// contour's last segment ends on its first point, and so takes its handle.
+fn segment_vws_contour(vws_contour: &VWSContour, j: usize) -> VWSContour {
+    let handles = &vws_contour.handles;
+    match handles.len() {
+        0 => vws_contour.clone(),
+        n => VWSContour {
+            handles: vec![handles[j.min(n - 1)].clone(), handles[(j + 1) % n].clone()],
+            ..vws_contour.clone()
+        },
+    }
+}
+
+/// Strokes `path` according to the variable width stroke data stored in its lib (as written by MFEKglif),
+/// with the overrides of `options`.
+pub fn stroke_variable(path: &Glif<()>, options: &VwsOptions) -> Result<Glif<()>, StrokeError> {
+    glif_io::require_contours(path)?;
+    let (skeleton, vws_contours) = make_vws_contours(path, options)?;
+
+    let stroke = || stroke_vws_contours(&skeleton, &vws_contours, &options.vws_settings, options.segmentwise);
+    let mut output_outline = catch_geometry(stroke)?;
+    if let (Some(join), Some(skeleton), false) = (options.join_custom.as_ref(), skeleton.outline.as_ref(), options.segmentwise) {
+        let width = |c: usize, p: usize| {
+            let handle = &vws_contours[c].handles[p];
+            handle.left_offset + handle.right_offset
+        };
+        output_outline = custom_join::add_custom_joins(&output_outline, skeleton, join, width, &options.boolean)?;
+    }
+    Ok(Glif {
+        outline: Some(output_outline),
+        ..path.clone()
+    })
+}
+
+/// Instead of stroking `path`, attaches its variable width stroke data (with the overrides of `options`)
+/// to each of its contours as an operation, for further editing in MFEKglif. The operation has no room
+/// for a custom join, which is left out.
+pub fn stroke_variable_glifjson(path: &Glif<()>, options: &VwsOptions) -> Result<MFEKGlif<()>, StrokeError> {
+    let (skeleton, vws_contours) = make_vws_contours(path, options)?;
+    Ok(attach_vws_operations(&skeleton, &vws_contours))
+}
+
+pub fn vws_cli(matches: &clap::ArgMatches) -> Result<(), StrokeError> {
+    let input_file = matches.value_of_os("input").unwrap();
+    let output_file = matches.value_of_os("output").unwrap();
+    let input_options = InputOptions::from_matches(matches);
+    let startcap: Option<CapType> = matches.value_of("startcap").map(|c| parse_arg("startcap", c)).transpose()?;
+    let endcap: Option<CapType> = matches.value_of("endcap").map(|c| parse_arg("endcap", c)).transpose()?;
+    let (jointype, join_custom) = match matches.value_of("jointype") {
+        Some(j) => {
+            let (jointype, join_custom) = custom_join::join_if_requested(j, &input_options)?;
+            (Some(jointype), join_custom)
+        }
+        None => (None, None),
+    };
+    let (left, right) = if matches.is_present("left") {
+        (
+            Some(matches.value_of("left").unwrap().parse().unwrap()),
+            Some(matches.value_of("right").unwrap().parse().unwrap()),
+        )
+    } else if let Some(width) = matches.value_of("width") {
+        let width: f64 = width.parse().unwrap();
+        (Some(width / 2.0), Some(width / 2.0))
+    } else {
+        (None, None)
+    };
+    let default_interpolation = match matches.value_of("interpolation").unwrap() {
+        "linear" => InterpolationType::Linear,
+        "none" => InterpolationType::Null,
+        _ => unreachable!(),
+    };
+
+    let cap_custom = |ct: Option<CapType>, arg: &str| match ct {
+        Some(ct) => custom_cap_if_requested(ct, matches.value_of(arg).unwrap(), &input_options),
+        None => Ok(None),
+    };
+    let options = VwsOptions {
+        vws_settings: VWSSettings {
+            cap_custom_start: cap_custom(startcap, "startcap")?,
+            cap_custom_end: cap_custom(endcap, "endcap")?,
+        },
+        startcap,
+        endcap,
+        jointype,
+        join_custom,
+        remove_internal: matches.is_present("remove-internal"),
+        remove_external: matches.is_present("remove-external"),
+        segmentwise: matches.is_present("segmentwise"),
+        left,
+        right,
+        profile: matches.value_of_os("profile").map(WidthProfile::read).transpose()?,
+        default_interpolation,
+        width_curve: matches.value_of("width-curve").unwrap().parse().map_err(StrokeError::Usage)?,
+        width_samples: matches.value_of("width-samples").unwrap().parse().unwrap(),
+        pen: matches.value_of("pen-width").map(|width| {
+            let ramp = |arg: &str| matches.value_of(arg).unwrap().parse::<Ramp>().unwrap(); // validated by clap
+            Pen {
+                width: width.parse().unwrap(),
+                thickness: ramp("pen-thickness"),
+                angle: ramp("pen-angle"),
+                model: match matches.value_of("pressure") {
+                    Some(pressure) => PenModel::Expansion { pressure: pressure.parse().unwrap() },
+                    None => PenModel::Translation,
+                },
+            }
+        }),
+        boolean: BooleanOptions::from_matches(matches),
+    };
+    let preview = PreviewOptions::from_matches(matches)?;
+
+    if ufo::is_ufo(input_file) {
+        let widths = match (matches.value_of("pen-width"), options.left, options.right) {
+            (Some(pen_width), _, _) => format!("pen, {} wide", pen_width),
+            (None, Some(left), Some(right)) => format!("{} left, {} right", left, right),
+            _ => String::from("stored"),
+        };
+        let or_stored = |arg: &str| matches.value_of(arg).unwrap_or("stored");
+        let settings = format!(
+            "widths: {}\nprofile: {}\nwidth curve: {}\ncaps: {} start, {} end\njoin: {}\nsegmentwise: {}",
+            widths,
+            matches.value_of_os("profile").map(|p| FsPath::new(p).display().to_string()).unwrap_or_else(|| String::from("none")),
+            matches.value_of("width-curve").unwrap(),
+            or_stored("startcap"),
+            or_stored("endcap"),
+            or_stored("jointype"),
+            options.segmentwise
+        );
+        return ufo::stroke_ufo_cli(matches, input_file.as_ref(), output_file.as_ref(), &settings, |glif| stroke_variable(glif, &options));
+    }
+
+    proof::reject_for_glif(matches)?;
+
+    let input = glif_io::read_input(input_file, &input_options)?;
+
+    if glif_io::is_glifjson(output_file) {
+        glif_io::reject_for_glifjson(&[
+            ("A custom --jointype", options.join_custom.is_some()),
+            ("--remove-overlap", matches.is_present("remove-overlap")),
+        ])?;
+        let out = stroke_variable_glifjson(&input, &options).map_err(|e| e.in_file(input_file))?;
+        glif_io::write_glifjson(&out, output_file)?;
+        if let Some(preview) = &preview {
+            let out = stroke_variable(&input, &options).map_err(|e| e.in_file(input_file))?;
+            preview::write_preview(&input, &out, preview)?;
+        }
+        return Ok(());
+    }
+
+    let out = stroke_variable(&input, &options)
+        .and_then(|out| remove_overlap_if_requested(matches, out))
+        .map_err(|e| e.in_file(input_file))?;
+    if let Some(preview) = &preview {
+        preview::write_preview(&input, &out, preview)?;
+    }
+    glif_io::write_output(&out, matches.is_present("svg-skeleton").then_some(&input), output_file)
+}
+
+#[cfg(test)]
+mod tests {
+    use glifparser::{Point, PointType};
+
+    use super::*;
+    use crate::width_profile::{ProfilePosition, ProfileRow};
+
+    // A glif of one open, straight contour along the x axis, through points at 0, 100 and 200.
+    fn line() -> Glif<()> {
+        let mut path: Glif<()> = Glif::new();
+        let contour = [(0., PointType::Move), (100., PointType::Line), (200., PointType::Line)]
+            .iter()
+            .map(|&(x, ptype)| Point::from_x_y_type((x, 0.), ptype))
+            .collect();
+        path.outline = Some(vec![contour]);
+        path
+    }
+
+    fn row(contour: usize, at: f64, width: f64) -> ProfileRow {
+        ProfileRow {
+            contour,
+            position: ProfilePosition::Fraction(at),
+            left: width,
+            right: width,
+            tangent: 0.,
+            curve: None,
+        }
+    }
+
+    fn profiled(rows: Vec<ProfileRow>) -> VwsOptions {
+        VwsOptions {
+            profile: Some(WidthProfile {
+                path: "profile.csv".into(),
+                rows,
+            }),
+            ..VwsOptions::default()
+        }
+    }
+
+    #[test]
+    fn linear_profile_stops_between_points_are_kept() {
+        let (skeleton, vws_contours) = make_vws_contours(&line(), &profiled(vec![row(0, 0., 10.), row(0, 0.25, 50.), row(0, 1., 10.)])).unwrap();
+        let contour = &skeleton.outline.unwrap()[0];
+        assert_eq!(contour.len(), 4);
+        assert_eq!(contour[1].x, 50.);
+        assert_eq!(vws_contours[0].handles[1].left_offset, 50.);
+
+        // Stops on the points leave the contour as it is.
+        let (skeleton, _) = make_vws_contours(&line(), &profiled(vec![row(0, 0., 10.), row(0, 0.5, 50.)])).unwrap();
+        assert_eq!(skeleton.outline.unwrap()[0].len(), 3);
+    }
+
+    #[test]
+    fn profile_rows_past_the_glif_are_errors() {
+        let result = make_vws_contours(&line(), &profiled(vec![row(0, 0., 10.), row(1, 0., 10.)]));
+        assert!(matches!(result, Err(StrokeError::Profile { .. })));
+    }
 
-    let out = variable_width_stroke_glif(&input, settings);
-    let glifstring = glifparser::write(&out).unwrap(); // TODO: Proper error handling!
-    fs::write(output_string, glifstring).expect("Unable to write file");
+    #[test]
+    fn each_segment_takes_the_handles_of_its_ends() {
+        let (_, vws_contours) = make_vws_contours(&line(), &profiled(vec![row(0, 0., 10.), row(0, 0.5, 20.), row(0, 1., 30.)])).unwrap();
+        let widths = |j| segment_vws_contour(&vws_contours[0], j).handles.iter().map(|h| h.left_offset).collect::<Vec<_>>();
+        assert_eq!(widths(0), [10., 20.]);
+        assert_eq!(widths(1), [20., 30.]);
+        // Closing the contour, its last segment runs back to the first point.
+        assert_eq!(widths(2), [30., 10.]);
+    }
 }
@@ -0,0 +1,484 @@
+// Width profiles: the widths of a variable width stroke kept in a CSV or JSON file of their own, so
+// that they can live in version control apart from the skeleton glyphs.
+//
+// Each row gives the widths at one position along one contour: its index in the glif, the position
+// (a fraction of the contour's arc length, or an on-curve point index), the width to the left and to
+// the right, and a tangent offset. In CSV:
+//
+//     contour,position,left,right,tangent
+//     0,0,10,10,0
+//     0,0.5,40,40,0
+//     0,#7,10,10,0
+//
+// where `#7` is point 7, the header is optional (but must name the columns so if given), the tangent may
+// be left out, and lines beginning with # are comments. A sixth column may name the curve the widths
+// follow from that row to the next (see `WidthCurve`). In JSON, an array of objects with the keys
+// `contour`, `at` (a fraction) or `point` (an index), `left`, `right` and optionally `tangent` and
+// `curve`.
+//
+// VWS handles sit on the on-curve points, and MFEKmath only interpolates linearly between them. So the
+// contour gets a point at each row between its points, each point gets the widths interpolated at its
+// own arc length between the rows around it, and for the other curves the contour is then subdivided,
+// so that the handles are close enough together to follow the curve.
+
+use std::fs;
+use std::path::{Path as FsPath, PathBuf as FsPathBuf};
+use std::str::FromStr;
+
+use glifparser::glif::contour_operations::vws::{InterpolationType, VWSHandle};
+use glifparser::Contour;
+use serde_json::Value;
+
+use crate::bezier::point_arc_lengths;
+use crate::StrokeError;
+
+/// How the widths change from one handle (or profile row) to the next.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum WidthCurve {
+    Linear,
+    /// Slow to leave the first widths, then quick.
+    EaseIn,
+    /// Quick to leave the first widths, then slow.
+    EaseOut,
+    /// Slow at both ends.
+    EaseInOut,
+    /// A Catmull-Rom spline through the widths of all the handles, so they swell and thin smoothly.
+    CatmullRom,
+    /// The first widths, up to the next handle.
+    Hold,
+}
+
+impl WidthCurve {
+    /// The names [`WidthCurve::from_str`] accepts, for clap.
+    pub const NAMES: [&'static str; 6] = ["linear", "ease-in", "ease-out", "ease-in-out", "catmull-rom", "hold"];
+
+    /// The curve which, run backwards, is this one: ease-in for ease-out, and the reverse.
+    pub(crate) fn mirrored(self) -> Self {
+        match self {
+            WidthCurve::EaseIn => WidthCurve::EaseOut,
+            WidthCurve::EaseOut => WidthCurve::EaseIn,
+            curve => curve,
+        }
+    }
+}
+
+impl FromStr for WidthCurve {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Ok(match s.to_ascii_lowercase().as_str() {
+            "linear" => WidthCurve::Linear,
+            "ease-in" => WidthCurve::EaseIn,
+            "ease-out" => WidthCurve::EaseOut,
+            "ease-in-out" => WidthCurve::EaseInOut,
+            "catmull-rom" => WidthCurve::CatmullRom,
+            "hold" | "step" => WidthCurve::Hold,
+            _ => return Err(format!("unknown width curve {:?} (expected one of {})", s, WidthCurve::NAMES.join(", "))),
+        })
+    }
+}
+
+/// Where along a contour a profile row applies.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ProfilePosition {
+    /// A fraction of the contour's arc length, from 0 (its first point) to 1 (its end).
+    Fraction(f64),
+    /// An on-curve point, by index.
+    Point(usize),
+}
+
+/// One row of a width profile.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ProfileRow {
+    pub contour: usize,
+    pub position: ProfilePosition,
+    pub left: f64,
+    pub right: f64,
+    pub tangent: f64,
+    /// The curve from this row to the next, if not the one given for the whole run.
+    pub curve: Option<WidthCurve>,
+}
+
+/// The rows of a width profile file, see [`WidthProfile::read`].
+#[derive(Debug, Clone)]
+pub struct WidthProfile {
+    /// The file the profile was read from, for error messages.
+    pub path: FsPathBuf,
+    pub rows: Vec<ProfileRow>,
+}
+
+fn parse_position(s: &str) -> Result<ProfilePosition, String> {
+    match s.strip_prefix('#') {
+        Some(point) => point.trim().parse().map(ProfilePosition::Point).map_err(|_| format!("bad point index {:?}", s)),
+        None => s.parse().map(ProfilePosition::Fraction).map_err(|_| format!("bad position {:?}", s)),
+    }
+}
+
+// Whether `fields` are the column names, as a CSV profile's first row may give them.
+fn is_header(fields: &[&str]) -> bool {
+    const COLUMNS: [&str; 6] = ["contour", "position", "left", "right", "tangent", "curve"];
+    (4..=6).contains(&fields.len()) && fields.iter().zip(COLUMNS).all(|(field, column)| field.eq_ignore_ascii_case(column))
+}
+
+fn parse_csv(text: &str) -> Result<Vec<ProfileRow>, String> {
+    let mut rows = vec![];
+    let lines = text.lines().enumerate().map(|(i, l)| (i + 1, l.trim())).filter(|(_, l)| !l.is_empty() && !l.starts_with('#'));
+    for (n, (lineno, line)) in lines.enumerate() {
+        let fields: Vec<&str> = line.split(',').map(str::trim).collect();
+        if n == 0 && is_header(&fields) {
+            continue;
+        }
+        if !(4..=6).contains(&fields.len()) {
+            return Err(format!("line {}: expected 4 to 6 fields, found {}", lineno, fields.len()));
+        }
+        let number = |i: usize| fields[i].parse::<f64>().map_err(|_| format!("line {}: bad number {:?}", lineno, fields[i]));
+        rows.push(ProfileRow {
+            contour: fields[0].parse().map_err(|_| format!("line {}: bad contour index {:?}", lineno, fields[0]))?,
+            position: parse_position(fields[1]).map_err(|e| format!("line {}: {}", lineno, e))?,
+            left: number(2)?,
+            right: number(3)?,
+            tangent: if fields.len() >= 5 { number(4)? } else { 0. },
+            curve: match fields.get(5) {
+                Some(curve) => Some(curve.parse().map_err(|e| format!("line {}: {}", lineno, e))?),
+                None => None,
+            },
+        });
+    }
+    Ok(rows)
+}
+
+fn parse_json(text: &str) -> Result<Vec<ProfileRow>, String> {
+    let json: Value = serde_json::from_str(text).map_err(|e| e.to_string())?;
+    let array = json.as_array().ok_or_else(|| String::from("expected an array of rows"))?;
+    array
+        .iter()
+        .enumerate()
+        .map(|(i, row)| {
+            let number = |key: &str| row.get(key).and_then(Value::as_f64);
+            let required = |key: &str| number(key).ok_or_else(|| format!("row {}: no number {:?}", i, key));
+            let position = match (number("at"), row.get("point").and_then(Value::as_u64)) {
+                (Some(at), None) => ProfilePosition::Fraction(at),
+                (None, Some(point)) => ProfilePosition::Point(point as usize),
+                _ => return Err(format!(r#"row {}: needs either "at" or "point""#, i)),
+            };
+            Ok(ProfileRow {
+                contour: row
+                    .get("contour")
+                    .and_then(Value::as_u64)
+                    .ok_or_else(|| format!(r#"row {}: no contour index "contour""#, i))? as usize,
+                position,
+                left: required("left")?,
+                right: required("right")?,
+                tangent: number("tangent").unwrap_or(0.),
+                curve: match row.get("curve").and_then(Value::as_str) {
+                    Some(curve) => Some(curve.parse().map_err(|e| format!("row {}: {}", i, e))?),
+                    None => None,
+                },
+            })
+        })
+        .collect()
+}
+
+/// The widths at one position along a contour, and the curve they follow to the next stop.
+#[derive(Debug, Clone, Copy)]
+pub(crate) struct Stop {
+    /// Fraction of the contour's arc length.
+    pub at: f64,
+    pub left: f64,
+    pub right: f64,
+    pub tangent: f64,
+    pub curve: WidthCurve,
+}
+
+/// Stops at each point of `contour`, with the widths of the corresponding handle.
+pub(crate) fn stops_from_handles(contour: &Contour<()>, handles: &[VWSHandle], curve: WidthCurve) -> Vec<Stop> {
+    let lengths = point_arc_lengths(contour);
+    let total = *lengths.last().unwrap();
+    lengths
+        .iter()
+        .zip(handles)
+        .map(|(length, h)| Stop {
+            at: if total > 0. { length / total } else { 0. },
+            left: h.left_offset,
+            right: h.right_offset,
+            tangent: h.tangent_offset,
+            curve,
+        })
+        .collect()
+}
+
+// Interpolates (left, right, tangent) at `t` between stops `j - 1` and `j`, `k` of the way between them.
+fn interpolate(stops: &[Stop], j: usize, k: f64) -> (f64, f64, f64) {
+    let value = |s: &Stop| (s.left, s.right, s.tangent);
+    let (a, b) = (value(&stops[j - 1]), value(&stops[j]));
+    let eased = |k: f64| {
+        let lerp = |x: f64, y: f64| x + (y - x) * k;
+        (lerp(a.0, b.0), lerp(a.1, b.1), lerp(a.2, b.2))
+    };
+    match stops[j - 1].curve {
+        WidthCurve::Linear => eased(k),
+        WidthCurve::EaseIn => eased(k * k),
+        WidthCurve::EaseOut => eased(1. - (1. - k) * (1. - k)),
+        WidthCurve::EaseInOut => eased(k * k * (3. - 2. * k)),
+        WidthCurve::Hold => a,
+        WidthCurve::CatmullRom => {
+            let before = value(&stops[j.saturating_sub(2)]);
+            let after = value(&stops[(j + 1).min(stops.len() - 1)]);
+            let spline = |p0: f64, p1: f64, p2: f64, p3: f64| {
+                0.5 * (2. * p1 + (p2 - p0) * k + (2. * p0 - 5. * p1 + 4. * p2 - p3) * k * k + (3. * p1 - p0 - 3. * p2 + p3) * k * k * k)
+            };
+            (
+                spline(before.0, a.0, b.0, after.0).max(0.),
+                spline(before.1, a.1, b.1, after.1).max(0.),
+                spline(before.2, a.2, b.2, after.2),
+            )
+        }
+    }
+}
+
+/// The VWS handles of `contour`, one per point and one for its end, with the widths of `stops` (sorted
+/// by position) at each.
+pub(crate) fn handles_at(stops: &[Stop], contour: &Contour<()>, interpolation: InterpolationType) -> Vec<VWSHandle> {
+    let lengths = point_arc_lengths(contour);
+    let total = *lengths.last().unwrap();
+    lengths
+        .iter()
+        .map(|length| {
+            let t = if total > 0. { length / total } else { 0. };
+            let (left, right, tangent) = match stops.iter().position(|s| s.at > t) {
+                Some(0) => (stops[0].left, stops[0].right, stops[0].tangent),
+                None => {
+                    let last = &stops[stops.len() - 1];
+                    (last.left, last.right, last.tangent)
+                }
+                Some(j) => interpolate(stops, j, (t - stops[j - 1].at) / (stops[j].at - stops[j - 1].at)),
+            };
+            VWSHandle {
+                left_offset: left,
+                right_offset: right,
+                tangent_offset: tangent,
+                interpolation,
+            }
+        })
+        .collect()
+}
+
+impl WidthProfile {
+    /// Reads a profile from a .json file, or from a CSV file (any other extension).
+    pub fn read<P: AsRef<FsPath>>(path: P) -> Result<Self, StrokeError> {
+        let path = path.as_ref();
+        let text = fs::read_to_string(path).map_err(|source| StrokeError::Read { path: path.to_path_buf(), source })?;
+        let is_json = path.extension().map(|e| e.eq_ignore_ascii_case("json")).unwrap_or(false);
+        let rows = if is_json { parse_json(&text) } else { parse_csv(&text) };
+        let rows = rows.map_err(|message| StrokeError::Profile { path: path.to_path_buf(), message })?;
+        if let Some(row) = rows.iter().find(|r| matches!(r.position, ProfilePosition::Fraction(f) if !(0. ..=1.).contains(&f))) {
+            return Err(StrokeError::Profile {
+                path: path.to_path_buf(),
+                message: format!("contour {}: position {:?} isn't between 0 and 1", row.contour, row.position),
+            });
+        }
+        Ok(WidthProfile { path: path.to_path_buf(), rows })
+    }
+
+    /// Fails if any row is of a contour past the `contours` a glif has.
+    pub(crate) fn check_contours(&self, contours: usize) -> Result<(), StrokeError> {
+        match self.rows.iter().find(|r| r.contour >= contours) {
+            Some(row) => Err(StrokeError::Profile {
+                path: self.path.clone(),
+                message: format!("there's no contour {} (the glif has {})", row.contour, contours),
+            }),
+            None => Ok(()),
+        }
+    }
+
+    /// The stops of the contour at index `cidx`, sorted by position, with `curve` for rows which don't
+    /// name their own. `None` if the profile has no rows for this contour.
+    pub(crate) fn stops(&self, cidx: usize, contour: &Contour<()>, curve: WidthCurve) -> Result<Option<Vec<Stop>>, StrokeError> {
+        let lengths = point_arc_lengths(contour);
+        let total = *lengths.last().unwrap();
+
+        let mut stops = vec![];
+        for row in self.rows.iter().filter(|r| r.contour == cidx) {
+            let at = match row.position {
+                ProfilePosition::Fraction(f) => f,
+                ProfilePosition::Point(i) if i < contour.len() => {
+                    if total > 0. {
+                        lengths[i] / total
+                    } else {
+                        0.
+                    }
+                }
+                ProfilePosition::Point(i) => {
+                    return Err(StrokeError::Profile {
+                        path: self.path.clone(),
+                        message: format!("contour {} has no point {} (it has {})", cidx, i, contour.len()),
+                    })
+                }
+            };
+            stops.push(Stop {
+                at,
+                left: row.left,
+                right: row.right,
+                tangent: row.tangent,
+                curve: row.curve.unwrap_or(curve),
+            });
+        }
+        stops.sort_by(|a, b| a.at.total_cmp(&b.at));
+        Ok((!stops.is_empty()).then_some(stops))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::bezier;
+    use glifparser::{Point, PointType};
+
+    // An open, straight contour along the x axis, through points at 0, 100 and 200.
+    fn line() -> Contour<()> {
+        [(0., PointType::Move), (100., PointType::Line), (200., PointType::Line)]
+            .iter()
+            .map(|&(x, ptype)| Point::from_x_y_type((x, 0.), ptype))
+            .collect()
+    }
+
+    fn profile(csv: &str) -> WidthProfile {
+        WidthProfile {
+            path: FsPathBuf::from("profile.csv"),
+            rows: parse_csv(csv).unwrap(),
+        }
+    }
+
+    fn left_widths(handles: &[VWSHandle]) -> Vec<f64> {
+        handles.iter().map(|h| h.left_offset).collect()
+    }
+
+    #[test]
+    fn csv_rows() {
+        let rows = parse_csv("contour,position,left,right,tangent\n# a comment\n\n0,0.5,10,20\n1,#2,30,40,5,ease-in\n").unwrap();
+        assert_eq!(rows.len(), 2);
+        assert_eq!(rows[0].position, ProfilePosition::Fraction(0.5));
+        assert_eq!((rows[0].left, rows[0].right, rows[0].tangent, rows[0].curve), (10., 20., 0., None));
+        assert_eq!(rows[1].contour, 1);
+        assert_eq!(rows[1].position, ProfilePosition::Point(2));
+        assert_eq!((rows[1].tangent, rows[1].curve), (5., Some(WidthCurve::EaseIn)));
+        // Without a header.
+        assert_eq!(parse_csv("0,0,1,1\n").unwrap().len(), 1);
+    }
+
+    #[test]
+    fn a_bad_first_row_is_an_error_not_a_header() {
+        let error = parse_csv("# widths\nx,0,10,10\n0,1,10,10\n").unwrap_err();
+        assert!(error.starts_with("line 2:"), "{}", error);
+        assert!(parse_csv("contour,position,left\n").is_err());
+        assert!(parse_csv("0,0,10\n").unwrap_err().starts_with("line 1:"));
+    }
+
+    #[test]
+    fn json_rows() {
+        let rows = parse_json(r#"[{"contour": 0, "at": 0.25, "left": 10, "right": 20, "curve": "hold"}, {"contour": 0, "point": 1, "left": 1, "right": 2, "tangent": 3}]"#).unwrap();
+        assert_eq!(rows[0].position, ProfilePosition::Fraction(0.25));
+        assert_eq!(rows[0].curve, Some(WidthCurve::Hold));
+        assert_eq!(rows[1].position, ProfilePosition::Point(1));
+        assert_eq!(rows[1].tangent, 3.);
+        assert!(parse_json(r#"[{"contour": 0, "left": 1, "right": 1}]"#).is_err());
+        assert!(parse_json(r#"[{"contour": 0, "at": 0, "point": 0, "left": 1, "right": 1}]"#).is_err());
+    }
+
+    #[test]
+    fn stops_are_sorted_and_placed_by_arc_length() {
+        let profile = profile("0,1,30,30\n0,#1,20,20\n0,0,10,10,0,ease-out\n1,0,99,99\n");
+        let stops = profile.stops(0, &line(), WidthCurve::Linear).unwrap().unwrap();
+        assert_eq!(stops.iter().map(|s| s.at).collect::<Vec<_>>(), [0., 0.5, 1.]);
+        assert_eq!(stops.iter().map(|s| s.curve).collect::<Vec<_>>(), [WidthCurve::EaseOut, WidthCurve::Linear, WidthCurve::Linear]);
+        assert!(profile.stops(2, &line(), WidthCurve::Linear).unwrap().is_none());
+    }
+
+    #[test]
+    fn rows_past_the_glif_are_errors() {
+        assert!(matches!(profile("0,#3,10,10\n").stops(0, &line(), WidthCurve::Linear), Err(StrokeError::Profile { .. })));
+        let profile = profile("0,0,10,10\n1,0,10,10\n");
+        assert!(profile.check_contours(2).is_ok());
+        assert!(matches!(profile.check_contours(1), Err(StrokeError::Profile { .. })));
+    }
+
+    #[test]
+    fn handles_interpolate_between_stops() {
+        let stops = profile("0,0,10,10\n0,1,30,30\n").stops(0, &line(), WidthCurve::Linear).unwrap().unwrap();
+        // One handle per point, and one for the end.
+        let handles = handles_at(&stops, &line(), InterpolationType::Linear);
+        assert_eq!(left_widths(&handles), [10., 20., 30., 30.]);
+    }
+
+    #[test]
+    fn a_stop_between_points_gets_a_point_of_its_own() {
+        let stops = profile("0,0,10,10\n0,0.25,50,50\n0,1,10,10\n").stops(0, &line(), WidthCurve::Linear).unwrap().unwrap();
+        // Sampled at the points alone, the stop is lost.
+        assert!(!left_widths(&handles_at(&stops, &line(), InterpolationType::Linear)).contains(&50.));
+        let split = bezier::split_at_lengths(&line(), &stops.iter().map(|s| s.at * 200.).collect::<Vec<_>>());
+        assert_eq!(split.len(), 4);
+        assert_eq!((split[1].x, split[1].y), (50., 0.));
+        let handles = handles_at(&stops, &split, InterpolationType::Linear);
+        assert_eq!(left_widths(&handles)[..3], [10., 50., 10. + 40. * 2. / 3.]);
+    }
+
+    // Stops at 0, 0.5 and 1 of widths 0, 10 and 0 on the left, leaving the first by `curve`.
+    fn rise(curve: WidthCurve) -> Vec<Stop> {
+        [(0., 0.), (0.5, 10.), (1., 0.)]
+            .iter()
+            .map(|&(at, width)| Stop {
+                at,
+                left: width,
+                right: 2. * width,
+                tangent: 0.,
+                curve,
+            })
+            .collect()
+    }
+
+    #[test]
+    fn every_curve_runs_between_the_widths() {
+        for (curve, quarter) in [
+            (WidthCurve::Linear, 2.5),
+            (WidthCurve::EaseIn, 0.625),
+            (WidthCurve::EaseOut, 4.375),
+            (WidthCurve::EaseInOut, 1.5625),
+            (WidthCurve::Hold, 0.),
+        ] {
+            let stops = rise(curve);
+            assert_eq!(interpolate(&stops, 1, 0.), (0., 0., 0.), "{:?}", curve);
+            assert_eq!(interpolate(&stops, 1, 0.25), (quarter, 2. * quarter, 0.), "{:?}", curve);
+            if curve != WidthCurve::Hold {
+                assert_eq!(interpolate(&stops, 1, 1.), (10., 20., 0.), "{:?}", curve);
+            }
+        }
+    }
+
+    #[test]
+    fn catmull_rom_passes_through_the_widths_and_overshoots_none_below_zero() {
+        let stops = rise(WidthCurve::CatmullRom);
+        assert_eq!(interpolate(&stops, 1, 0.).0, 0.);
+        assert_eq!(interpolate(&stops, 1, 1.).0, 10.);
+        // Unlike a line, it's flattened towards the peak at 10, which it reaches with no slope.
+        let (before_peak, _, _) = interpolate(&stops, 1, 0.9);
+        assert!(before_peak > 9.5 && before_peak < 10.);
+        // Widths never go negative, even where the spline would.
+        let dip = [(0., 10.), (0.5, 0.), (1., 10.)].map(|(at, width)| Stop {
+            at,
+            left: width,
+            right: width,
+            tangent: 0.,
+            curve: WidthCurve::CatmullRom,
+        });
+        assert!((0..=10).all(|i| interpolate(&dip, 1, i as f64 / 10.).0 >= 0.));
+    }
+
+    #[test]
+    fn mirrored_curves_swap_easing() {
+        assert_eq!(WidthCurve::EaseIn.mirrored(), WidthCurve::EaseOut);
+        assert_eq!(WidthCurve::EaseOut.mirrored(), WidthCurve::EaseIn);
+        assert_eq!(WidthCurve::CatmullRom.mirrored(), WidthCurve::CatmullRom);
+        assert_eq!("step".parse::<WidthCurve>(), Ok(WidthCurve::Hold));
+        assert!("bouncy".parse::<WidthCurve>().is_err());
+    }
+}